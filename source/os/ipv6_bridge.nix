@@ -93,7 +93,9 @@ in
           '';
         systemd.services.glue_mangle_ip_configure = {
           wantedBy = [ "nftables.service" ];
-          serviceConfig.Type = "simple";
+          # Ready once the queue is bound and br0 has a global IP, which may take a while
+          serviceConfig.Type = "notify";
+          serviceConfig.TimeoutStartSec = "infinity";
           startLimitIntervalSec = 0;
           serviceConfig.Restart = "always";
           serviceConfig.RestartSec = 60;
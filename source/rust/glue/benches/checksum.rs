@@ -0,0 +1,64 @@
+//! Checksum throughput on various packet sizes. Run with `cargo bench --bench
+//! checksum`.
+use {
+    criterion::{
+        black_box,
+        criterion_group,
+        criterion_main,
+        BenchmarkId,
+        Criterion,
+        Throughput,
+    },
+    glue::ra_dns::{
+        checksum_roll,
+        icmpv6_udp_checksum,
+    },
+};
+
+/// IPv6 payload sizes: a small RA, a typical DHCPv6 reply, a full ethernet frame and
+/// a jumbo frame.
+const PACKET_SIZES: &[usize] = &[64, 512, 1460, 8960];
+
+/// An IPv6 UDP packet with a `payload_size` byte payload.
+fn udp_packet(payload_size: usize) -> Vec<u8> {
+    let mut out = vec![0x60, 0, 0, 0];
+    out.extend((payload_size as u16).to_be_bytes());
+    out.extend([17, 0x40]);
+    out.extend([0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    out.extend([0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+    out.extend((0 .. payload_size).map(|i| i as u8));
+    return out;
+}
+
+fn bench_icmpv6_udp_checksum(c: &mut Criterion) {
+    let mut group = c.benchmark_group("icmpv6_udp_checksum");
+    for size in PACKET_SIZES {
+        let packet = udp_packet(*size);
+        group.throughput(Throughput::Bytes(packet.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &packet, |b, packet| {
+            b.iter(|| {
+                return icmpv6_udp_checksum(black_box(packet)).unwrap();
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_checksum_roll(c: &mut Criterion) {
+    let mut group = c.benchmark_group("checksum_roll");
+    for size in [64 * 1024, 1024 * 1024] {
+        let data = (0 .. size).map(|i| i as u8).collect::<Vec<_>>();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter(|| {
+                let mut sum64 = 0u64;
+                checksum_roll(&mut sum64, black_box(data));
+                return sum64;
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_icmpv6_udp_checksum, bench_checksum_roll);
+criterion_main!(benches);
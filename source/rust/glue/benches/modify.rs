@@ -0,0 +1,60 @@
+//! Compare `modify` (allocating per packet) with `modify_into` (reusing a buffer).
+//! Run with `cargo bench --bench modify`. Times are per packet, throughput is in
+//! bytes of the source packet.
+use {
+    criterion::{
+        black_box,
+        criterion_group,
+        criterion_main,
+        Criterion,
+        Throughput,
+    },
+    glue::ra_dns::{
+        modify,
+        modify_into,
+        ModifyConfig,
+    },
+    std::net::{
+        Ipv4Addr,
+        Ipv6Addr,
+    },
+};
+
+/// The fuzz corpus seeds, without the fuzz config header.
+const FUZZ_HEADER_SIZE: usize = 21;
+const PACKETS: &[(&str, &[u8])] = &[
+    ("ra", include_bytes!("../fuzz/corpus/modify/ra1")),
+    ("dhcp6", include_bytes!("../fuzz/corpus/modify/dhcp1")),
+    ("dhcp4", include_bytes!("../fuzz/corpus/modify/dhcp4_ack")),
+];
+
+fn bench_modify(c: &mut Criterion) {
+    let config = ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 53)],
+        dns_search: vec!["home.arpa".to_string()],
+        rdnss_lifetime: Some(600),
+        dns_ips_v4: vec![Ipv4Addr::new(10, 0, 0, 53)],
+        ..Default::default()
+    };
+    for (name, packet) in PACKETS {
+        let packet = &packet[FUZZ_HEADER_SIZE..];
+        let mut group = c.benchmark_group(*name);
+        group.throughput(Throughput::Bytes(packet.len() as u64));
+        group.bench_function("modify", |b| {
+            b.iter(|| {
+                return modify(black_box(packet), &config).unwrap();
+            });
+        });
+        let mut scratch = vec![];
+        group.bench_function("modify_into", |b| {
+            b.iter(|| {
+                modify_into(&mut scratch, black_box(packet), &config).unwrap();
+                return black_box(scratch.len());
+            });
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_modify);
+criterion_main!(benches);
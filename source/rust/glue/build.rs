@@ -0,0 +1,52 @@
+//! Embed the git commit and build time for `mangle_ip_configure --version`.
+use {
+    std::{
+        env,
+        process::Command,
+        time::{
+            SystemTime,
+            UNIX_EPOCH,
+        },
+    },
+};
+
+/// Run git, `None` if it's not installed or this isn't a checkout.
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let out = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    if out.is_empty() {
+        return None;
+    }
+    return Some(out);
+}
+
+fn main() {
+    // Rerun whenever the binary is rebuilt for a source change, so the timestamp and
+    // dirty state stay current
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    // And when a commit is made, checked out or staged
+    if let Some(git_dir) = git(&["rev-parse", "--absolute-git-dir"]) {
+        println!("cargo:rerun-if-changed={}/HEAD", git_dir);
+        println!("cargo:rerun-if-changed={}/logs/HEAD", git_dir);
+        println!("cargo:rerun-if-changed={}/index", git_dir);
+    }
+    let commit = git(&["describe", "--always", "--dirty"]).unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=GLUE_GIT_COMMIT={}", commit);
+
+    // Seconds since the epoch, or `SOURCE_DATE_EPOCH` for reproducible builds
+    let timestamp = match env::var("SOURCE_DATE_EPOCH") {
+        Ok(t) => t,
+        Err(_) => match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(t) => t.as_secs().to_string(),
+            Err(_) => "unknown".to_string(),
+        },
+    };
+    println!("cargo:rustc-env=GLUE_BUILD_TIMESTAMP={}", timestamp);
+}
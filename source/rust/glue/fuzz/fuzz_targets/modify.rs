@@ -0,0 +1,62 @@
+//! Run with `cargo +nightly fuzz run modify`. The input is a small config header
+//! followed by the packet, see `corpus/modify` for examples.
+#![no_main]
+
+use {
+    glue::ra_dns::{
+        modify,
+        ModifyConfig,
+        Pref64,
+    },
+    libfuzzer_sys::fuzz_target,
+    std::net::{
+        Ipv4Addr,
+        Ipv6Addr,
+    },
+};
+
+/// Flags (1 byte), lifetime/mtu (4 bytes), DNS IP (16 bytes, the last 4 are also
+/// the IPv4 DNS IP)
+const HEADER_SIZE: usize = 1 + 4 + 16;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((header, packet)) = data.split_first_chunk::<HEADER_SIZE>() else {
+        return;
+    };
+    let flags = header[0];
+    let number = u32::from_be_bytes(header[1 .. 5].try_into().unwrap());
+    let ip = Ipv6Addr::from(<[u8; 16]>::try_from(&header[5 .. 21]).unwrap());
+    let config = ModifyConfig {
+        dns_ips: vec![ip; 1 + (flags as usize & 0x3)],
+        dns_search: if flags & 0x4 != 0 {
+            vec!["home.arpa".to_string()]
+        } else {
+            vec![]
+        },
+        rdnss_lifetime: (flags & 0x8 != 0).then_some(number),
+        min_rdnss_lifetime: (number & 0x1000 != 0).then_some(number >> 16),
+        max_rdnss_lifetime: (number & 0x2000 != 0).then_some(number >> 20),
+        mtu: (flags & 0x10 != 0).then_some(number),
+        keep_existing_dns: flags & 0x20 != 0,
+        dhcp_message_types: (flags & 0x40 != 0).then(|| vec![number as u8]),
+        dns_ips_v4: if flags & 0x80 != 0 {
+            vec![Ipv4Addr::from(<[u8; 4]>::try_from(&header[17 .. 21]).unwrap())]
+        } else {
+            vec![]
+        },
+        set_other_flag: (number & 0x100 != 0).then_some(number & 0x200 != 0),
+        set_managed_flag: (number & 0x400 != 0).then_some(number & 0x800 != 0),
+        set_other_flag_on_rewrite: number & 0x10000 != 0,
+        pref64: (number & 0x4000 != 0).then(|| Pref64::new(ip, 96)).flatten(),
+        dns_options_at_end: number & 0x8000 != 0,
+        verify_checksum: number & 0x1 != 0,
+        consolidate_rdnss: number & 0x2 != 0,
+        cur_hop_limit: (number & 0x4 != 0).then_some((number >> 24) as u8),
+        router_lifetime: (number & 0x8 != 0).then_some((number >> 16) as u16),
+        reachable_time: (number & 0x10 != 0).then_some(number),
+        retrans_timer: (number & 0x20 != 0).then_some(number),
+        dhcp_ports: (number & 0x40 != 0).then(|| vec![(number >> 16) as u16, 547]),
+        max_packet: (number & 0x80 != 0).then_some((number >> 20) as usize),
+    };
+    _ = modify(packet, &config);
+});
@@ -3,12 +3,95 @@ use {
         vark,
         Aargvark,
     },
-    glue::unstable_ip::UnstableIpv6,
+    glue::{
+        ra_dns::{
+            list_options,
+            modify_into,
+            parse::parse,
+            ra_prefix,
+            ra_source,
+            ModifyConfig,
+            ModifyError,
+            Pref64,
+            DHCP_ADVERTISE,
+            DHCP_REPLY,
+        },
+        unstable_ip::UnstableIpv6,
+    },
     loga::{
+        ea,
         fatal,
+        DebugDisplay,
+        ErrContext,
         ResultContext,
     },
-    manglelib::modify,
+    manglelib::{
+        control::{
+            self,
+            ControlHandler,
+        },
+        dnscommand::run_dns_command,
+        events::{
+            event,
+            EventLog,
+            LogFormat,
+            LogLevel,
+            LogSink,
+            LogTarget,
+        },
+        health::{
+            self,
+            Health,
+        },
+        hook::spawn_ip_change_hook,
+        inspect::{
+            describe,
+            parse_hex,
+        },
+        is_transient_recv_error,
+        metrics::{
+            self,
+            IfaceMetrics,
+            Metrics,
+        },
+        netlink::{
+            ipv6_addrs,
+            watch_ipv6_addr_changes,
+            AddrInfo,
+        },
+        nftcheck,
+        nftgen::{
+            self,
+            NftGenConfig,
+        },
+        pcap::PcapOut,
+        rasource::RaSourceFilter,
+        ratelimit::RateLimiter,
+        reassembly::ReassemblingQueue,
+        reload::Reloadable,
+        replay::replay,
+        recv,
+        select::{
+            is_candidate,
+            parse_dns_ip,
+            parse_interface_dns,
+            select_ip,
+            AddressSelect,
+            Ipv6Prefix,
+        },
+        spawn_signal_handler,
+        startup::{
+            StartupTimeoutAction,
+            StartupWatch,
+        },
+        supervise::{
+            Supervisor,
+            WatcherRestart,
+        },
+        systemd::Notifier,
+        PacketQueue,
+        QueuedPacket,
+    },
     network_interface::{
         NetworkInterface,
         NetworkInterfaceConfig,
@@ -17,152 +100,2040 @@ use {
         Queue,
         Verdict,
     },
+    serde::{
+        Deserialize,
+        Deserializer,
+    },
     std::{
+        collections::{
+            HashMap,
+            HashSet,
+        },
+        fs::{
+            read,
+            File,
+        },
+        io::{
+            BufReader,
+            BufWriter,
+        },
+        net::{
+            Ipv4Addr,
+            Ipv6Addr,
+            SocketAddr,
+        },
         panic,
+        path::PathBuf,
         process,
+        str::FromStr,
         sync::{
+            atomic::{
+                AtomicBool,
+                AtomicU64,
+                Ordering,
+            },
+            mpsc::{
+                channel,
+                Receiver,
+                RecvTimeoutError,
+                Sender,
+            },
             Arc,
             Mutex,
         },
         thread::{
+            scope,
             sleep,
             spawn,
         },
-        time::Duration,
+        time::{
+            Duration,
+            Instant,
+            SystemTime,
+        },
     },
 };
 
 mod manglelib;
 
-#[derive(Aargvark)]
+/// How long an IP must be stable before running `--on-ip-change`.
+const HOOK_DEBOUNCE: Duration = Duration::from_secs(5);
+
+/// How long `--dns-command` can run before it's killed and treated as no IP.
+const DNS_COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait before retrying after failing to list interfaces.
+const LIST_RETRY_PERIOD: Duration = Duration::from_secs(1);
+
+/// `--health-listen` reports an interface watcher or queue worker stalled after this
+/// many recheck periods without a loop.
+const HEALTH_STALL_PERIODS: u64 = 3;
+
+/// `--max-packet` if not set, the minimum IPv6 MTU.
+pub(crate) const DEFAULT_MAX_PACKET: usize = 1280;
+
+/// How often to check for an IP while waiting for `--startup-timeout`.
+const STARTUP_CHECK_PERIOD: Duration = Duration::from_secs(1);
+
+/// How long to wait before restarting a dead interface watcher, with `--watcher-restart
+/// respawn`.
+const WATCHER_RESPAWN_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Aargvark, Deserialize, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+enum ParseFailureAction {
+    /// Drop the packet
+    Drop,
+    /// Deliver the packet unmodified
+    Accept,
+}
+
+#[derive(Aargvark, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "kebab-case")]
+enum QueueFail {
+    /// Accept packets, unmodified (un-rewritten RAs reach clients)
+    Open,
+    /// Drop packets (clients miss RAs until the next one)
+    Closed,
+}
+
+#[derive(Aargvark, Deserialize, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+enum ModifiedVerdict {
+    /// Send the rewritten packet through the hook again, from the first rule of the
+    /// first chain. Your rules must skip packets with `--nf-mark` or it will be queued
+    /// again.
+    Repeat,
+    /// Let the rewritten packet continue to the next chain or hook, skipping the rest
+    /// of the queueing chain. Cheaper, but rules after the queue rule in that chain
+    /// won't see the packet.
+    Accept,
+}
+
+impl ModifiedVerdict {
+    fn verdict(self) -> Verdict {
+        match self {
+            ModifiedVerdict::Repeat => return Verdict::Repeat,
+            ModifiedVerdict::Accept => return Verdict::Accept,
+        }
+    }
+}
+
+#[derive(Aargvark, Deserialize, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+enum FlagSetting {
+    True,
+    False,
+    /// Keep the router's value
+    Unchanged,
+}
+
+impl FlagSetting {
+    fn to_option(self) -> Option<bool> {
+        match self {
+            FlagSetting::True => return Some(true),
+            FlagSetting::False => return Some(false),
+            FlagSetting::Unchanged => return None,
+        }
+    }
+}
+
+#[derive(Aargvark, Deserialize, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+enum RdnssPosition {
+    /// Where the router's RDNSS (or DNSSL) was, keeping the option order
+    Original,
+    /// After all other options
+    End,
+}
+
+#[derive(Aargvark, Deserialize, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+enum DhcpMessageType {
+    Advertise,
+    Reply,
+}
+
+/// Also used as the config file format - keys are the flag names without the
+/// leading `--`.
+#[derive(Aargvark, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
 struct Args {
-    /// Name of address to get ipv6 address from to add to RDNSS
-    #[vark(flag = "--interface")]
-    interface: String,
-    /// How often (seconds) to recheck the interface for a new IP. Defaults to 60s.
+    /// JSON file with any of these settings. Flags on the command line take
+    /// precedence. On SIGHUP it's re-read and the DNS, RA and address selection
+    /// settings are replaced; queue, interface, verdict and logging settings need a
+    /// restart.
+    #[serde(skip)]
+    config: Option<PathBuf>,
+    /// Names of interfaces to get ipv6 addresses from to add to RDNSS. Each packet gets
+    /// the address of the interface it's leaving by. If not specified, only the
+    /// `--dns-ip` addresses are advertised.
+    interface: Option<Vec<String>>,
+    /// Like `--interface`, but by index (ifindex) rather than name. Can't be combined
+    /// with `--interface`.
+    interface_index: Option<Vec<u32>>,
+    /// How often (seconds) to recheck the interface for a new IP, in case an address
+    /// change notification is missed. Defaults to 60s.
     recheck_period: Option<u64>,
-    /// Which netfilter queue to read from
-    #[vark(flag = "--nf-queue")]
-    nf_queue: u16,
-    /// Mark packets after modification - you must use this in your nftables rule to
-    /// prevent re-processing the same packet (feedback loop).
-    #[vark(flag = "--nf-mark")]
-    nf_mark: u32,
+    /// Which netfilter queues to read from. Each queue is processed by its own thread.
+    nf_queue: Option<Vec<u16>>,
+    /// A range of netfilter queues to read from, `start:end` inclusive, like
+    /// nftables' `queue num start-end`. For use with nftables' `fanout`.
+    nf_queue_range: Option<String>,
+    /// Mark packets after modification - with `--verdict repeat` you must use this in
+    /// your nftables rule to prevent re-processing the same packet (feedback loop).
+    /// Required.
+    nf_mark: Option<u32>,
+    /// Only set these bits of the mark, keeping the rest of the packet's mark (ex: bits
+    /// other tools use). `--nf-mark` must be within the mask, and your nftables rule
+    /// should skip packets with `meta mark & mask == mark`. Defaults to the whole mark.
+    nf_mark_mask: Option<u32>,
+    /// How many bytes of each queued packet the kernel copies to us. Packets longer
+    /// than this would be truncated, so leave it at the maximum unless you know your
+    /// packets are small. Defaults to 65535.
+    copy_range: Option<u16>,
+    /// The most packets the kernel holds in each queue waiting for us. Defaults to the
+    /// kernel's (1024).
+    queue_maxlen: Option<u32>,
+    /// What the kernel does with packets when a queue is full. `open` lets
+    /// un-rewritten RAs (with the router's DNS) leak to clients, `closed` drops them.
+    /// Defaults to `closed`.
+    queue_fail: Option<QueueFail>,
+    /// The verdict for rewritten packets. Defaults to `repeat`.
+    verdict: Option<ModifiedVerdict>,
     /// Override/inject RA MTU
     mtu: Option<u32>,
+    /// Override the Cur Hop Limit in rewritten RAs. Defaults to the router's.
+    cur_hop_limit: Option<u8>,
+    /// Override the Router Lifetime (seconds) in rewritten RAs. 0 tells clients this
+    /// isn't a default router. Defaults to the router's.
+    router_lifetime: Option<u16>,
+    /// Override the Reachable Time (milliseconds) in rewritten RAs. Defaults to the
+    /// router's.
+    reachable_time: Option<u32>,
+    /// Override the Retrans Timer (milliseconds) in rewritten RAs. Defaults to the
+    /// router's.
+    retrans_timer: Option<u32>,
+    /// Override/inject the RA NAT64 prefix (PREF64, RFC 8781) as `addr/len` (ex:
+    /// `64:ff9b::/96`), for CLAT. The length must be 32, 40, 48, 56, 64 or 96. The
+    /// prefix's lifetime is the RA's router lifetime (after `--router-lifetime`).
+    pref64: Option<String>,
+    /// Where to put the rewritten RDNSS/DNSSL options in RAs. Defaults to `original`.
+    rdnss_position: Option<RdnssPosition>,
+    /// DNS server addresses to advertise. With `--interface` these are advertised after
+    /// the interface address (ex: a fallback resolver), otherwise they're advertised as
+    /// is. Link-local addresses can have a zone (ex: `fe80::1%eth0`), but are
+    /// non-standard and many clients ignore them.
+    dns_ip: Option<Vec<String>>,
+    /// Spread clients over several resolvers: rotate which of the advertised addresses
+    /// is listed first with each rewritten packet, still listing all of them.
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    dns_rotate: Option<()>,
+    /// Advertise a fixed address for packets leaving by an interface instead of an
+    /// interface address, as `interface=addr` (ex: `eth1=fd00:1::53`). The interfaces
+    /// must exist at startup. `--dns-ip` addresses are still advertised after it.
+    interface_dns: Option<Vec<String>>,
+    /// Only rewrite packets leaving by these interfaces, passing others through. The
+    /// interfaces must exist at startup.
+    out_interface: Option<Vec<String>>,
+    /// DNS server addresses to put in IPv4 DHCP offers/acks, replacing the server's.
+    /// Your nftables rule must also queue IPv4 DHCP (from udp port 67).
+    dns_ip4: Option<Vec<Ipv4Addr>>,
+    /// Only advertise interface addresses within this subnet (`addr/len`). If several
+    /// match, `--address-select` chooses. Applied after `--allow-ula`, so use a ULA
+    /// prefix here with `--allow-ula` to only advertise a ULA.
+    dns_prefix: Option<String>,
+    /// Also consider unique-local (`fc00::/7`) interface addresses, not just global
+    /// ones
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    allow_ula: Option<()>,
+    /// Also consider temporary (privacy extension) interface addresses. These rotate
+    /// every few hours, so clients will lose the resolver.
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    allow_temporary: Option<()>,
+    /// How to choose if an interface has several addresses that could be advertised,
+    /// after preferring ones that aren't deprecated, then ones that aren't temporary,
+    /// then ones in the prefix of the RAs being rewritten. The advertised address is
+    /// kept until it's gone or another is strictly better. Defaults to `lowest`.
+    address_select: Option<AddressSelect>,
+    /// Search domains to advertise in RAs (DNSSL) and DHCPv6 (domain search list),
+    /// replacing any the router sent
+    dns_search: Option<Vec<String>>,
+    /// Lifetime (seconds) to advertise in RDNSS/DNSSL options. Defaults to the lifetime
+    /// in the router's RDNSS option.
+    rdnss_lifetime: Option<u32>,
+    /// Raise shorter RDNSS/DNSSL lifetimes (from `--rdnss-lifetime` or the router) to
+    /// this many seconds, so clients don't lose DNS between RAs. Defaults to the
+    /// recheck period. A lifetime of 0 (withdrawing) is never raised.
+    min_rdnss_lifetime: Option<u32>,
+    /// Lower longer RDNSS/DNSSL lifetimes to this many seconds, so clients don't keep
+    /// using a stale address for long.
+    max_rdnss_lifetime: Option<u32>,
+    /// Set the Other configuration flag in RAs, telling clients to get other settings
+    /// (ex: DNS) via DHCPv6. RAs are rewritten if this changes the flag. If not
+    /// specified, the flag is set in RAs rewritten for DNS but other RAs are left
+    /// alone.
+    set_other_flag: Option<FlagSetting>,
+    /// Set the Managed address configuration flag in RAs, telling clients to get
+    /// addresses via DHCPv6. RAs are rewritten if this changes the flag. Defaults to
+    /// `unchanged`.
+    set_managed_flag: Option<FlagSetting>,
+    /// Advertise the router's DNS servers as well, before ours (RA RDNSS and DHCPv6)
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    keep_existing_rdnss: Option<()>,
+    /// With `--keep-existing-rdnss`, merge the router's RDNSS options and ours into a
+    /// single RDNSS option without duplicate addresses, rather than keeping each
+    /// option. Its lifetime is chosen as for ours (see `--rdnss-lifetime`).
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    consolidate_rdnss: Option<()>,
+    /// When an interface loses its IP, keep rewriting RAs for this many seconds with
+    /// the old IP and an RDNSS lifetime of 0, telling clients to stop using it right
+    /// away (RFC 6106), before dropping them. Only RAs that have an RDNSS option are
+    /// rewritten. No RAs are sent on our own, so this should be longer than the
+    /// router's RA interval for clients to see the withdrawal.
+    withdraw_on_loss: Option<u64>,
+    /// What to do with packets that can't be parsed or rewritten, including fragments that
+    /// can't be reassembled. Defaults to `accept`.
+    on_parse_failure: Option<ParseFailureAction>,
+    /// Only rewrite this many RAs per second from each router, bursting up to a
+    /// second's worth. Protects against amplifying RA floods.
+    max_ra_per_sec: Option<u32>,
+    /// What to do with RAs over `--max-ra-per-sec`. Defaults to `drop`.
+    on_rate_limit: Option<ParseFailureAction>,
+    /// What to do with packets leaving by an interface without a global IP (before the
+    /// first is found, or after it's lost). `drop` keeps clients from getting the
+    /// router's DNS, `accept` gives them the router's unmodified RAs rather than none.
+    /// Defaults to `drop`.
+    pre_ip: Option<ParseFailureAction>,
+    /// Check the checksum of RAs and DHCPv6 packets before rewriting them, rather than
+    /// giving corrupt packets a new, valid checksum.
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    verify_checksum: Option<()>,
+    /// What to do with packets that fail `--verify-checksum`. Defaults to `drop`.
+    on_bad_checksum: Option<ParseFailureAction>,
+    /// Don't let rewriting grow a packet past this many bytes (ex: the link MTU). If
+    /// the search list doesn't fit it's left out, otherwise the original packet is
+    /// passed through. Defaults to 1280, the IPv6 minimum MTU.
+    max_packet: Option<usize>,
+    /// Only rewrite RAs from these routers, as `allow=prefix` or `deny=prefix` (ex:
+    /// `allow=fe80::1`, a plain address is `/128`). Deny entries win; if there are
+    /// allow entries, RAs must match one. Other RAs are passed through.
+    ra_source: Option<Vec<String>>,
+    /// DHCPv6 message types to rewrite DNS in, others are passed through. Defaults to
+    /// both `advertise` and `reply`.
+    dhcp_message_types: Option<Vec<DhcpMessageType>>,
+    /// UDP ports DHCPv6 is sent between, for non-standard deployments. Other UDP is
+    /// passed through. Defaults to `546 547`.
+    dhcp_ports: Option<Vec<u16>>,
+    /// Shell command to run when an interface's advertised IP changes. It gets the new
+    /// IP (or `none`) as `$1` and in `SPAGHATTEWAY_DNS_IP`, and the interface in
+    /// `SPAGHATTEWAY_INTERFACE`. Runs after the IP has been stable for a few seconds.
+    on_ip_change: Option<String>,
+    /// Run this shell command each recheck to get the address to advertise for the
+    /// `--interface`s, instead of using their addresses (ex: to query a metadata
+    /// service). It must print one IPv6 address within 10 seconds; failure or anything
+    /// else is treated as no IP.
+    dns_command: Option<String>,
+    /// What to do if an interface watcher stops unexpectedly (ex: a bug), since its
+    /// interface's IP would go stale. Defaults to `exit`.
+    watcher_restart: Option<WatcherRestart>,
+    /// If no interface has a global IP this many seconds after starting, warn and act
+    /// per `--on-startup-timeout`, rather than silently waiting indefinitely.
+    startup_timeout: Option<u64>,
+    /// What to do after `--startup-timeout`. Defaults to `exit`.
+    on_startup_timeout: Option<StartupTimeoutAction>,
+    /// Write each rewritten packet, before and after, to this pcap file (replaced at
+    /// startup). Originals are marked as "to another host" and rewrites as "sent by
+    /// us". Packets are skipped if writing falls behind.
+    pcap_out: Option<PathBuf>,
+    /// Serve Prometheus metrics on this address
+    metrics_listen: Option<SocketAddr>,
+    /// Serve an HTTP health check on this address (ex: for `/healthz`). It returns 503
+    /// when no watched interface has an address to advertise or an interface watcher
+    /// or queue worker has stalled, otherwise 200.
+    health_listen: Option<SocketAddr>,
+    /// Accept commands on a Unix socket at this path: `status` (addresses and
+    /// counters, as JSON), `recheck` (poll the interfaces now) and `set-dns <addr>
+    /// [interface]` (advertise `addr` until the next poll). One command per line.
+    control_socket: Option<PathBuf>,
+    /// Minimum level of messages to log. Defaults to `info`.
+    log_level: Option<LogLevel>,
+    /// How to format log messages. Defaults to `text`.
+    log_format: Option<LogFormat>,
+    /// Where to write log messages. Defaults to `stderr`.
+    log_target: Option<LogTarget>,
+    /// The file for `--log-target file`.
+    log_file: Option<PathBuf>,
+    /// Log how packets would be rewritten, but pass everything through unmodified
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    dry_run: Option<()>,
+    /// Stop after this many packets (total across queues), unbinding and exiting
+    /// normally. For testing.
+    max_packets: Option<u64>,
+    /// Instead of running, rewrite this packet (hex, starting at the IP header) with
+    /// `--dns-ip` and the other settings, print what was found and the result, and
+    /// exit. For debugging and bug reports.
+    #[serde(skip)]
+    parse: Option<String>,
+    /// Instead of running, rewrite the packets in this pcap file (ethernet, Linux
+    /// cooked or raw IP) with `--dns-ip` and the other settings, writing all of them to
+    /// `--replay-out`, and exit.
+    #[serde(skip)]
+    replay_in: Option<PathBuf>,
+    /// The pcap file to write with `--replay-in` (replaced).
+    #[serde(skip)]
+    replay_out: Option<PathBuf>,
+    /// Instead of running, print an nftables ruleset that queues RAs and DHCP
+    /// responses (leaving by `--interface`, if set) to `--nf-queue` for this
+    /// configuration, skipping packets marked with `--nf-mark`, and exit. Doesn't
+    /// apply it.
+    #[serde(skip)]
+    print_nftables: Option<()>,
+    /// Print the version, git commit and build time, and exit.
+    #[serde(skip)]
+    version: Option<()>,
 }
 
-fn main() {
-    match || -> Result<(), loga::Error> {
-        let orig_hook = panic::take_hook();
-        panic::set_hook(Box::new(move |panic_info| {
-            orig_hook(panic_info);
-            process::exit(1);
-        }));
-        let args = vark::<Args>();
-        let recheck_period = args.recheck_period.unwrap_or(60);
-        let mut nf_queue = Queue::open().context("Error opening netfilter queue")?;
-        nf_queue.bind(args.nf_queue).context("Error binding netfilter queue")?;
-        let ip_rxtx = Arc::new(Mutex::new(None));
-
-        // Wait for initial ip, or get next ip
-        spawn({
-            let ip_rxtx = ip_rxtx.clone();
-            let want_iface = args.interface;
-            move || {
-                let mut found_first = false;
-                loop {
-                    let mut found = None;
-                    for iface in NetworkInterface::show()
-                        .context("Failure listing network interfaces")
-                        .unwrap()
-                        .iter() {
-                        if want_iface != iface.name {
-                            continue;
-                        }
-                        for addr in &iface.addr {
-                            let std::net::IpAddr::V6(addr) = addr.ip() else {
-                                continue;
-                            };
-                            if !addr.unstable_is_global() {
-                                continue;
-                            }
-                            found = Some(addr);
-                            found_first = true;
-                        }
-                    }
-                    if found.is_none() {
-                        eprintln!("Interface not found or no global ipv6 address found on interface.");
+/// Read a `true`/`false` config value into a presence flag, as `Option<()>` would
+/// otherwise be `null`.
+fn deserialize_flag<'de, D: Deserializer<'de>>(d: D) -> Result<Option<()>, D::Error> {
+    return Ok(bool::deserialize(d)?.then_some(()));
+}
+
+impl Args {
+    /// Fill in anything not set in `self` from `other`.
+    fn or(self, other: Args) -> Args {
+        return Args {
+            config: self.config,
+            interface: self.interface.or(other.interface),
+            interface_index: self.interface_index.or(other.interface_index),
+            recheck_period: self.recheck_period.or(other.recheck_period),
+            nf_queue: self.nf_queue.or(other.nf_queue),
+            nf_queue_range: self.nf_queue_range.or(other.nf_queue_range),
+            nf_mark: self.nf_mark.or(other.nf_mark),
+            nf_mark_mask: self.nf_mark_mask.or(other.nf_mark_mask),
+            copy_range: self.copy_range.or(other.copy_range),
+            queue_maxlen: self.queue_maxlen.or(other.queue_maxlen),
+            queue_fail: self.queue_fail.or(other.queue_fail),
+            verdict: self.verdict.or(other.verdict),
+            mtu: self.mtu.or(other.mtu),
+            cur_hop_limit: self.cur_hop_limit.or(other.cur_hop_limit),
+            router_lifetime: self.router_lifetime.or(other.router_lifetime),
+            reachable_time: self.reachable_time.or(other.reachable_time),
+            retrans_timer: self.retrans_timer.or(other.retrans_timer),
+            pref64: self.pref64.or(other.pref64),
+            rdnss_position: self.rdnss_position.or(other.rdnss_position),
+            dns_ip: self.dns_ip.or(other.dns_ip),
+            dns_rotate: self.dns_rotate.or(other.dns_rotate),
+            interface_dns: self.interface_dns.or(other.interface_dns),
+            out_interface: self.out_interface.or(other.out_interface),
+            dns_ip4: self.dns_ip4.or(other.dns_ip4),
+            dns_prefix: self.dns_prefix.or(other.dns_prefix),
+            allow_ula: self.allow_ula.or(other.allow_ula),
+            allow_temporary: self.allow_temporary.or(other.allow_temporary),
+            address_select: self.address_select.or(other.address_select),
+            dns_search: self.dns_search.or(other.dns_search),
+            rdnss_lifetime: self.rdnss_lifetime.or(other.rdnss_lifetime),
+            min_rdnss_lifetime: self.min_rdnss_lifetime.or(other.min_rdnss_lifetime),
+            max_rdnss_lifetime: self.max_rdnss_lifetime.or(other.max_rdnss_lifetime),
+            set_other_flag: self.set_other_flag.or(other.set_other_flag),
+            set_managed_flag: self.set_managed_flag.or(other.set_managed_flag),
+            keep_existing_rdnss: self.keep_existing_rdnss.or(other.keep_existing_rdnss),
+            consolidate_rdnss: self.consolidate_rdnss.or(other.consolidate_rdnss),
+            withdraw_on_loss: self.withdraw_on_loss.or(other.withdraw_on_loss),
+            on_parse_failure: self.on_parse_failure.or(other.on_parse_failure),
+            max_ra_per_sec: self.max_ra_per_sec.or(other.max_ra_per_sec),
+            on_rate_limit: self.on_rate_limit.or(other.on_rate_limit),
+            pre_ip: self.pre_ip.or(other.pre_ip),
+            verify_checksum: self.verify_checksum.or(other.verify_checksum),
+            on_bad_checksum: self.on_bad_checksum.or(other.on_bad_checksum),
+            max_packet: self.max_packet.or(other.max_packet),
+            ra_source: self.ra_source.or(other.ra_source),
+            dhcp_message_types: self.dhcp_message_types.or(other.dhcp_message_types),
+            dhcp_ports: self.dhcp_ports.or(other.dhcp_ports),
+            on_ip_change: self.on_ip_change.or(other.on_ip_change),
+            dns_command: self.dns_command.or(other.dns_command),
+            watcher_restart: self.watcher_restart.or(other.watcher_restart),
+            startup_timeout: self.startup_timeout.or(other.startup_timeout),
+            on_startup_timeout: self.on_startup_timeout.or(other.on_startup_timeout),
+            pcap_out: self.pcap_out.or(other.pcap_out),
+            metrics_listen: self.metrics_listen.or(other.metrics_listen),
+            health_listen: self.health_listen.or(other.health_listen),
+            control_socket: self.control_socket.or(other.control_socket),
+            log_level: self.log_level.or(other.log_level),
+            log_format: self.log_format.or(other.log_format),
+            log_target: self.log_target.or(other.log_target),
+            log_file: self.log_file.or(other.log_file),
+            dry_run: self.dry_run.or(other.dry_run),
+            max_packets: self.max_packets.or(other.max_packets),
+            parse: self.parse,
+            replay_in: self.replay_in,
+            replay_out: self.replay_out,
+            print_nftables: self.print_nftables,
+            version: self.version,
+        };
+    }
+}
+
+/// How to act on the result of `modify`.
+struct VerdictConfig {
+    nf_mark: u32,
+    /// The bits of the mark that are ours, `nf_mark` is within it.
+    nf_mark_mask: u32,
+    /// For rewritten packets.
+    modified_verdict: Verdict,
+    on_parse_failure: ParseFailureAction,
+    on_rate_limit: ParseFailureAction,
+    /// For packets leaving by an interface without an IP.
+    pre_ip: ParseFailureAction,
+    on_bad_checksum: ParseFailureAction,
+    dry_run: bool,
+    pcap_out: Option<PcapOut>,
+}
+
+impl VerdictConfig {
+    /// The mark for a rewritten packet that was queued with `mark`, replacing only our
+    /// bits.
+    fn rewritten_mark(&self, mark: u32) -> u32 {
+        return (mark & !self.nf_mark_mask) | self.nf_mark;
+    }
+
+    /// Whether a packet queued with `mark` has already been rewritten.
+    fn is_marked(&self, mark: u32) -> bool {
+        return mark & self.nf_mark_mask == self.nf_mark;
+    }
+}
+
+/// The packet rewriting settings from `args`, with no DNS IPs.
+fn build_modify_config(args: &Args, recheck_period: u64) -> Result<ModifyConfig, loga::Error> {
+    let min_rdnss_lifetime = args.min_rdnss_lifetime.unwrap_or(recheck_period.min(u32::MAX as u64) as u32);
+    if let Some(max_rdnss_lifetime) = args.max_rdnss_lifetime.filter(|max| *max < min_rdnss_lifetime) {
+        return Err(
+            loga::err_with(
+                "--max-rdnss-lifetime is less than the minimum RDNSS lifetime",
+                ea!(max = max_rdnss_lifetime, min = min_rdnss_lifetime),
+            ),
+        );
+    }
+    let pref64 = match &args.pref64 {
+        Some(p) => {
+            let prefix = Ipv6Prefix::from_str(p).map_err(loga::err)?;
+            Some(
+                Pref64::new(
+                    prefix.addr,
+                    prefix.len,
+                ).ok_or_else(|| loga::err_with("Unsupported --pref64 prefix length", ea!(prefix = p)))?,
+            )
+        },
+        None => None,
+    };
+    return Ok(ModifyConfig {
+        dns_ips: vec![],
+        dns_search: args.dns_search.clone().unwrap_or_default(),
+        rdnss_lifetime: args.rdnss_lifetime,
+        min_rdnss_lifetime: Some(min_rdnss_lifetime),
+        max_rdnss_lifetime: args.max_rdnss_lifetime,
+        mtu: args.mtu,
+        cur_hop_limit: args.cur_hop_limit,
+        router_lifetime: args.router_lifetime,
+        reachable_time: args.reachable_time,
+        retrans_timer: args.retrans_timer,
+        keep_existing_dns: args.keep_existing_rdnss.is_some(),
+        consolidate_rdnss: args.consolidate_rdnss.is_some(),
+        dhcp_message_types: args.dhcp_message_types.as_ref().map(|types| types.iter().map(|t| match t {
+            DhcpMessageType::Advertise => DHCP_ADVERTISE,
+            DhcpMessageType::Reply => DHCP_REPLY,
+        }).collect()),
+        dhcp_ports: args.dhcp_ports.clone(),
+        dns_ips_v4: args.dns_ip4.clone().unwrap_or_default(),
+        set_other_flag: args.set_other_flag.and_then(|s| s.to_option()),
+        set_other_flag_on_rewrite: args.set_other_flag.is_none(),
+        set_managed_flag: args.set_managed_flag.unwrap_or(FlagSetting::Unchanged).to_option(),
+        pref64,
+        dns_options_at_end: matches!(args.rdnss_position, Some(RdnssPosition::End)),
+        verify_checksum: args.verify_checksum.is_some(),
+        max_packet: Some(args.max_packet.unwrap_or(DEFAULT_MAX_PACKET)),
+    });
+}
+
+/// The command line arguments, with anything missing filled in from the config
+/// file.
+fn load_args(cli_args: &Args) -> Result<Args, loga::Error> {
+    let Some(path) = &cli_args.config else {
+        return Ok(cli_args.clone());
+    };
+    let raw = read(path).context_with("Error reading config file", ea!(path = path.dbg_str()))?;
+    let file_args =
+        serde_json::from_slice::<Args>(&raw).context_with("Error parsing config file", ea!(path = path.dbg_str()))?;
+    return Ok(cli_args.clone().or(file_args));
+}
+
+/// The `--dns-ip` addresses, with their zones.
+fn dns_ips_zoned(args: &Args) -> Result<Vec<(Ipv6Addr, Option<String>)>, loga::Error> {
+    return args.dns_ip.iter().flatten().map(|s| parse_dns_ip(s).map_err(loga::err)).collect();
+}
+
+/// The `--dns-ip` addresses to put in packets, the zones aren't sent.
+fn dns_ips(args: &Args) -> Result<Vec<Ipv6Addr>, loga::Error> {
+    return Ok(dns_ips_zoned(args)?.into_iter().map(|(ip, _)| ip).collect());
+}
+
+/// `static_dns` if not watching interfaces, so `--dns-ip` is advertised as is.
+fn build_live_config(args: &Args, static_dns: bool) -> Result<LiveConfig, loga::Error> {
+    let recheck_period = args.recheck_period.unwrap_or(60);
+    let mut modify_config = build_modify_config(args, recheck_period)?;
+    let extra_dns_ips = dns_ips(args)?;
+    if static_dns {
+        modify_config.dns_ips = extra_dns_ips.clone();
+    }
+    let dns_prefix = match &args.dns_prefix {
+        Some(p) => Some(Ipv6Prefix::from_str(p).map_err(loga::err)?),
+        None => None,
+    };
+    let mut ra_sources = RaSourceFilter::default();
+    for entry in args.ra_source.iter().flatten() {
+        ra_sources.add(entry).map_err(loga::err)?;
+    }
+    return Ok(LiveConfig {
+        modify_config,
+        extra_dns_ips,
+        dns_rotate: args.dns_rotate.is_some(),
+        watch_config: WatchConfig {
+            recheck_period,
+            dns_prefix,
+            allow_ula: args.allow_ula.is_some(),
+            allow_temporary: args.allow_temporary.is_some(),
+            address_select: args.address_select.unwrap_or(AddressSelect::Lowest),
+            withdraw_on_loss: args.withdraw_on_loss.map(Duration::from_secs),
+        },
+        ra_sources,
+    });
+}
+
+/// Rotate `dns_ips` for `--dns-rotate`, then advance `rotation` for the next packet.
+fn rotate_dns_ips(dns_ips: &mut [Ipv6Addr], rotation: &mut usize) {
+    if dns_ips.len() > 1 {
+        dns_ips.rotate_left(*rotation % dns_ips.len());
+        *rotation = rotation.wrapping_add(1);
+    }
+}
+
+/// Drop a packet, unless in dry-run mode.
+fn drop_verdict<Q: PacketQueue>(
+    nf_queue: &mut Q,
+    mut nf_queue_msg: Q::Message,
+    verdict_config: &VerdictConfig,
+    metrics: &Metrics,
+) -> Result<(), loga::Error> {
+    if verdict_config.dry_run {
+        nf_queue_msg.set_verdict(Verdict::Accept);
+    } else {
+        nf_queue_msg.set_verdict(Verdict::Drop);
+        Metrics::inc(&metrics.packets_dropped);
+    }
+    nf_queue.verdict(nf_queue_msg).context("Error setting netfilter message verdict")?;
+    return Ok(());
+}
+
+fn modify_and_verdict<Q: PacketQueue>(
+    log: &EventLog,
+    nf_queue: &mut Q,
+    mut nf_queue_msg: Q::Message,
+    scratch: &mut Vec<u8>,
+    modify_config: &ModifyConfig,
+    verdict_config: &VerdictConfig,
+    metrics: &Metrics,
+) -> Result<(), loga::Error> {
+    // `--max-packet` is for whole packets, refragmenting keeps the fragments within
+    // the original sizes
+    let unlimited_config;
+    let modify_config = if nf_queue_msg.is_reassembled() && modify_config.max_packet.is_some() {
+        unlimited_config = ModifyConfig {
+            max_packet: None,
+            ..modify_config.clone()
+        };
+        &unlimited_config
+    } else {
+        modify_config
+    };
+    let mut res = modify_into(scratch, nf_queue_msg.get_payload(), modify_config);
+    if matches!(res, Err(ModifyError::TooLarge)) && !modify_config.dns_search.is_empty() {
+        // The search list is the least important addition, try without it
+        event!(
+            log,
+            LogLevel::Info,
+            "too_large_no_search",
+            "Rewritten packet would be over --max-packet, leaving out the search list",
+            outdev = nf_queue_msg.get_outdev(),
+            length = nf_queue_msg.get_payload().len()
+        );
+        res = modify_into(scratch, nf_queue_msg.get_payload(), &ModifyConfig {
+            dns_search: vec![],
+            ..modify_config.clone()
+        });
+    }
+    match res {
+        Ok(()) => {
+            let ipv6_packet = scratch.as_slice();
+            if let Some(pcap_out) = &verdict_config.pcap_out {
+                pcap_out.capture(log, nf_queue_msg.get_payload(), ipv6_packet);
+            }
+            if verdict_config.dry_run {
+                // Log what would change, then pass the original through
+                let original = nf_queue_msg.get_payload();
+                let mut removed = list_options(original, modify_config).unwrap_or_default();
+                let mut added = list_options(ipv6_packet, modify_config).unwrap_or_default();
+                removed.retain(|o| match added.iter().position(|a| a == o) {
+                    Some(i) => {
+                        added.remove(i);
+                        return false;
+                    },
+                    None => return true,
+                });
+                event!(
+                    log,
+                    LogLevel::Info,
+                    "dry_run_modified",
+                    "Dry run, would rewrite packet",
+                    outdev = nf_queue_msg.get_outdev(),
+                    original_length = original.len(),
+                    new_length = ipv6_packet.len(),
+                    dns_ips = modify_config.dns_ips.dbg_str(),
+                    options_removed = removed.dbg_str(),
+                    options_added = added.dbg_str()
+                );
+                nf_queue_msg.set_verdict(Verdict::Accept);
+                Metrics::inc(&metrics.packets_dry_run_modified);
+            } else {
+                event!(
+                    log,
+                    LogLevel::Debug,
+                    "modified",
+                    "Rewrote packet",
+                    outdev = nf_queue_msg.get_outdev(),
+                    dns_ips = modify_config.dns_ips.dbg_str()
+                );
+                // Copied into the message, the only per-packet allocation
+                nf_queue_msg.set_payload(ipv6_packet);
+                nf_queue_msg.set_nfmark(verdict_config.rewritten_mark(nf_queue_msg.get_nfmark()));
+                nf_queue_msg.set_verdict(verdict_config.modified_verdict);
+                Metrics::inc(&metrics.packets_modified);
+            }
+        },
+        Err(ModifyError::NotApplicable) => {
+            // Not something we rewrite (ex: matched by a broad nftables rule)
+            event!(
+                log,
+                LogLevel::Debug,
+                "not_applicable",
+                "Not a packet to rewrite, passing through",
+                outdev = nf_queue_msg.get_outdev()
+            );
+            nf_queue_msg.set_verdict(Verdict::Accept);
+        },
+        Err(ModifyError::TooLarge) => {
+            // Better the router's DNS than a packet that doesn't make it to clients
+            event!(
+                log,
+                LogLevel::Warn,
+                "too_large",
+                "Rewritten packet would be over --max-packet, passing the original through",
+                outdev = nf_queue_msg.get_outdev(),
+                length = nf_queue_msg.get_payload().len()
+            );
+            nf_queue_msg.set_verdict(Verdict::Accept);
+        },
+        Err(ModifyError::BadChecksum) => {
+            // Corrupted upstream, don't hide it by rewriting with a valid checksum
+            Metrics::inc(&metrics.packets_bad_checksum);
+            event!(
+                log,
+                LogLevel::Info,
+                "bad_checksum",
+                "Packet has a bad checksum, not rewriting",
+                outdev = nf_queue_msg.get_outdev(),
+                length = nf_queue_msg.get_payload().len()
+            );
+            match verdict_config.on_bad_checksum {
+                ParseFailureAction::Drop => {
+                    return drop_verdict(nf_queue, nf_queue_msg, verdict_config, metrics);
+                },
+                ParseFailureAction::Accept => {
+                    nf_queue_msg.set_verdict(Verdict::Accept);
+                },
+            }
+        },
+        Err(e) => {
+            // Bad, not a real packet, or undocumented headers or other issues
+            Metrics::inc(&metrics.parse_failures);
+            event!(
+                log,
+                LogLevel::Debug,
+                "parse_failure",
+                "Couldn't parse or rewrite packet",
+                outdev = nf_queue_msg.get_outdev(),
+                length = nf_queue_msg.get_payload().len(),
+                error = e
+            );
+            match verdict_config.on_parse_failure {
+                ParseFailureAction::Drop => {
+                    return drop_verdict(nf_queue, nf_queue_msg, verdict_config, metrics);
+                },
+                ParseFailureAction::Accept => {
+                    nf_queue_msg.set_verdict(Verdict::Accept);
+                },
+            }
+        },
+    }
+    nf_queue.verdict(nf_queue_msg).context("Error setting netfilter message verdict")?;
+    return Ok(());
+}
+
+/// An interface to watch, from `--interface` or `--interface-index`.
+enum WantIface {
+    Name(String),
+    Index(u32),
+}
+
+impl WantIface {
+    fn matches(&self, iface: &NetworkInterface) -> bool {
+        match self {
+            WantIface::Name(name) => return *name == iface.name,
+            WantIface::Index(index) => return *index == iface.index,
+        }
+    }
+
+    /// For logs, metrics and the IP change hook.
+    fn label(&self) -> String {
+        match self {
+            WantIface::Name(name) => return name.clone(),
+            WantIface::Index(index) => return index.to_string(),
+        }
+    }
+}
+
+/// The latest state of a watched interface.
+struct IfaceState {
+    /// `None` if the interface doesn't exist.
+    index: Option<u32>,
+    /// The global address to advertise, `None` if there isn't one.
+    ip: Option<Ipv6Addr>,
+    /// The address that was lost and until when to withdraw it, with
+    /// `--withdraw-on-loss`.
+    withdraw: Option<(Ipv6Addr, Instant)>,
+    /// The prefix in the last RA that left by the interface, to prefer addresses
+    /// clients are on-link with.
+    ra_prefix: Option<Ipv6Prefix>,
+}
+
+/// Settings shared by all interface watchers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct WatchConfig {
+    recheck_period: u64,
+    dns_prefix: Option<Ipv6Prefix>,
+    allow_ula: bool,
+    allow_temporary: bool,
+    address_select: AddressSelect,
+    withdraw_on_loss: Option<Duration>,
+}
+
+/// The settings that are replaced when the config file is reloaded.
+struct LiveConfig {
+    /// `dns_ips` is only set when using static addresses, otherwise it's filled in per
+    /// packet.
+    modify_config: ModifyConfig,
+    /// From `--dns-ip`.
+    extra_dns_ips: Vec<Ipv6Addr>,
+    dns_rotate: bool,
+    watch_config: WatchConfig,
+    ra_sources: RaSourceFilter,
+}
+
+impl LiveConfig {
+    /// Settings that differ in `new`, as (name, old, new).
+    fn changes(&self, new: &LiveConfig) -> Vec<(&'static str, String, String)> {
+        let (old_modify, new_modify) = (&self.modify_config, &new.modify_config);
+        let (old_watch, new_watch) = (&self.watch_config, &new.watch_config);
+        let mut out = vec![];
+        for (name, old, new) in [
+            ("dns-ip", self.extra_dns_ips.dbg_str(), new.extra_dns_ips.dbg_str()),
+            ("dns-rotate", self.dns_rotate.dbg_str(), new.dns_rotate.dbg_str()),
+            ("dns-ip4", old_modify.dns_ips_v4.dbg_str(), new_modify.dns_ips_v4.dbg_str()),
+            ("dns-search", old_modify.dns_search.dbg_str(), new_modify.dns_search.dbg_str()),
+            ("rdnss-lifetime", old_modify.rdnss_lifetime.dbg_str(), new_modify.rdnss_lifetime.dbg_str()),
+            ("min-rdnss-lifetime", old_modify.min_rdnss_lifetime.dbg_str(), new_modify.min_rdnss_lifetime.dbg_str()),
+            ("max-rdnss-lifetime", old_modify.max_rdnss_lifetime.dbg_str(), new_modify.max_rdnss_lifetime.dbg_str()),
+            ("mtu", old_modify.mtu.dbg_str(), new_modify.mtu.dbg_str()),
+            ("cur-hop-limit", old_modify.cur_hop_limit.dbg_str(), new_modify.cur_hop_limit.dbg_str()),
+            ("router-lifetime", old_modify.router_lifetime.dbg_str(), new_modify.router_lifetime.dbg_str()),
+            ("reachable-time", old_modify.reachable_time.dbg_str(), new_modify.reachable_time.dbg_str()),
+            ("retrans-timer", old_modify.retrans_timer.dbg_str(), new_modify.retrans_timer.dbg_str()),
+            ("pref64", old_modify.pref64.dbg_str(), new_modify.pref64.dbg_str()),
+            ("keep-existing-rdnss", old_modify.keep_existing_dns.dbg_str(), new_modify.keep_existing_dns.dbg_str()),
+            ("consolidate-rdnss", old_modify.consolidate_rdnss.dbg_str(), new_modify.consolidate_rdnss.dbg_str()),
+            (
+                "dhcp-message-types",
+                old_modify.dhcp_message_types.dbg_str(),
+                new_modify.dhcp_message_types.dbg_str(),
+            ),
+            ("dhcp-ports", old_modify.dhcp_ports.dbg_str(), new_modify.dhcp_ports.dbg_str()),
+            (
+                "set-other-flag",
+                (old_modify.set_other_flag, old_modify.set_other_flag_on_rewrite).dbg_str(),
+                (new_modify.set_other_flag, new_modify.set_other_flag_on_rewrite).dbg_str(),
+            ),
+            ("set-managed-flag", old_modify.set_managed_flag.dbg_str(), new_modify.set_managed_flag.dbg_str()),
+            ("rdnss-position", old_modify.dns_options_at_end.dbg_str(), new_modify.dns_options_at_end.dbg_str()),
+            ("verify-checksum", old_modify.verify_checksum.dbg_str(), new_modify.verify_checksum.dbg_str()),
+            ("max-packet", old_modify.max_packet.dbg_str(), new_modify.max_packet.dbg_str()),
+            ("recheck-period", old_watch.recheck_period.dbg_str(), new_watch.recheck_period.dbg_str()),
+            ("dns-prefix", old_watch.dns_prefix.dbg_str(), new_watch.dns_prefix.dbg_str()),
+            ("allow-ula", old_watch.allow_ula.dbg_str(), new_watch.allow_ula.dbg_str()),
+            ("allow-temporary", old_watch.allow_temporary.dbg_str(), new_watch.allow_temporary.dbg_str()),
+            ("address-select", old_watch.address_select.dbg_str(), new_watch.address_select.dbg_str()),
+            ("withdraw-on-loss", old_watch.withdraw_on_loss.dbg_str(), new_watch.withdraw_on_loss.dbg_str()),
+            ("ra-source", self.ra_sources.dbg_str(), new.ra_sources.dbg_str()),
+        ] {
+            if old != new {
+                out.push((name, old, new));
+            }
+        }
+        return out;
+    }
+}
+
+/// Everything an interface watcher needs, shared between watchers.
+struct Watcher<'a> {
+    log: &'a EventLog,
+    stop: &'a AtomicBool,
+    metrics: &'a Metrics,
+    notifier: &'a Notifier,
+    iface_states: &'a Mutex<HashMap<String, IfaceState>>,
+    live: &'a Reloadable<LiveConfig>,
+    /// From `spawn_ip_change_hook`, for this interface.
+    on_ip_change: Option<Sender<Option<Ipv6Addr>>>,
+    /// `--dns-command`, used instead of the interface's addresses.
+    dns_command: Option<&'a str>,
+    health: &'a Health,
+}
+
+fn watch_interface(watcher: Watcher, want: &WantIface, recheck_rx: &Receiver<()>) {
+    let Watcher { log, stop, metrics, notifier, iface_states, live, on_ip_change, dns_command, health } = watcher;
+    let want_iface = want.label();
+    let health_name = format!("interface {}", want_iface);
+    let mut found_first = false;
+
+    // Only warn once per run of `--dns-command` failures
+    let mut command_failing = false;
+    while !stop.load(Ordering::Relaxed) {
+        health.beat(&health_name, SystemTime::now());
+        let watch_config = live.get().watch_config;
+        let mut found_index = None;
+        let mut candidates = vec![];
+        // Keep the last known state until listing works again
+        let ifaces = match NetworkInterface::show() {
+            Ok(i) => i,
+            Err(e) => {
+                log.err(
+                    LogLevel::Warn,
+                    "error",
+                    e.context_with("Failure listing network interfaces, retrying", ea!(interface = want_iface)),
+                );
+                sleep(LIST_RETRY_PERIOD);
+                continue;
+            },
+        };
+        for iface in &ifaces {
+            if !want.matches(iface) {
+                continue;
+            }
+            found_index = Some(iface.index);
+            if dns_command.is_some() {
+                continue;
+            }
+
+            // Netlink has the flags (tentative, deprecated) and lifetimes - during a prefix
+            // change the old address lingers until its lifetime runs out, so prefer the one
+            // that'll be around longer
+            let addrs = match ipv6_addrs(iface.index) {
+                Ok(a) => a,
+                Err(e) => {
+                    log.err(
+                        LogLevel::Warn,
+                        "error",
+                        e.context_with(
+                            "Error listing addresses via netlink, falling back to ignoring flags and lifetimes",
+                            ea!(interface = want_iface),
+                        ),
+                    );
+                    iface.addr.iter().filter_map(|addr| match addr.ip() {
+                        std::net::IpAddr::V6(addr) => return Some(AddrInfo {
+                            addr,
+                            flags: 0,
+                            valid_lifetime: u32::MAX,
+                            created: 0,
+                        }),
+                        std::net::IpAddr::V4(_) => return None,
+                    }).collect()
+                },
+            };
+            for addr in addrs {
+                if !is_candidate(&addr.addr, addr.flags, watch_config.allow_ula, watch_config.allow_temporary) {
+                    continue;
+                }
+                candidates.push(addr);
+            }
+        }
+        let found = match (dns_command, found_index) {
+            (None, _) => {
+                let (current, ra_prefix) =
+                    iface_states.lock().unwrap().get(&want_iface).map(|s| (s.ip, s.ra_prefix)).unwrap_or_default();
+                select_ip(
+                    candidates,
+                    watch_config.dns_prefix.as_ref(),
+                    ra_prefix.as_ref(),
+                    watch_config.address_select,
+                    current,
+                )
+            },
+            (Some(_), None) => None,
+            (Some(command), Some(_)) => match run_dns_command(command, DNS_COMMAND_TIMEOUT) {
+                Ok(ip) => {
+                    if command_failing {
+                        event!(
+                            log,
+                            LogLevel::Info,
+                            "dns_command_recovered",
+                            "DNS command succeeded again",
+                            interface = want_iface
+                        );
+                        command_failing = false;
                     }
-                    *ip_rxtx.lock().unwrap() = Some(found);
-                    if !found_first {
-                        sleep(Duration::from_secs(5));
+                    Some(ip)
+                },
+                Err(e) => {
+                    log.err(if command_failing {
+                        LogLevel::Debug
                     } else {
-                        sleep(Duration::from_secs(recheck_period));
-                    }
+                        LogLevel::Warn
+                    }, "dns_command_failed", e);
+                    command_failing = true;
+                    None
+                },
+            },
+        };
+        if found.is_some() {
+            found_first = true;
+        }
+        {
+            let mut iface_states = iface_states.lock().unwrap();
+            let state = iface_states.get_mut(&want_iface).unwrap();
+            if found != state.ip {
+                match (state.ip, found) {
+                    (Some(old_ip), Some(ip)) => {
+                        event!(
+                            log,
+                            LogLevel::Info,
+                            "ip_changed",
+                            "Switching to a different IP",
+                            interface = want_iface,
+                            old_ip = old_ip,
+                            ip = ip
+                        );
+                        notifier.status(&format!("{}: advertising {}", want_iface, ip));
+                    },
+                    (None, Some(ip)) => {
+                        event!(
+                            log,
+                            LogLevel::Info,
+                            "ip_found",
+                            "Found global IP, switching to rewriting packets",
+                            interface = want_iface,
+                            ip = ip
+                        );
+                        notifier.ip_found();
+                        notifier.status(&format!("{}: advertising {}", want_iface, ip));
+                    },
+                    (_, None) => {
+                        event!(
+                            log,
+                            LogLevel::Info,
+                            "ip_lost",
+                            "Interface not found or no global ipv6 address found on interface, not rewriting packets",
+                            interface = want_iface
+                        );
+                        notifier.status(&format!("{}: no global IP, not rewriting packets", want_iface));
+                    },
                 }
+                if let Some(on_ip_change) = &on_ip_change {
+                    _ = on_ip_change.send(found);
+                }
+                let mut interfaces = metrics.interfaces.lock().unwrap();
+                let iface_metrics = interfaces.entry(want_iface.clone()).or_default();
+                iface_metrics.ip = found;
+                iface_metrics.last_change = Some(SystemTime::now());
+            }
+            state.index = found_index;
+            state.withdraw = match (state.ip, found, watch_config.withdraw_on_loss) {
+                (Some(old_ip), None, Some(withdraw_on_loss)) => Some((old_ip, Instant::now() + withdraw_on_loss)),
+                (_, Some(_), _) => None,
+                _ => state.withdraw,
+            };
+            state.ip = found;
+            health.have_ip.store(iface_states.values().any(|s| s.ip.is_some()), Ordering::Relaxed);
+        }
+        let period = if !found_first {
+            Duration::from_secs(5)
+        } else {
+            Duration::from_secs(watch_config.recheck_period)
+        };
+        match recheck_rx.recv_timeout(period) {
+            Ok(_) => {
+                // Coalesce bursts of changes into one recheck
+                while recheck_rx.try_recv().is_ok() { }
+            },
+            Err(RecvTimeoutError::Timeout) => { },
+            Err(RecvTimeoutError::Disconnected) => {
+                sleep(period);
+            },
+        }
+    }
+}
+
+/// What `--control-socket` commands act on.
+struct Control {
+    log: EventLog,
+    metrics: Arc<Metrics>,
+    /// `None` if only using static addresses.
+    iface_states: Option<Arc<Mutex<HashMap<String, IfaceState>>>>,
+    live: Arc<Reloadable<LiveConfig>>,
+    /// One per interface watcher.
+    recheck_txs: Vec<Sender<()>>,
+}
+
+impl ControlHandler for Control {
+    fn status(&self) -> serde_json::Value {
+        let mut interfaces = serde_json::Map::new();
+        if let Some(iface_states) = &self.iface_states {
+            for (name, state) in iface_states.lock().unwrap().iter() {
+                interfaces.insert(name.clone(), serde_json::json!({
+                    "index": state.index,
+                    "ip": state.ip,
+                }));
             }
+        }
+        let mut counters = serde_json::Map::new();
+        for (name, _, counter) in self.metrics.counters() {
+            counters.insert(name.to_string(), serde_json::json!(counter.load(Ordering::Relaxed)));
+        }
+        return serde_json::json!({
+            "interfaces": interfaces,
+            "static_dns_ips": self.live.get().extra_dns_ips,
+            "counters": counters,
         });
+    }
+
+    fn recheck(&self) {
+        for recheck_tx in &self.recheck_txs {
+            _ = recheck_tx.send(());
+        }
+    }
 
-        // Drop messages until we get an ip
-        eprintln!("Starting, waiting for first packet, then dropping packets until global IP found");
-        let (mut nf_queue_msg, mut ip) = loop {
-            let mut nf_queue_msg = nf_queue.recv().context("Error reading netfilter queue")?;
-            if let Some(Some(ip)) = ip_rxtx.lock().unwrap().take() {
-                break (nf_queue_msg, ip);
+    fn set_dns(&self, ip: Ipv6Addr, interface: Option<&str>) -> Result<(), String> {
+        let Some(iface_states) = &self.iface_states else {
+            return Err("set-dns needs --interface or --interface-index".to_string());
+        };
+        let mut iface_states = iface_states.lock().unwrap();
+        if let Some(interface) = interface.filter(|i| !iface_states.contains_key(*i)) {
+            return Err(format!("Not a watched interface [{}]", interface));
+        }
+        for (name, state) in iface_states.iter_mut() {
+            if interface.is_some_and(|i| i != name) {
+                continue;
             }
-            nf_queue_msg.set_verdict(Verdict::Drop);
+
+            // The watcher replaces it when it next finds a different address
+            state.ip = Some(ip);
+            state.withdraw = None;
+            event!(
+                self.log,
+                LogLevel::Info,
+                "dns_override",
+                "Advertising an address from the control socket until the next poll",
+                interface = name,
+                ip = ip
+            );
+        }
+        return Ok(());
+    }
+}
+
+/// Everything a queue worker needs, shared between workers.
+#[derive(Clone, Copy)]
+struct Worker<'a> {
+    log: &'a EventLog,
+    stop: &'a AtomicBool,
+    metrics: &'a Metrics,
+    notifier: &'a Notifier,
+    live: &'a Reloadable<LiveConfig>,
+    verdict_config: &'a VerdictConfig,
+    /// Shared between queues, so a router is limited however its RAs are spread.
+    rate_limiter: Option<&'a Mutex<RateLimiter>>,
+    /// From `--interface-dns`, by interface index.
+    interface_dns: &'a HashMap<u32, Ipv6Addr>,
+    /// From `--out-interface`, by interface index. `None` for all interfaces.
+    out_interfaces: Option<&'a HashSet<u32>>,
+    /// `None` if only using static addresses.
+    iface_states: Option<&'a Mutex<HashMap<String, IfaceState>>>,
+    /// Packets left before stopping, with `--max-packets`.
+    remaining_packets: Option<&'a AtomicU64>,
+    /// Set to accept packets for interfaces without an IP rather than dropping them,
+    /// with `--on-startup-timeout passthrough`.
+    startup_passthrough: Option<&'a AtomicBool>,
+    /// With `--health-listen`, each queue beats as `queue N` whenever its loop wakes.
+    health: Option<&'a Health>,
+}
+
+/// Process packets from `nf_queue` until stopped.
+///
+/// Interface state is looked up as each packet is received rather than cached
+/// between packets, so a packet is never rewritten with an IP that was lost while
+/// the queue was idle. Since nothing is sent without a packet to rewrite, there's no
+/// need to wake the loop on IP changes; it only wakes between packets to check
+/// `stop` (see `recv`).
+fn process_queue<Q: PacketQueue>(
+    worker: Worker,
+    log: &EventLog,
+    nf_queue_num: u16,
+    nf_queue: &mut Q,
+) -> Result<(), loga::Error> {
+    let health_name = format!("queue {}", nf_queue_num);
+    let mut generation = worker.live.generation();
+    let mut live = worker.live.get();
+    let mut modify_config = live.modify_config.clone();
+
+    // Rewritten packets are built here, reused to avoid allocating per packet
+    let mut scratch = Vec::with_capacity(2048);
+
+    // With `--dns-rotate`, how many places to rotate the next packet's DNS IPs
+    let mut dns_rotation = 0usize;
+    loop {
+        let mut nf_queue_msg = match recv(nf_queue, worker.stop, || {
+            if let Some(health) = worker.health {
+                health.beat(&health_name, SystemTime::now());
+            }
+        }) {
+            Ok(Some(m)) => m,
+            Ok(None) => return Ok(()),
+            // No backoff, under load that would only drop more
+            Err(e) if is_transient_recv_error(&e) => {
+                Metrics::inc(&worker.metrics.recv_errors_recovered);
+                log.err(LogLevel::Warn, "recv_error", e.context("Error reading netfilter queue, continuing"));
+                continue;
+            },
+            Err(e) => {
+                event!(log, LogLevel::Error, "recv_error", "Error reading netfilter queue, stopping", error = e);
+                return Err(e.context("Error reading netfilter queue"));
+            },
+        };
+
+        // Loga has no trace level, so filter here rather than logging these at debug
+        if log.level <= LogLevel::Trace {
+            if let Ok(parsed) = parse(nf_queue_msg.get_payload()) {
+                event!(
+                    log,
+                    LogLevel::Trace,
+                    "received",
+                    "Received packet",
+                    outdev = nf_queue_msg.get_outdev(),
+                    packet = parsed
+                );
+            }
+        }
+
+        // Pick up reloaded settings
+        if worker.live.generation() != generation {
+            generation = worker.live.generation();
+            live = worker.live.get();
+            modify_config = live.modify_config.clone();
+        }
+
+        // Stop once this one's done. Other queues may already have received a packet,
+        // they're still processed.
+        if let Some(remaining_packets) = worker.remaining_packets {
+            let remaining = remaining_packets.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+            if matches!(remaining, Ok(1) | Err(_)) {
+                worker.stop.store(true, Ordering::Relaxed);
+            }
+        }
+
+        // Already rewritten, the nftables rule should have skipped it - accept rather than
+        // risk a loop
+        if worker.verdict_config.is_marked(nf_queue_msg.get_nfmark()) {
+            if worker.metrics.packets_already_marked.fetch_add(1, Ordering::Relaxed) == 0 {
+                event!(
+                    log,
+                    LogLevel::Warn,
+                    "already_marked",
+                    "Queued a packet already marked with --nf-mark, the nftables rule should exclude marked packets",
+                    nf_mark = worker.verdict_config.nf_mark
+                );
+            }
+            nf_queue_msg.set_verdict(Verdict::Accept);
             nf_queue.verdict(nf_queue_msg).context("Error setting netfilter message verdict")?;
+            continue;
+        }
+
+        // Not leaving by an `--out-interface`
+        if worker.out_interfaces.is_some_and(|o| !o.contains(&nf_queue_msg.get_outdev())) {
+            nf_queue_msg.set_verdict(Verdict::Accept);
+            nf_queue.verdict(nf_queue_msg).context("Error setting netfilter message verdict")?;
+            continue;
+        }
+
+        // Another router's RA
+        if let Some(source) = ra_source(nf_queue_msg.get_payload()).filter(|s| !live.ra_sources.allows(s)) {
+            event!(
+                log,
+                LogLevel::Debug,
+                "ra_source_skipped",
+                "RA from a router excluded by --ra-source, not rewriting",
+                source = source
+            );
+            nf_queue_msg.set_verdict(Verdict::Accept);
+            nf_queue.verdict(nf_queue_msg).context("Error setting netfilter message verdict")?;
+            continue;
+        }
+
+        // Don't amplify RA floods
+        if let Some(rate_limiter) = worker.rate_limiter {
+            if let Some(source) = ra_source(nf_queue_msg.get_payload()) {
+                if !rate_limiter.lock().unwrap().allow(source, Instant::now()) {
+                    Metrics::inc(&worker.metrics.packets_rate_limited);
+                    event!(log, LogLevel::Debug, "rate_limited", "RA over rate limit, not rewriting", source = source);
+                    match worker.verdict_config.on_rate_limit {
+                        ParseFailureAction::Drop => {
+                            drop_verdict(nf_queue, nf_queue_msg, worker.verdict_config, worker.metrics)?;
+                        },
+                        ParseFailureAction::Accept => {
+                            nf_queue_msg.set_verdict(Verdict::Accept);
+                            nf_queue.verdict(nf_queue_msg).context("Error setting netfilter message verdict")?;
+                        },
+                    }
+                    continue;
+                }
+            }
+        }
+
+        // Fixed address for the outgoing interface, nothing to wait for
+        let is_ipv4 = nf_queue_msg.get_payload().first().map(|b| b >> 4) == Some(4);
+        let outdev = nf_queue_msg.get_outdev();
+        if let Some(ip) = worker.interface_dns.get(&outdev).filter(|_| !is_ipv4) {
+            modify_config.dns_ips = [*ip].into_iter().chain(live.extra_dns_ips.iter().cloned()).collect();
+            if live.dns_rotate {
+                rotate_dns_ips(&mut modify_config.dns_ips, &mut dns_rotation);
+            }
+            let res = modify_and_verdict(
+                log,
+                nf_queue,
+                nf_queue_msg,
+                &mut scratch,
+                &modify_config,
+                worker.verdict_config,
+                worker.metrics,
+            );
+            modify_config.dns_ips.clone_from(&live.modify_config.dns_ips);
+            res?;
+            continue;
+        }
+
+        // Static addresses (always for IPv4), nothing to wait for
+        let Some(iface_states) = worker.iface_states.filter(|_| !is_ipv4) else {
+            if !is_ipv4 && modify_config.dns_ips.is_empty() {
+                // Only `--interface-dns`, and not leaving by one of those interfaces
+                nf_queue_msg.set_verdict(Verdict::Accept);
+                nf_queue.verdict(nf_queue_msg).context("Error setting netfilter message verdict")?;
+                continue;
+            }
+            if live.dns_rotate {
+                rotate_dns_ips(&mut modify_config.dns_ips, &mut dns_rotation);
+            }
+            let res = modify_and_verdict(
+                log,
+                nf_queue,
+                nf_queue_msg,
+                &mut scratch,
+                &modify_config,
+                worker.verdict_config,
+                worker.metrics,
+            );
+            if live.dns_rotate {
+                modify_config.dns_ips.clone_from(&live.modify_config.dns_ips);
+            }
+            res?;
+            continue;
         };
-        loop {
-            eprintln!("Found global IP {}, switching from dropping to rewriting packets", ip);
-
-            // Replace RDNSS in subsequent RAs (continue with last msg of previous loop).
-            // Until we lose the ip again.
-            loop {
-                // Modify
-                match modify(nf_queue_msg.get_payload(), ip, args.mtu) {
-                    Some(ipv6_packet) => {
-                        nf_queue_msg.set_payload(ipv6_packet);
-                        nf_queue_msg.set_nfmark(args.nf_mark);
-                        nf_queue_msg.set_verdict(Verdict::Repeat);
-                        nf_queue.verdict(nf_queue_msg).context("Error setting netfilter message verdict")?;
+
+        // Rewrite using the IP of the interface the packet is leaving by, dropping if that
+        // interface has no global IP yet
+        let ra_prefix = ra_prefix(nf_queue_msg.get_payload()).map(|(addr, len)| Ipv6Prefix {
+            addr,
+            len,
+        });
+        let (ip, withdraw) = {
+            let mut iface_states = iface_states.lock().unwrap();
+            let any_missing = iface_states.values().any(|s| s.index.is_none());
+            match iface_states.values_mut().find(|s| s.index == Some(outdev)) {
+                Some(state) => {
+                    // For the watcher's next address selection
+                    if ra_prefix.is_some() {
+                        state.ra_prefix = ra_prefix;
+                    }
+                    (
+                        Some(state.ip),
+                        state.withdraw.filter(|(_, until)| Instant::now() < *until).map(|(ip, _)| ip),
+                    )
+                },
+                // Not going out a watched interface, unless it's one that doesn't exist
+                // yet
+                None if any_missing => (Some(None), None),
+                None => (None, None),
+            }
+        };
+        match (ip, withdraw) {
+            (Some(Some(ip)), _) => {
+                modify_config.dns_ips = [ip].into_iter().chain(live.extra_dns_ips.iter().cloned()).collect();
+                if live.dns_rotate {
+                    rotate_dns_ips(&mut modify_config.dns_ips, &mut dns_rotation);
+                }
+                modify_and_verdict(
+                    log,
+                    nf_queue,
+                    nf_queue_msg,
+                    &mut scratch,
+                    &modify_config,
+                    worker.verdict_config,
+                    worker.metrics,
+                )?;
+            },
+            (Some(None), Some(withdraw_ip)) if ra_source(nf_queue_msg.get_payload()).is_some() => {
+                // Tell clients to forget the lost IP rather than letting it sit in their
+                // caches
+                event!(
+                    log,
+                    LogLevel::Debug,
+                    "withdrawing",
+                    "No global IP for outgoing interface, withdrawing the old one",
+                    outdev = outdev,
+                    ip = withdraw_ip
+                );
+                modify_config.dns_ips = vec![withdraw_ip];
+                modify_config.rdnss_lifetime = Some(0);
+                let res = modify_and_verdict(
+                    log,
+                    nf_queue,
+                    nf_queue_msg,
+                    &mut scratch,
+                    &modify_config,
+                    worker.verdict_config,
+                    worker.metrics,
+                );
+                modify_config.rdnss_lifetime = live.modify_config.rdnss_lifetime;
+                res?;
+            },
+            (Some(None), _) if worker.startup_passthrough.is_some_and(|p| p.load(Ordering::Relaxed)) => {
+                event!(
+                    log,
+                    LogLevel::Debug,
+                    "passthrough",
+                    "No global IP for outgoing interface since starting, passing packet through",
+                    outdev = outdev
+                );
+                nf_queue_msg.set_verdict(Verdict::Accept);
+                nf_queue.verdict(nf_queue_msg).context("Error setting netfilter message verdict")?;
+            },
+            (Some(None), _) => {
+                match worker.verdict_config.pre_ip {
+                    ParseFailureAction::Drop => {
+                        event!(
+                            log,
+                            LogLevel::Debug,
+                            "dropped",
+                            "No global IP for outgoing interface, dropping packet",
+                            outdev = outdev
+                        );
+                        drop_verdict(nf_queue, nf_queue_msg, worker.verdict_config, worker.metrics)?;
                     },
-                    None => {
-                        // Bad, not a real packet, or undocumented headers or other issues
-                        nf_queue_msg.set_verdict(Verdict::Drop);
+                    ParseFailureAction::Accept => {
+                        event!(
+                            log,
+                            LogLevel::Debug,
+                            "accepted_no_ip",
+                            "No global IP for outgoing interface, passing packet through",
+                            outdev = outdev
+                        );
+                        nf_queue_msg.set_verdict(Verdict::Accept);
                         nf_queue.verdict(nf_queue_msg).context("Error setting netfilter message verdict")?;
                     },
                 }
+            },
+            (None, _) => {
+                // Nothing to say about it
+                nf_queue_msg.set_verdict(Verdict::Accept);
+                nf_queue.verdict(nf_queue_msg).context("Error setting netfilter message verdict")?;
+            },
+        }
+    }
+}
 
-                // Wait for next msg
-                nf_queue_msg = nf_queue.recv().context("Error reading netfilter queue")?;
+/// Kernel settings for each netfilter queue, set when binding.
+#[derive(Clone, Copy)]
+struct QueueConfig {
+    copy_range: u16,
+    max_len: Option<u32>,
+    fail: QueueFail,
+}
 
-                // Check for ips changes
-                if let Some(update) = ip_rxtx.lock().unwrap().take() {
-                    match update {
-                        Some(new_ip) => {
-                            ip = new_ip;
-                        },
-                        None => {
-                            break;
+/// Bind `nf_queue_num` and process packets from it until stopped.
+fn run_queue(worker: Worker, nf_queue_num: u16, queue_config: QueueConfig) -> Result<(), loga::Error> {
+    let log = worker.log.fork_queue(nf_queue_num);
+    let mut nf_queue = Queue::open().context("Error opening netfilter queue")?;
+    nf_queue.bind(nf_queue_num).context_with("Error binding netfilter queue", ea!(queue = nf_queue_num))?;
+
+    // Copy whole packets, a truncated packet would be rewritten with the wrong lengths
+    // and checksum
+    nf_queue
+        .set_copy_range(nf_queue_num, queue_config.copy_range)
+        .context_with("Error setting netfilter queue copy range", ea!(queue = nf_queue_num))?;
+    if let Some(max_len) = queue_config.max_len {
+        nf_queue
+            .set_queue_max_len(nf_queue_num, max_len)
+            .context_with("Error setting netfilter queue max length", ea!(queue = nf_queue_num))?;
+    }
+    nf_queue
+        .set_fail_open(nf_queue_num, matches!(queue_config.fail, QueueFail::Open))
+        .context_with("Error setting netfilter queue fail open", ea!(queue = nf_queue_num))?;
+    nf_queue.set_nonblocking(true);
+    worker.notifier.queue_bound();
+    let fail_verdict = match worker.verdict_config.on_parse_failure {
+        ParseFailureAction::Drop if !worker.verdict_config.dry_run => Verdict::Drop,
+        _ => Verdict::Accept,
+    };
+    let res = process_queue(worker, &log, nf_queue_num, &mut ReassemblingQueue::new(&log, &mut nf_queue, fail_verdict));
+    worker.notifier.stopping();
+    event!(log, LogLevel::Debug, "stopping", "Stopping, unbinding queue");
+    if let Err(e) = nf_queue.unbind(nf_queue_num) {
+        log.err(LogLevel::Warn, "error", e.context("Error unbinding netfilter queue"));
+    }
+    return res;
+}
+
+/// `--version`, the commit and build time (seconds since the epoch) are from
+/// `build.rs`.
+fn version() -> String {
+    return format!(
+        "{} {} (commit {}, built {})",
+        env!("CARGO_BIN_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        env!("GLUE_GIT_COMMIT"),
+        env!("GLUE_BUILD_TIMESTAMP")
+    );
+}
+
+fn main() {
+    match || -> Result<(), loga::Error> {
+        let orig_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |panic_info| {
+            orig_hook(panic_info);
+            process::exit(1);
+        }));
+        let cli_args = vark::<Args>();
+        if cli_args.version.is_some() {
+            println!("{}", version());
+            return Ok(());
+        }
+        let args = load_args(&cli_args)?;
+        let recheck_period = args.recheck_period.unwrap_or(60);
+        let mut modify_config = build_modify_config(&args, recheck_period)?;
+
+        // Show what would happen to one packet and exit, no queue needed
+        if let Some(hex) = &args.parse {
+            let packet = parse_hex(hex).ok_or_else(|| loga::err("--parse isn't a valid hex packet"))?;
+            modify_config.dns_ips = dns_ips(&args)?;
+            if modify_config.dns_ips.is_empty() {
+                return Err(loga::err("--parse needs --dns-ip"));
+            }
+            print!("{}", describe(&packet, &modify_config));
+            return Ok(());
+        }
+
+        // Same, for a whole capture
+        if let Some(replay_in) = &args.replay_in {
+            let Some(replay_out) = &args.replay_out else {
+                return Err(loga::err("--replay-in needs --replay-out"));
+            };
+            modify_config.dns_ips = dns_ips(&args)?;
+            if modify_config.dns_ips.is_empty() {
+                return Err(loga::err("--replay-in needs --dns-ip"));
+            }
+            let input =
+                File::open(replay_in).context_with("Error opening pcap file", ea!(path = replay_in.dbg_str()))?;
+            let output =
+                File::create(
+                    replay_out,
+                ).context_with("Error creating pcap file", ea!(path = replay_out.dbg_str()))?;
+            let counts = replay(BufReader::new(input), BufWriter::new(output), &modify_config)?;
+            println!("Modified {}, unchanged {}, failed {}", counts.modified, counts.unchanged, counts.failed);
+            return Ok(());
+        }
+        let mut nf_queue_nums = args.nf_queue.clone().unwrap_or_default();
+        if let Some(range) = &args.nf_queue_range {
+            let Some((start, end)) = range.split_once(':').and_then(|(start, end)| {
+                return Some((u16::from_str(start).ok()?, u16::from_str(end).ok()?));
+            }) else {
+                return Err(loga::err_with("Invalid --nf-queue-range, expected start:end", ea!(range = range)));
+            };
+            nf_queue_nums.extend(start ..= end);
+        }
+        nf_queue_nums.sort();
+        nf_queue_nums.dedup();
+        if nf_queue_nums.is_empty() {
+            return Err(
+                loga::err("--nf-queue or --nf-queue-range must be specified, on the command line or in the config file"),
+            );
+        }
+        let Some(nf_mark) = args.nf_mark else {
+            return Err(loga::err("--nf-mark must be specified, on the command line or in the config file"));
+        };
+        let nf_mark_mask = args.nf_mark_mask.unwrap_or(u32::MAX);
+        if nf_mark & !nf_mark_mask != 0 {
+            return Err(
+                loga::err_with(
+                    "--nf-mark has bits outside --nf-mark-mask",
+                    ea!(nf_mark = format!("0x{:08x}", nf_mark), nf_mark_mask = format!("0x{:08x}", nf_mark_mask)),
+                ),
+            );
+        }
+        if args.print_nftables.is_some() {
+            let ruleset = nftgen::ruleset(&NftGenConfig {
+                queues: &nf_queue_nums,
+                nf_mark,
+                nf_mark_mask,
+                interfaces: args.interface.as_deref().unwrap_or_default(),
+                dhcp4: args.dns_ip4.as_ref().is_some_and(|ips| !ips.is_empty()),
+            }).ok_or_else(|| loga::err("--print-nftables needs the queues to be one contiguous range"))?;
+            print!("{}", ruleset);
+            return Ok(());
+        }
+        let log_sink = match args.log_target.unwrap_or(LogTarget::Stderr) {
+            LogTarget::Stderr => LogSink::Stderr,
+            LogTarget::Syslog => LogSink::syslog()?,
+            LogTarget::File => {
+                let path = args.log_file.as_ref().context("--log-target file requires --log-file")?;
+                LogSink::file(path).context_with("Error opening log file", ea!(path = path.dbg_str()))?
+            },
+        };
+        let log =
+            EventLog::new(
+                args.log_format.unwrap_or(LogFormat::Text),
+                args.log_level.unwrap_or(LogLevel::Info),
+                log_sink,
+            );
+        let verdict_config = VerdictConfig {
+            nf_mark,
+            nf_mark_mask,
+            modified_verdict: args.verdict.unwrap_or(ModifiedVerdict::Repeat).verdict(),
+            on_parse_failure: args.on_parse_failure.unwrap_or(ParseFailureAction::Accept),
+            on_rate_limit: args.on_rate_limit.unwrap_or(ParseFailureAction::Drop),
+            pre_ip: args.pre_ip.unwrap_or(ParseFailureAction::Drop),
+            on_bad_checksum: args.on_bad_checksum.unwrap_or(ParseFailureAction::Drop),
+            dry_run: args.dry_run.is_some(),
+            pcap_out: match &args.pcap_out {
+                Some(path) => Some(PcapOut::open(&log, path)?),
+                None => None,
+            },
+        };
+        if verdict_config.dry_run {
+            event!(log, LogLevel::Info, "dry_run", "Dry run, packets will be logged but passed through unmodified");
+        }
+
+        // `repeat` verdicts loop unless the rule skips marked packets, a common mistake
+        if !verdict_config.dry_run && verdict_config.modified_verdict == Verdict::Repeat {
+            nftcheck::check(&log, &nf_queue_nums, nf_mark);
+        }
+        let want_ifaces = match (args.interface.clone(), args.interface_index.clone()) {
+            (Some(_), Some(_)) => {
+                return Err(loga::err("Only one of --interface or --interface-index can be specified"));
+            },
+            (Some(names), None) => Some(names.into_iter().map(WantIface::Name).collect::<Vec<_>>()),
+            (None, Some(indexes)) => Some(indexes.into_iter().map(WantIface::Index).collect()),
+            (None, None) => None,
+        };
+        if want_ifaces.is_none() && args.dns_command.is_some() {
+            return Err(loga::err("--dns-command requires --interface or --interface-index"));
+        }
+        let static_dns = want_ifaces.is_none();
+        let live = Arc::new(Reloadable::new(build_live_config(&args, static_dns)?));
+        let extra_dns_ips = dns_ips(&args)?;
+        for (ip, zone) in dns_ips_zoned(&args)? {
+            if ip.unstable_is_unicast_link_local() {
+                event!(
+                    log,
+                    LogLevel::Warn,
+                    "link_local_dns",
+                    "Advertising a link-local DNS address, this is non-standard and many clients ignore it",
+                    ip = ip,
+                    zone = zone.dbg_str()
+                );
+            }
+        }
+        if want_ifaces.is_none() && extra_dns_ips.is_empty() && args.interface_dns.is_none() {
+            return Err(
+                loga::err("One of --interface, --interface-index, --interface-dns or --dns-ip must be specified"),
+            );
+        }
+
+        // Packets only have the outgoing interface index, so resolve names now
+        let system_ifaces = if args.interface_dns.is_some() || args.out_interface.is_some() {
+            NetworkInterface::show().context("Failure listing network interfaces")?
+        } else {
+            vec![]
+        };
+        let mut interface_dns = HashMap::new();
+        if let Some(entries) = &args.interface_dns {
+            for entry in entries {
+                let (name, ip) = parse_interface_dns(entry).map_err(loga::err)?;
+                let Some(iface) = system_ifaces.iter().find(|i| i.name == name) else {
+                    return Err(loga::err_with("Unknown interface in --interface-dns", ea!(interface = name)));
+                };
+                interface_dns.insert(iface.index, ip);
+            }
+        }
+        let out_interfaces = match &args.out_interface {
+            Some(names) => {
+                let mut out = HashSet::new();
+                for name in names {
+                    let Some(iface) = system_ifaces.iter().find(|i| i.name == *name) else {
+                        return Err(loga::err_with("Unknown interface in --out-interface", ea!(interface = name)));
+                    };
+                    out.insert(iface.index);
+                }
+                Some(out)
+            },
+            None => None,
+        };
+        if let Some(rdnss_lifetime) = args.rdnss_lifetime {
+            let effective = modify_config.effective_rdnss_lifetime(rdnss_lifetime);
+            if effective != rdnss_lifetime {
+                event!(
+                    log,
+                    LogLevel::Info,
+                    "rdnss_lifetime_clamped",
+                    "Clamping RDNSS lifetime to the configured minimum/maximum",
+                    rdnss_lifetime = rdnss_lifetime,
+                    effective = effective
+                );
+            }
+            if (effective as u64) < recheck_period {
+                event!(
+                    log,
+                    LogLevel::Warn,
+                    "config_warning",
+                    "RDNSS lifetime is shorter than the recheck period, clients may be left without DNS between RAs",
+                    rdnss_lifetime = effective,
+                    recheck_period = recheck_period
+                );
+            }
+        }
+        let metrics = Arc::new(Metrics::default());
+        let notifier = Arc::new(Notifier::new(&log, nf_queue_nums.len(), want_ifaces.is_some()));
+        if let Some(listen) = args.metrics_listen {
+            metrics::serve(
+                &log,
+                listen,
+                metrics.clone(),
+            ).context_with("Error starting metrics server", ea!(listen = listen))?;
+        }
+        let health = Arc::new(Health::default());
+        health.have_ip.store(want_ifaces.is_none(), Ordering::Relaxed);
+        if let Some(listen) = args.health_listen {
+            health::serve(
+                &log,
+                listen,
+                health.clone(),
+                Duration::from_secs(recheck_period.max(5) * HEALTH_STALL_PERIODS),
+            ).context_with("Error starting health check server", ea!(listen = listen))?;
+        }
+
+        // Stop cleanly on SIGTERM/SIGINT - finish the current packet, then unbind
+        let stop = Arc::new(AtomicBool::new(false));
+        for signal in [libc::SIGTERM, libc::SIGINT] {
+            let stop = stop.clone();
+            unsafe {
+                signal_hook_registry::register(signal, move || stop.store(true, Ordering::Relaxed))
+            }.context("Error registering signal handler")?;
+        }
+
+        // Watch interfaces for IPs, unless using static addresses
+        let supervisor = Supervisor {
+            log: log.clone(),
+            stop: stop.clone(),
+            died: Arc::new(AtomicBool::new(false)),
+            policy: args.watcher_restart.unwrap_or(WatcherRestart::Exit),
+            respawn_delay: WATCHER_RESPAWN_DELAY,
+        };
+        let startup_timed_out = Arc::new(AtomicBool::new(false));
+        let startup_passthrough = Arc::new(AtomicBool::new(false));
+        let mut watcher_recheck_txs = vec![];
+        let iface_states = match want_ifaces {
+            None => {
+                event!(
+                    log,
+                    LogLevel::Info,
+                    "starting",
+                    "Starting, rewriting packets with static DNS IPs",
+                    ips = extra_dns_ips.dbg_str()
+                );
+                None
+            },
+            Some(want_ifaces) => {
+                let mut iface_states = HashMap::new();
+                for want in &want_ifaces {
+                    iface_states.insert(want.label(), IfaceState {
+                        index: None,
+                        ip: None,
+                        withdraw: None,
+                        ra_prefix: None,
+                    });
+                    metrics.interfaces.lock().unwrap().insert(want.label(), IfaceMetrics::default());
+                }
+                let iface_states = Arc::new(Mutex::new(iface_states));
+
+                // Recheck immediately when addresses change, rather than waiting for the next
+                // poll
+                let mut recheck_txs = vec![];
+                let mut recheck_rxs = vec![];
+                for _ in &want_ifaces {
+                    let (recheck_tx, recheck_rx) = channel();
+                    recheck_txs.push(recheck_tx);
+                    recheck_rxs.push(recheck_rx);
+                }
+                watcher_recheck_txs.clone_from(&recheck_txs);
+                spawn({
+                    let log = log.clone();
+                    move || {
+                        let e = watch_ipv6_addr_changes(|| {
+                            event!(
+                                log,
+                                LogLevel::Debug,
+                                "address_change",
+                                "Address change notification, rechecking interfaces"
+                            );
+                            for recheck_tx in &recheck_txs {
+                                _ = recheck_tx.send(());
+                            }
+                        });
+                        log.err(
+                            LogLevel::Warn,
+                            "error",
+                            e.context("Error watching for address changes, falling back to polling"),
+                        );
+                    }
+                });
+
+                // Wait for initial ip, or get next ip
+                for (want, recheck_rx) in Iterator::zip(want_ifaces.into_iter(), recheck_rxs) {
+                    supervisor.spawn(want.label(), {
+                        let iface_states = iface_states.clone();
+                        let stop = stop.clone();
+                        let metrics = metrics.clone();
+                        let notifier = notifier.clone();
+                        let live = live.clone();
+                        let log = log.clone();
+                        let on_ip_change =
+                            args
+                                .on_ip_change
+                                .clone()
+                                .map(
+                                    |command| spawn_ip_change_hook(
+                                        &log,
+                                        command,
+                                        want.label(),
+                                        HOOK_DEBOUNCE,
+                                    ),
+                                );
+                        let dns_command = args.dns_command.clone();
+                        let health = health.clone();
+                        move || {
+                            watch_interface(Watcher {
+                                log: &log,
+                                stop: &stop,
+                                metrics: &metrics,
+                                notifier: &notifier,
+                                iface_states: &iface_states,
+                                live: &live,
+                                on_ip_change: on_ip_change.clone(),
+                                dns_command: dns_command.as_deref(),
+                                health: &health,
+                            }, &want, &recheck_rx);
+                        }
+                    });
+                }
+                match verdict_config.pre_ip {
+                    ParseFailureAction::Drop => event!(
+                        log,
+                        LogLevel::Info,
+                        "starting",
+                        "Starting, dropping packets to each interface until it has a global IP"
+                    ),
+                    ParseFailureAction::Accept => event!(
+                        log,
+                        LogLevel::Info,
+                        "starting",
+                        "Starting, passing packets to each interface through until it has a global IP"
+                    ),
+                }
+                if let Some(timeout) = args.startup_timeout {
+                    StartupWatch {
+                        log: log.clone(),
+                        stop: stop.clone(),
+                        health: health.clone(),
+                        timeout: Duration::from_secs(timeout),
+                        action: args.on_startup_timeout.unwrap_or(StartupTimeoutAction::Exit),
+                        timed_out: startup_timed_out.clone(),
+                        passthrough: startup_passthrough.clone(),
+                        check_period: STARTUP_CHECK_PERIOD,
+                    }.spawn();
+                }
+                Some(iface_states)
+            },
+        };
+        if let Some(path) = &args.control_socket {
+            control::serve(&log, path, Arc::new(Control {
+                log: log.clone(),
+                metrics: metrics.clone(),
+                iface_states: iface_states.clone(),
+                live: live.clone(),
+                recheck_txs: watcher_recheck_txs.clone(),
+            })).context_with("Error starting control socket", ea!(path = path.dbg_str()))?;
+        }
+
+        // On SIGHUP reopen the log file and reload the config file, then recheck now (ex:
+        // after a PPP reconnect) rather than waiting for the next poll or address change
+        // notification
+        spawn_signal_handler(libc::SIGHUP, {
+            let log = log.clone();
+            let live = live.clone();
+            move || {
+                if let Err(e) = log.reopen() {
+                    log.err(LogLevel::Warn, "log_reopen_failed", e.context("Error reopening log file"));
+                }
+                event!(log, LogLevel::Info, "sighup", "Received SIGHUP, reloading config and rechecking interfaces");
+                if cli_args.config.is_some() {
+                    match load_args(&cli_args).and_then(|args| build_live_config(&args, static_dns)) {
+                        Ok(new) => {
+                            let changes = live.get().changes(&new);
+                            for (setting, old, new) in &changes {
+                                event!(
+                                    log,
+                                    LogLevel::Info,
+                                    "config_changed",
+                                    "Setting changed by reload",
+                                    setting = setting,
+                                    old = old,
+                                    new = new
+                                );
+                            }
+                            if changes.is_empty() {
+                                event!(log, LogLevel::Info, "config_unchanged", "Reloaded config, no settings changed");
+                            }
+                            live.set(new);
                         },
+                        // Keep the current settings
+                        Err(e) => log.err(LogLevel::Error, "reload_failed", e),
                     }
                 }
+                for recheck_tx in &watcher_recheck_txs {
+                    _ = recheck_tx.send(());
+                }
             }
-            eprintln!("Lost IP, switching from modifying packets to dropping them");
+        }).context("Error registering SIGHUP handler")?;
+
+        // On SIGUSR1 print the counters, for debugging without `--metrics-listen`
+        spawn_signal_handler(libc::SIGUSR1, {
+            let metrics = metrics.clone();
+            move || eprint!("{}", metrics.dump(SystemTime::now()))
+        }).context("Error registering SIGUSR1 handler")?;
 
-            // Drop messages again
-            loop {
-                nf_queue_msg = nf_queue.recv().context("Error reading netfilter queue")?;
-                if let Some(Some(new_ip)) = ip_rxtx.lock().unwrap().take() {
-                    ip = new_ip;
-                    break;
+        // One worker per queue, if any fails stop the rest
+        let rate_limiter = args.max_ra_per_sec.map(|n| Mutex::new(RateLimiter::new(n)));
+        let remaining_packets = args.max_packets.map(AtomicU64::new);
+        let worker = Worker {
+            log: &log,
+            stop: &stop,
+            metrics: &metrics,
+            notifier: &notifier,
+            live: &live,
+            verdict_config: &verdict_config,
+            rate_limiter: rate_limiter.as_ref(),
+            interface_dns: &interface_dns,
+            out_interfaces: out_interfaces.as_ref(),
+            iface_states: iface_states.as_deref(),
+            remaining_packets: remaining_packets.as_ref(),
+            startup_passthrough: Some(&startup_passthrough),
+            health: Some(&health),
+        };
+        let queue_config = QueueConfig {
+            copy_range: args.copy_range.unwrap_or(u16::MAX),
+            max_len: args.queue_maxlen,
+            fail: args.queue_fail.unwrap_or(QueueFail::Closed),
+        };
+        event!(
+            log,
+            LogLevel::Info,
+            "queue_config",
+            "Netfilter queue settings",
+            copy_range = queue_config.copy_range,
+            max_len = queue_config.max_len.map(|l| l.to_string()).unwrap_or_else(|| "default".to_string()),
+            fail = queue_config.fail.dbg_str()
+        );
+        if queue_config.copy_range < 1280 {
+            event!(
+                log,
+                LogLevel::Warn,
+                "copy_range_small",
+                "--copy-range is less than the IPv6 minimum MTU, larger packets won't be rewritten correctly",
+                copy_range = queue_config.copy_range
+            );
+        }
+        let res = scope(|s| {
+            let workers = nf_queue_nums.iter().map(|nf_queue_num| s.spawn(move || {
+                let res = run_queue(worker, *nf_queue_num, queue_config);
+                if res.is_err() {
+                    worker.stop.store(true, Ordering::Relaxed);
                 }
-                nf_queue_msg.set_verdict(Verdict::Drop);
-                nf_queue.verdict(nf_queue_msg).context("Error setting netfilter message verdict")?;
-            };
+                return res;
+            })).collect::<Vec<_>>();
+            let mut res = Ok(());
+            for worker in workers {
+                if let Err(e) = worker.join().unwrap() {
+                    if res.is_ok() {
+                        res = Err(e);
+                    } else {
+                        log.err(LogLevel::Error, "error", e);
+                    }
+                }
+            }
+            return res;
+        });
+        event!(log, LogLevel::Info, "stopped", "Stopped");
+        if res.is_ok() && supervisor.died.load(Ordering::Relaxed) {
+            return Err(loga::err("An interface watcher stopped unexpectedly"));
+        }
+        if res.is_ok() && startup_timed_out.load(Ordering::Relaxed) {
+            return Err(loga::err("No interface got a global IP within --startup-timeout"));
         }
+        return res;
     }() {
         Ok(_) => (),
         Err(e) => fatal(e),
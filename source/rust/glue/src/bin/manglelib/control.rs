@@ -0,0 +1,112 @@
+//! `--control-socket`, a line protocol over a Unix socket for querying and poking
+//! the running daemon (ex: `socat - UNIX-CONNECT:/run/spaghatteway.sock`).
+//!
+//! Each line is a command, each gets a one line response: `status` gets a JSON
+//! object, others `ok`, and failures `error: <message>`.
+use {
+    super::events::{
+        EventLog,
+        LogLevel,
+    },
+    loga::ErrContext,
+    std::{
+        fs::{
+            remove_file,
+            symlink_metadata,
+        },
+        io::{
+            self,
+            BufRead,
+            BufReader,
+            Write,
+        },
+        net::Ipv6Addr,
+        os::unix::{
+            fs::FileTypeExt,
+            net::{
+                UnixListener,
+                UnixStream,
+            },
+        },
+        path::Path,
+        str::FromStr,
+        sync::Arc,
+        thread::spawn,
+    },
+};
+
+/// What the control commands act on.
+pub trait ControlHandler: Send + Sync {
+    /// For `status`: interfaces, addresses and counters.
+    fn status(&self) -> serde_json::Value;
+
+    /// For `recheck`: poll the interfaces now.
+    fn recheck(&self);
+
+    /// For `set-dns`: advertise `ip` for `interface` (or all interfaces) until the next
+    /// poll.
+    fn set_dns(&self, ip: Ipv6Addr, interface: Option<&str>) -> Result<(), String>;
+}
+
+/// Run one command line, returning the response line (without the newline).
+pub fn handle_line(handler: &dyn ControlHandler, line: &str) -> String {
+    let mut words = line.split_whitespace();
+    let res = match (words.next(), words.next(), words.next(), words.next()) {
+        (Some("status"), None, _, _) => return handler.status().to_string(),
+        (Some("recheck"), None, _, _) => {
+            handler.recheck();
+            Ok(())
+        },
+        (Some("set-dns"), Some(ip), interface, None) => match Ipv6Addr::from_str(ip) {
+            Ok(ip) => handler.set_dns(ip, interface),
+            Err(e) => Err(format!("Invalid address [{}]: {}", ip, e)),
+        },
+        (Some("set-dns"), _, _, _) => Err("Usage: set-dns <addr> [interface]".to_string()),
+        (Some(command @ ("status" | "recheck")), _, _, _) => Err(format!("{} takes no arguments", command)),
+        (Some(command), _, _, _) => Err(format!("Unknown command [{}], expected status, recheck or set-dns", command)),
+        (None, _, _, _) => Err("Empty command".to_string()),
+    };
+    match res {
+        Ok(()) => return "ok".to_string(),
+        Err(e) => return format!("error: {}", e),
+    }
+}
+
+fn respond(conn: UnixStream, handler: &dyn ControlHandler) -> io::Result<()> {
+    let mut writer = conn.try_clone()?;
+    for line in BufReader::new(conn).lines() {
+        let line = line?;
+        writeln!(writer, "{}", handle_line(handler, &line))?;
+    }
+    return Ok(());
+}
+
+/// Bind `path` (replacing a stale socket) and serve commands from a background
+/// thread, one thread per connection.
+pub fn serve(log: &EventLog, path: &Path, handler: Arc<dyn ControlHandler>) -> io::Result<()> {
+    // Left over from a previous run that didn't exit cleanly
+    if symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+        remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    let log = log.clone();
+    spawn(move || {
+        for conn in listener.incoming() {
+            let conn = match conn {
+                Ok(c) => c,
+                Err(e) => {
+                    log.err(LogLevel::Warn, "control_error", e.context("Error accepting control connection"));
+                    continue;
+                },
+            };
+            let log = log.clone();
+            let handler = handler.clone();
+            spawn(move || {
+                if let Err(e) = respond(conn, handler.as_ref()) {
+                    log.err(LogLevel::Debug, "control_error", e.context("Error serving control connection"));
+                }
+            });
+        }
+    });
+    return Ok(());
+}
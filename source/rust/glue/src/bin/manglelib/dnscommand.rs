@@ -0,0 +1,70 @@
+//! `--dns-command`, getting the address to advertise from a script rather than an
+//! interface.
+use {
+    loga::{
+        ea,
+        DebugDisplay,
+        ResultContext,
+    },
+    std::{
+        net::Ipv6Addr,
+        os::unix::process::CommandExt,
+        process::{
+            Command,
+            Stdio,
+        },
+        str::FromStr,
+        thread::sleep,
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+};
+
+/// How often to check whether the command has exited.
+const POLL_PERIOD: Duration = Duration::from_millis(50);
+
+/// The address in a `--dns-command`'s output: exactly one IPv6 address, surrounding
+/// whitespace ignored.
+pub fn parse_output(stdout: &[u8]) -> Result<Ipv6Addr, String> {
+    let text = std::str::from_utf8(stdout).map_err(|e| format!("Output isn't UTF-8: {}", e))?.trim();
+    return Ipv6Addr::from_str(text).map_err(|e| format!("Output [{}] isn't an IPv6 address: {}", text, e));
+}
+
+/// Run `command` with `sh -c`, returning the address it printed. If it hasn't exited
+/// within `timeout` it's killed (along with anything it started) and this fails.
+pub fn run_dns_command(command: &str, timeout: Duration) -> Result<Ipv6Addr, loga::Error> {
+    let mut child =
+        Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // Its own process group, so the whole group can be killed
+            .process_group(0)
+            .spawn()
+            .context_with("Error starting DNS command", ea!(command = command))?;
+    let deadline = Instant::now() + timeout;
+    loop {
+        if child.try_wait().context_with("Error waiting for DNS command", ea!(command = command))?.is_some() {
+            break;
+        }
+        if Instant::now() >= deadline {
+            unsafe {
+                libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+            }
+            _ = child.wait();
+            return Err(loga::err_with("DNS command timed out", ea!(command = command, timeout = timeout.dbg_str())));
+        }
+        sleep(POLL_PERIOD);
+    }
+    let output = child.wait_with_output().context_with("Error reading DNS command output", ea!(command = command))?;
+    if !output.status.success() {
+        return Err(
+            loga::err_with("DNS command exited with error code", ea!(command = command, output = output.dbg_str())),
+        );
+    }
+    return parse_output(&output.stdout).map_err(|e| loga::err_with(e, ea!(command = command)));
+}
@@ -0,0 +1,259 @@
+use {
+    aargvark::Aargvark,
+    loga::{
+        ea,
+        Log,
+    },
+    serde::Deserialize,
+    std::{
+        fs::{
+            File,
+            OpenOptions,
+        },
+        io::{
+            self,
+            Write,
+        },
+        path::{
+            Path,
+            PathBuf,
+        },
+        sync::{
+            Arc,
+            Mutex,
+        },
+        time::{
+            SystemTime,
+            UNIX_EPOCH,
+        },
+    },
+};
+
+#[derive(Aargvark, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogLevel {
+    /// Debug, plus every queued packet decoded
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    /// Only failures that stop something, like a queue worker or a config reload
+    Error,
+}
+
+impl LogLevel {
+    pub fn loga(self) -> loga::Level {
+        match self {
+            LogLevel::Trace | LogLevel::Debug => return loga::DEBUG,
+            LogLevel::Info => return loga::INFO,
+            // Loga has nothing higher, events below `Error` are filtered by `EventLog`
+            LogLevel::Warn | LogLevel::Error => return loga::WARN,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            LogLevel::Trace => return "trace",
+            LogLevel::Debug => return "debug",
+            LogLevel::Info => return "info",
+            LogLevel::Warn => return "warn",
+            LogLevel::Error => return "error",
+        }
+    }
+}
+
+#[derive(Aargvark, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogFormat {
+    /// Human readable, via loga
+    Text,
+    /// One JSON object per line, with `ts`, `level`, `event`, `message` and event
+    /// specific fields
+    Json,
+}
+
+#[derive(Aargvark, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogTarget {
+    Stderr,
+    /// The local syslog, facility `daemon`
+    Syslog,
+    /// Append to `--log-file`, reopened on SIGHUP (for logrotate)
+    File,
+}
+
+/// Where log lines are written, see `LogTarget`.
+pub enum LogSink {
+    Stderr,
+    Syslog(Mutex<syslog::Logger<syslog::LoggerBackend, syslog::Formatter3164>>),
+    File {
+        path: PathBuf,
+        file: Mutex<File>,
+    },
+}
+
+impl LogSink {
+    /// Connect to the local syslog.
+    pub fn syslog() -> Result<LogSink, loga::Error> {
+        let logger = syslog::unix(syslog::Formatter3164 {
+            facility: syslog::Facility::LOG_DAEMON,
+            hostname: None,
+            process: "spaghatteway".to_string(),
+            pid: std::process::id(),
+        }).map_err(|e| loga::err_with("Error connecting to syslog", ea!(err = e)))?;
+        return Ok(LogSink::Syslog(Mutex::new(logger)));
+    }
+
+    /// Open `path` for appending, creating it if it doesn't exist.
+    pub fn file(path: &Path) -> io::Result<LogSink> {
+        return Ok(LogSink::File {
+            path: path.to_path_buf(),
+            file: Mutex::new(OpenOptions::new().create(true).append(true).open(path)?),
+        });
+    }
+}
+
+/// Logs discrete events, either as text or JSON lines. Use with `event!`.
+#[derive(Clone)]
+pub struct EventLog {
+    pub log: Log,
+    pub format: LogFormat,
+    pub level: LogLevel,
+    /// Added to every JSON event, for text these are in `log`.
+    fields: Vec<(&'static str, String)>,
+    sink: Arc<LogSink>,
+}
+
+impl EventLog {
+    pub fn new(format: LogFormat, level: LogLevel, sink: LogSink) -> EventLog {
+        return EventLog {
+            log: Log::new_root(level.loga()),
+            format,
+            level,
+            fields: vec![],
+            sink: Arc::new(sink),
+        };
+    }
+
+    /// Whether text is logged via `log` (to stderr) rather than by `text`.
+    pub fn text_via_loga(&self) -> bool {
+        return matches!(*self.sink, LogSink::Stderr);
+    }
+
+    /// Reopen the log file, so it can be rotated. Does nothing for other targets.
+    pub fn reopen(&self) -> io::Result<()> {
+        if let LogSink::File { path, file } = &*self.sink {
+            *file.lock().unwrap() = OpenOptions::new().create(true).append(true).open(path)?;
+        }
+        return Ok(());
+    }
+
+    /// Write a line (without the newline) to the sink.
+    fn write_line(&self, level: LogLevel, line: &str) {
+        match &*self.sink {
+            LogSink::Stderr => {
+                _ = std::io::stderr().write_all(format!("{}\n", line).as_bytes());
+            },
+            LogSink::Syslog(logger) => {
+                let mut logger = logger.lock().unwrap();
+                _ = match level {
+                    LogLevel::Trace | LogLevel::Debug => logger.debug(line),
+                    LogLevel::Info => logger.info(line),
+                    LogLevel::Warn => logger.warning(line),
+                    LogLevel::Error => logger.err(line),
+                };
+            },
+            LogSink::File { file, .. } => {
+                _ = writeln!(file.lock().unwrap(), "{}", line);
+            },
+        }
+    }
+
+    /// Add the queue number to all events.
+    pub fn fork_queue(&self, queue: u16) -> EventLog {
+        let mut out = self.clone();
+        out.log = self.log.fork(ea!(queue = queue));
+        out.fields.push(("queue", queue.to_string()));
+        return out;
+    }
+
+    /// Write a JSON event line, if `level` is enabled.
+    pub fn json(&self, level: LogLevel, event: &str, message: &str, fields: Vec<(&str, String)>) {
+        if level < self.level {
+            return;
+        }
+        let mut out = serde_json::Map::new();
+        out.insert(
+            "ts".to_string(),
+            serde_json::json!(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64()),
+        );
+        out.insert("level".to_string(), serde_json::json!(level.name()));
+        out.insert("event".to_string(), serde_json::json!(event));
+        out.insert("message".to_string(), serde_json::json!(message));
+        for (k, v) in self.fields.iter().cloned().chain(fields) {
+            out.insert(k.to_string(), serde_json::json!(v));
+        }
+        self.write_line(level, &serde_json::Value::Object(out).to_string());
+    }
+
+    /// Write a text event line, if `level` is enabled. For targets other than stderr,
+    /// where `log` isn't used.
+    pub fn text(&self, level: LogLevel, message: &str, fields: Vec<(&str, String)>) {
+        if level < self.level {
+            return;
+        }
+        let mut line = String::new();
+        if let LogSink::File { .. } = &*self.sink {
+            // Syslog has its own timestamp and severity
+            line.push_str(
+                &format!(
+                    "{:.3} {} ",
+                    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64(),
+                    level.name().to_uppercase()
+                ),
+            );
+        }
+        line.push_str(message);
+        for (k, v) in self.fields.iter().cloned().chain(fields) {
+            line.push_str(&format!(" {}={}", k, v));
+        }
+        self.write_line(level, &line);
+    }
+
+    /// Log an error event.
+    pub fn err(&self, level: LogLevel, event: &str, e: loga::Error) {
+        if level < self.level {
+            return;
+        }
+        match self.format {
+            LogFormat::Text if self.text_via_loga() => self.log.log_err(level.loga(), e),
+            LogFormat::Text => self.text(level, &e.to_string(), vec![]),
+            LogFormat::Json => self.json(level, event, "Error", vec![("error", e.to_string())]),
+        }
+    }
+}
+
+/// `event!(log, LogLevel::Info, "event_name", "Message", field = value, ...)`
+macro_rules! event{
+    ($log: expr, $level: expr, $event: literal, $message: expr $(, $k: ident = $v: expr) * $(,) ?) => {
+        {
+            let log: &$crate::manglelib::events::EventLog = &$log;
+            match log.format {
+                $crate::manglelib::events::LogFormat::Text if log.text_via_loga() => {
+                    // Loga can't filter `Trace` from `Debug` or `Warn` from `Error`
+                    if $level >= log.level {
+                        log.log.log_with($level.loga(), $message, loga::ea!($($k = $v), *));
+                    }
+                },
+                $crate::manglelib::events::LogFormat::Text => {
+                    log.text($level, $message, vec![$((stringify!($k), $v.to_string())), *]);
+                },
+                $crate::manglelib::events::LogFormat::Json => {
+                    log.json($level, $event, $message, vec![$((stringify!($k), $v.to_string())), *]);
+                },
+            }
+        }
+    };
+}
+
+pub(crate) use event;
@@ -0,0 +1,85 @@
+//! `--health-listen`, an HTTP liveness check for supervisors.
+use {
+    super::{
+        events::EventLog,
+        http::{
+            self,
+            Response,
+        },
+    },
+    std::{
+        collections::BTreeMap,
+        io,
+        net::SocketAddr,
+        sync::{
+            atomic::{
+                AtomicBool,
+                Ordering,
+            },
+            Arc,
+            Mutex,
+        },
+        time::{
+            Duration,
+            SystemTime,
+            UNIX_EPOCH,
+        },
+    },
+};
+
+/// Updated by the interface watchers and queue workers.
+#[derive(Default)]
+pub struct Health {
+    /// Whether any watched interface has an address to advertise. Always set with
+    /// static addresses.
+    pub have_ip: AtomicBool,
+    /// When each loop (ex: `interface eth0`, `queue 0`) last went around, in seconds
+    /// since the epoch.
+    heartbeats: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Health {
+    /// Record that the loop `name` went around. Only loops that have beat at least
+    /// once are checked.
+    pub fn beat(&self, name: &str, now: SystemTime) {
+        let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let mut heartbeats = self.heartbeats.lock().unwrap();
+        match heartbeats.get_mut(name) {
+            Some(heartbeat) => *heartbeat = now,
+            None => {
+                heartbeats.insert(name.to_string(), now);
+            },
+        }
+    }
+
+    /// Why the daemon isn't healthy, or `None` if it is. A loop is stalled if it
+    /// hasn't gone around within `max_age`.
+    pub fn problem(&self, now: SystemTime, max_age: Duration) -> Option<String> {
+        let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        for (name, heartbeat) in self.heartbeats.lock().unwrap().iter() {
+            if now.saturating_sub(*heartbeat) > max_age.as_secs() {
+                return Some(format!("{} stalled", name));
+            }
+        }
+        if !self.have_ip.load(Ordering::Relaxed) {
+            return Some("no global IP".to_string());
+        }
+        return None;
+    }
+}
+
+/// Bind `listen` and serve the health check (ex: `/healthz`) from a background
+/// thread.
+pub fn serve(log: &EventLog, listen: SocketAddr, health: Arc<Health>, max_age: Duration) -> io::Result<()> {
+    return http::serve(log, listen, "health", move || {
+        let (status, body) = match health.problem(SystemTime::now(), max_age) {
+            None => ("200 OK", "ok".to_string()),
+            Some(problem) => ("503 Service Unavailable", problem),
+        };
+        return Response {
+            status,
+            content_type: "text/plain",
+            body: format!("{}\n", body),
+        };
+    });
+}
@@ -0,0 +1,85 @@
+use {
+    super::events::{
+        event,
+        EventLog,
+        LogLevel,
+    },
+    loga::{
+        ea,
+        ErrContext,
+    },
+    std::{
+        net::Ipv6Addr,
+        process::Command,
+        sync::mpsc::{
+            channel,
+            Sender,
+        },
+        thread::spawn,
+        time::Duration,
+    },
+};
+
+/// Start a background thread that runs `command` (with `sh -c`) when the IP of
+/// `interface` changes. Send each new IP (`None` when lost) to the returned
+/// sender. Changes are debounced: the command only runs once the IP has been
+/// stable for `debounce`, and only one command runs at a time.
+///
+/// The command gets the IP (or `none`) as `$1` and in `SPAGHATTEWAY_DNS_IP`, and
+/// the interface in `SPAGHATTEWAY_INTERFACE`.
+pub fn spawn_ip_change_hook(
+    log: &EventLog,
+    command: String,
+    interface: String,
+    debounce: Duration,
+) -> Sender<Option<Ipv6Addr>> {
+    let (tx, rx) = channel::<Option<Ipv6Addr>>();
+    let log = log.clone();
+    spawn(move || {
+        let mut last_run = None;
+        while let Ok(mut ip) = rx.recv() {
+            // Wait for it to settle
+            while let Ok(next) = rx.recv_timeout(debounce) {
+                ip = next;
+            }
+            if last_run == Some(ip) {
+                continue;
+            }
+            last_run = Some(ip);
+            let ip = match ip {
+                Some(ip) => ip.to_string(),
+                None => "none".to_string(),
+            };
+            match Command::new("sh")
+                .arg("-c")
+                .arg(&command)
+                .arg("sh")
+                .arg(&ip)
+                .env("SPAGHATTEWAY_DNS_IP", &ip)
+                .env("SPAGHATTEWAY_INTERFACE", &interface)
+                .status() {
+                Ok(status) => {
+                    if !status.success() {
+                        event!(
+                            log,
+                            LogLevel::Warn,
+                            "hook_failed",
+                            "IP change hook exited unsuccessfully",
+                            interface = interface,
+                            ip = ip,
+                            status = status
+                        );
+                    }
+                },
+                Err(e) => {
+                    log.err(
+                        LogLevel::Warn,
+                        "hook_failed",
+                        e.context_with("Error running IP change hook", ea!(interface = interface, ip = ip)),
+                    );
+                },
+            }
+        }
+    });
+    return tx;
+}
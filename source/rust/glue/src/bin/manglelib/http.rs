@@ -0,0 +1,75 @@
+//! A minimal HTTP server for `--metrics-listen` and `--health-listen`. Every path
+//! gets the same response, so requests are read but not parsed.
+use {
+    super::events::{
+        EventLog,
+        LogLevel,
+    },
+    loga::ErrContext,
+    std::{
+        io::{
+            self,
+            Read,
+            Write,
+        },
+        net::{
+            SocketAddr,
+            TcpListener,
+            TcpStream,
+        },
+        thread::spawn,
+        time::Duration,
+    },
+};
+
+pub struct Response {
+    /// Ex: `200 OK`
+    pub status: &'static str,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+fn respond(mut conn: TcpStream, handler: &impl Fn() -> Response) -> io::Result<()> {
+    conn.set_read_timeout(Some(Duration::from_secs(5)))?;
+    conn.set_write_timeout(Some(Duration::from_secs(5)))?;
+    let mut buf = [0u8; 4096];
+    _ = conn.read(&mut buf)?;
+    let response = handler();
+    write!(
+        conn,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        response.body
+    )?;
+    return Ok(());
+}
+
+/// Bind `listen` and answer every connection with `handler` from a background
+/// thread. `name` (ex: `metrics`) is used in error events.
+pub fn serve(
+    log: &EventLog,
+    listen: SocketAddr,
+    name: &'static str,
+    handler: impl Fn() -> Response + Send + 'static,
+) -> io::Result<()> {
+    let listener = TcpListener::bind(listen)?;
+    let log = log.clone();
+    let event = format!("{}_error", name);
+    spawn(move || {
+        for conn in listener.incoming() {
+            let conn = match conn {
+                Ok(c) => c,
+                Err(e) => {
+                    log.err(LogLevel::Warn, &event, e.context(&format!("Error accepting {} connection", name)));
+                    continue;
+                },
+            };
+            if let Err(e) = respond(conn, &handler) {
+                log.err(LogLevel::Debug, &event, e.context(&format!("Error serving {}", name)));
+            }
+        }
+    });
+    return Ok(());
+}
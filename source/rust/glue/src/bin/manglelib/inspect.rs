@@ -0,0 +1,102 @@
+//! `--parse`, showing what would happen to a captured packet.
+use {
+    glue::ra_dns::{
+        list_options,
+        message_kind,
+        modify,
+        parse::parse,
+        MessageKind,
+        ModifyConfig,
+    },
+    std::fmt::Write,
+};
+
+/// Decode a hex packet, as copied from Wireshark or `tcpdump -xx`. Whitespace,
+/// `:` separators and a leading `0x` are ignored.
+pub fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim();
+    let digits =
+        text
+            .strip_prefix("0x")
+            .unwrap_or(text)
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != ':')
+            .collect::<Vec<_>>();
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+    let mut out = vec![];
+    for pair in digits.chunks(2) {
+        out.push(((pair[0].to_digit(16)? << 4) | pair[1].to_digit(16)?) as u8);
+    }
+    return Some(out);
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        write!(out, "{:02x}", b).unwrap();
+    }
+    return out;
+}
+
+fn format_options(options: &[u16]) -> String {
+    return options.iter().map(|o| o.to_string()).collect::<Vec<_>>().join(" ");
+}
+
+/// Options (by type) that occur fewer times in `b` than in `a`.
+fn missing_options(a: &[u16], b: &[u16]) -> Vec<u16> {
+    let mut out = vec![];
+    let mut remaining = b.to_vec();
+    for option in a {
+        match remaining.iter().position(|o| o == option) {
+            Some(i) => {
+                remaining.remove(i);
+            },
+            None => out.push(*option),
+        }
+    }
+    return out;
+}
+
+/// Describe `packet` and what `modify` makes of it with `config`.
+pub fn describe(packet: &[u8], config: &ModifyConfig) -> String {
+    let mut out = String::new();
+    let kind = match (message_kind(packet, config), packet.first().map(|b| b >> 4)) {
+        (Some(MessageKind::Ra), _) => "RA".to_string(),
+        (Some(MessageKind::Dhcp6(type_)), _) => format!("DHCPv6, message type {}", type_),
+        (None, Some(4)) => "IPv4".to_string(),
+        (None, _) => "Unknown".to_string(),
+    };
+    writeln!(out, "Message: {}", kind).unwrap();
+    let before = list_options(packet, config);
+    match (parse(packet), &before) {
+        (Ok(parsed), _) => writeln!(out, "{}", parsed).unwrap(),
+        (Err(_), Some(before)) => writeln!(out, "Options: {}", format_options(before)).unwrap(),
+        (Err(_), None) => { },
+    }
+    let rewritten = match modify(packet, config) {
+        Ok(r) => r,
+        Err(e) => {
+            writeln!(out, "Not rewritten: {}", e).unwrap();
+            return out;
+        },
+    };
+    if rewritten == packet {
+        writeln!(out, "Unchanged").unwrap();
+        return out;
+    }
+    if let (Some(before), Some(after)) = (&before, list_options(&rewritten, config)) {
+        writeln!(out, "Rewritten options: {}", format_options(&after)).unwrap();
+        for (label, options) in [
+            ("Removed", missing_options(before, &after)),
+            ("Added", missing_options(&after, before)),
+        ] {
+            if !options.is_empty() {
+                writeln!(out, "{} options: {}", label, format_options(&options)).unwrap();
+            }
+        }
+    }
+    writeln!(out, "Rewritten: {}", to_hex(&rewritten)).unwrap();
+    return out;
+}
@@ -0,0 +1,152 @@
+use {
+    super::{
+        events::EventLog,
+        http::{
+            self,
+            Response,
+        },
+    },
+    std::{
+        collections::BTreeMap,
+        fmt::Write as _,
+        io,
+        net::{
+            Ipv6Addr,
+            SocketAddr,
+        },
+        sync::{
+            atomic::{
+                AtomicU64,
+                Ordering,
+            },
+            Arc,
+            Mutex,
+        },
+        time::{
+            SystemTime,
+            UNIX_EPOCH,
+        },
+    },
+};
+
+#[derive(Default)]
+pub struct IfaceMetrics {
+    /// The global IP being advertised.
+    pub ip: Option<Ipv6Addr>,
+    pub last_change: Option<SystemTime>,
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    pub packets_modified: AtomicU64,
+    pub packets_dropped: AtomicU64,
+    pub packets_dry_run_modified: AtomicU64,
+    pub parse_failures: AtomicU64,
+    pub packets_rate_limited: AtomicU64,
+    pub packets_bad_checksum: AtomicU64,
+    pub recv_errors_recovered: AtomicU64,
+    pub packets_already_marked: AtomicU64,
+    pub interfaces: Mutex<BTreeMap<String, IfaceMetrics>>,
+}
+
+impl Metrics {
+    pub fn inc(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The counters, as (name, help, counter).
+    pub fn counters(&self) -> [(&'static str, &'static str, &AtomicU64); 8] {
+        return [
+            ("spaghatteway_packets_modified_total", "Packets rewritten and reinjected", &self.packets_modified),
+            ("spaghatteway_packets_dropped_total", "Packets dropped", &self.packets_dropped),
+            (
+                "spaghatteway_packets_dry_run_modified_total",
+                "Packets that would have been rewritten, in dry-run mode",
+                &self.packets_dry_run_modified,
+            ),
+            ("spaghatteway_parse_failures_total", "Packets that couldn't be parsed or rewritten", &self.parse_failures),
+            (
+                "spaghatteway_packets_rate_limited_total",
+                "RAs over --max-ra-per-sec, not rewritten",
+                &self.packets_rate_limited,
+            ),
+            (
+                "spaghatteway_packets_bad_checksum_total",
+                "Packets failing --verify-checksum, not rewritten",
+                &self.packets_bad_checksum,
+            ),
+            (
+                "spaghatteway_recv_errors_recovered_total",
+                "Transient netfilter queue read errors (ex: ENOBUFS), skipped",
+                &self.recv_errors_recovered,
+            ),
+            (
+                "spaghatteway_packets_already_marked_total",
+                "Packets already marked with --nf-mark, accepted unmodified",
+                &self.packets_already_marked,
+            ),
+        ];
+    }
+
+    /// Render in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (name, help, counter) in self.counters() {
+            writeln!(out, "# HELP {} {}", name, help).unwrap();
+            writeln!(out, "# TYPE {} counter", name).unwrap();
+            writeln!(out, "{} {}", name, counter.load(Ordering::Relaxed)).unwrap();
+        }
+        let interfaces = self.interfaces.lock().unwrap();
+        writeln!(out, "# HELP spaghatteway_global_ip_known Whether the interface currently has a global IP").unwrap();
+        writeln!(out, "# TYPE spaghatteway_global_ip_known gauge").unwrap();
+        for (name, iface) in interfaces.iter() {
+            let known = iface.ip.is_some() as u8;
+            writeln!(out, "spaghatteway_global_ip_known{{interface=\"{}\"}} {}", name, known).unwrap();
+        }
+        writeln!(
+            out,
+            "# HELP spaghatteway_last_ip_change_timestamp_seconds When the interface's global IP last changed"
+        ).unwrap();
+        writeln!(out, "# TYPE spaghatteway_last_ip_change_timestamp_seconds gauge").unwrap();
+        for (name, iface) in interfaces.iter() {
+            let secs =
+                iface
+                    .last_change
+                    .map(|t| t.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs())
+                    .unwrap_or_default();
+            writeln!(out, "spaghatteway_last_ip_change_timestamp_seconds{{interface=\"{}\"}} {}", name, secs).unwrap();
+        }
+        return out;
+    }
+
+    /// A plain text snapshot of the counters and interfaces, for SIGUSR1.
+    pub fn dump(&self, now: SystemTime) -> String {
+        let mut out = String::new();
+        for (name, _, counter) in self.counters() {
+            let name = name.trim_start_matches("spaghatteway_").trim_end_matches("_total");
+            writeln!(out, "{}: {}", name, counter.load(Ordering::Relaxed)).unwrap();
+        }
+        for (name, iface) in self.interfaces.lock().unwrap().iter() {
+            let ip = iface.ip.map(|ip| ip.to_string()).unwrap_or_else(|| "none".to_string());
+            match iface.last_change {
+                Some(last_change) => {
+                    let ago = now.duration_since(last_change).unwrap_or_default().as_secs();
+                    writeln!(out, "interface {}: ip {}, changed {}s ago", name, ip, ago).unwrap();
+                },
+                None => writeln!(out, "interface {}: ip {}, never changed", name, ip).unwrap(),
+            }
+        }
+        return out;
+    }
+}
+
+/// Bind `listen` and serve metrics from a background thread.
+pub fn serve(log: &EventLog, listen: SocketAddr, metrics: Arc<Metrics>) -> io::Result<()> {
+    return http::serve(log, listen, "metrics", move || {
+        return Response {
+            status: "200 OK",
+            content_type: "text/plain; version=0.0.4",
+            body: metrics.render(),
+        };
+    });
+}
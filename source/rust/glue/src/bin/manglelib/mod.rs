@@ -1,261 +1,234 @@
 use {
-    flowcontrol::shed,
-    std::net::Ipv6Addr,
+    nfq::{
+        Message,
+        Queue,
+        Verdict,
+    },
+    std::{
+        io::{
+            self,
+            ErrorKind,
+            Read,
+            Write,
+        },
+        os::{
+            fd::AsRawFd,
+            unix::net::UnixStream,
+        },
+        sync::atomic::{
+            AtomicBool,
+            Ordering,
+        },
+        thread::spawn,
+        time::Duration,
+    },
 };
 
+pub mod control;
+pub mod dnscommand;
+pub mod events;
+pub mod health;
+pub mod hook;
+pub mod http;
+pub mod inspect;
+pub mod metrics;
+pub mod netlink;
+pub mod nftcheck;
+pub mod nftgen;
+pub mod pcap;
+pub mod rasource;
+pub mod ratelimit;
+pub mod reassembly;
+pub mod reload;
+pub mod replay;
+pub mod select;
+pub mod startup;
+pub mod supervise;
+pub mod systemd;
 #[cfg(test)]
-mod test_modify_dhcp_ex1;
+mod test_control;
 #[cfg(test)]
-mod test_checksum;
+mod test_dnscommand;
 #[cfg(test)]
-mod test_ra_modify_mtu;
+mod test_events;
 #[cfg(test)]
-mod test_ra_inject_mtu;
-
-#[inline]
-fn checksum_roll(sum32: &mut u32, bytes: &[u8]) {
-    let mut iter = bytes.chunks_exact(2);
-    for x in &mut iter {
-        *sum32 += u16::from_ne_bytes(x.try_into().unwrap()) as u32;
-    }
-    if let Some(remainder) = iter.remainder().first() {
-        let pair = [*remainder, 0x00];
-        *sum32 += u16::from_ne_bytes(pair) as u32;
-    }
-}
+mod test_health;
+#[cfg(test)]
+mod test_hook;
+#[cfg(test)]
+mod test_inspect;
+#[cfg(test)]
+mod test_metrics;
+#[cfg(test)]
+mod test_nftcheck;
+#[cfg(test)]
+mod test_nftgen;
+#[cfg(test)]
+mod test_queue;
+#[cfg(test)]
+mod test_rasource;
+#[cfg(test)]
+mod test_ratelimit;
+#[cfg(test)]
+mod test_reload;
+#[cfg(test)]
+mod test_replay;
+#[cfg(test)]
+mod test_select;
+#[cfg(test)]
+mod test_shutdown;
+#[cfg(test)]
+mod test_signal;
+#[cfg(test)]
+mod test_startup;
+#[cfg(test)]
+mod test_supervise;
+#[cfg(test)]
+mod test_version;
 
-fn checksum_finish(sum32: u32) -> [u8; 2] {
-    let high = (sum32 >> 16) as u16;
-    let low = (sum32 & 0xFFFF) as u16;
-    return (!(high + low).to_be()).to_be_bytes();
-}
+/// How often to wake up while waiting for packets to check if we should stop.
+const STOP_CHECK_PERIOD: Duration = Duration::from_secs(1);
 
-fn icmpv6_udp_checksum(source: &[u8]) -> Option<[u8; 2]> {
-    // * IPv6 pseudo-header https://datatracker.ietf.org/doc/html/rfc2460#section-8.1
-    //
-    // * ICMP https://datatracker.ietf.org/doc/html/rfc4443#section-2.3
-    //
-    //   Pseudo header + whole body
-    //
-    // * UDP https://datatracker.ietf.org/doc/html/rfc768
-    //
-    //   Pseudo header + whole body
-    let mut sum32 = 0u32;
+/// The parts of a netfilter queue message the main loop uses, see `PacketQueue`.
+pub trait QueuedPacket {
+    fn get_payload(&self) -> &[u8];
 
-    // Icmpv6 length (pseudo header)
-    checksum_roll(&mut sum32, source.get(4 .. 6)?);
+    /// The index of the interface the packet is leaving by.
+    fn get_outdev(&self) -> u32;
 
-    // Next header (pseudo header)
-    sum32 += u16::from_ne_bytes([0x00, *source.get(6)?]) as u32;
+    /// The packet's mark when it was queued.
+    fn get_nfmark(&self) -> u32;
 
-    // Source addr (pseudo header), dest addr (pseudo header), payload
-    checksum_roll(&mut sum32, source.get(8..)?);
+    /// Replace the packet, used with a `Repeat` verdict.
+    fn set_payload(&mut self, payload: &[u8]);
+    fn set_nfmark(&mut self, mark: u32);
+    fn set_verdict(&mut self, verdict: Verdict);
 
-    // Then do some rfc magic
-    return Some(checksum_finish(sum32));
+    /// A datagram reassembled from fragments, which are split again after rewriting
+    /// so they're no larger than the originals.
+    fn is_reassembled(&self) -> bool {
+        return false;
+    }
 }
 
-pub fn modify(source: &[u8], ip: Ipv6Addr, mtu: Option<u32>) -> Option<Vec<u8>> {
-    let mut ipv6_packet = vec![];
-    ipv6_packet.reserve(source.len() + 128);
-    ipv6_packet.extend_from_slice(source);
-
-    #[must_use]
-    fn splice(packet: &mut Vec<u8>, start: usize, end: Option<usize>, data: &[u8]) -> Option<()> {
-        if start > packet.len() {
-            return None;
-        }
-        if let Some(end) = end {
-            if end > packet.len() {
-                return None;
-            }
-            packet.splice(start .. end, data.iter().cloned());
-        } else {
-            packet.splice(start.., data.iter().cloned());
-        }
-        return Some(());
+impl QueuedPacket for Message {
+    fn get_payload(&self) -> &[u8] {
+        return Message::get_payload(self);
     }
 
-    #[must_use]
-    fn replace_u16(packet: &mut Vec<u8>, start: usize, data: &[u8; 2]) -> Option<()> {
-        packet.get_mut(start .. start + 2)?.copy_from_slice(data);
-        return Some(());
+    fn get_outdev(&self) -> u32 {
+        return Message::get_outdev(self);
     }
 
-    fn read_u16(packet: &[u8], start: usize) -> Option<u16> {
-        return Some(u16::from_be_bytes(packet.get(start .. start + 2)?.try_into().unwrap()));
+    fn get_nfmark(&self) -> u32 {
+        return Message::get_nfmark(self);
     }
 
-    const IPV6_PAYLOAD_START: usize = 40;
-    match *ipv6_packet.get(6)? {
-        // ICMP
-        //
-        // * https://datatracker.ietf.org/doc/html/rfc4443
-        //
-        // RA
-        //
-        // * https://datatracker.ietf.org/doc/html/rfc4861#section-4.2
-        //
-        // Only replace RA if present.  If it's not present, it may come via DHCP so don't
-        // add things here and confuse devices (also need the option for a lifetime to
-        // copy).
-        58 => {
-            // Confirm it's RA
-            let Some(type_) = ipv6_packet.get(IPV6_PAYLOAD_START) else {
-                return None;
-            };
-            if *type_ != 134 {
-                return None;
-            }
-
-            // Modify RA
-            const OPT_MTU: u8 = 5;
-            const OPT_RDNSS: u8 = 25;
-            const RA_FIXED_HEADER_SIZE: usize = 16;
-            const RA_OPTIONS_START: usize = IPV6_PAYLOAD_START + RA_FIXED_HEADER_SIZE;
-
-            // Set other info flag
-            *ipv6_packet.get_mut(IPV6_PAYLOAD_START + 5)? |= 0x40;
-
-            // Copy options, find + filter out RDNSS
-            struct FoundRdnss {
-                lifetime: u16,
-            }
+    fn set_payload(&mut self, payload: &[u8]) {
+        Message::set_payload(self, payload);
+    }
 
-            let mut found_rdnss = None;
-            let mut at_option_start = RA_OPTIONS_START;
-            let mut new_options = vec![];
-            new_options.reserve(ipv6_packet.len() - IPV6_PAYLOAD_START);
-            let mut modify = false;
-            if mtu.is_some() {
-                modify = true;
-            }
-            loop {
-                if at_option_start == ipv6_packet.len() {
-                    break;
-                }
-                let at_option_type = *ipv6_packet.get(at_option_start)?;
-                let at_option_length = *ipv6_packet.get(at_option_start + 1)? as usize * 8;
-                eprintln!("option type {} len {}; {:?}", at_option_type, at_option_length, mtu);
-                shed!{
-                    'next_option _;
-                    if at_option_type == OPT_RDNSS {
-                        found_rdnss = Some(FoundRdnss { lifetime: read_u16(&ipv6_packet, at_option_start + 4)? });
-                        modify = true;
-                        break 'next_option;
-                    }
-                    if mtu.is_some() && at_option_type == OPT_MTU {
-                        break 'next_option;
-                    }
-                    // Keep anything we're not going to modify
-                    new_options.extend_from_slice(ipv6_packet.get(at_option_start .. at_option_start + at_option_length)?);
-                }
-                at_option_start += at_option_length;
-            }
-            if !modify {
-                return Some(source.to_vec());
-            }
+    fn set_nfmark(&mut self, mark: u32) {
+        Message::set_nfmark(self, mark);
+    }
 
-            // Create custom MTU
-            if let Some(mtu) = mtu {
-                new_options.push(OPT_MTU);
-                new_options.push(1u8);
-                new_options.extend_from_slice(&[0, 0]);
-                new_options.extend(mtu.to_be_bytes());
-            }
+    fn set_verdict(&mut self, verdict: Verdict) {
+        Message::set_verdict(self, verdict);
+    }
+}
 
-            // Generate custom RDNSS
-            if let Some(found_rdnss) = found_rdnss {
-                new_options.push(OPT_RDNSS);
-                let lifetime_bytes = found_rdnss.lifetime.to_be_bytes();
-                let ip_bytes = ip.octets();
-                new_options.push(((1 + 1 + 2 + lifetime_bytes.len() + ip_bytes.len()) / 8) as u8);
-                new_options.extend_from_slice(&[0, 0]);
-                new_options.extend(lifetime_bytes);
-                new_options.extend(ip_bytes);
-            }
+/// The parts of the netfilter queue the main loop uses, so the loop can be tested
+/// without a real queue.
+pub trait PacketQueue {
+    type Message: QueuedPacket;
 
-            // Replace options
-            splice(&mut ipv6_packet, RA_OPTIONS_START, None, &new_options)?;
+    /// Get the next message without blocking, or `None` if there isn't one ready.
+    fn try_recv(&mut self) -> io::Result<Option<Self::Message>>;
 
-            // Update ipv6 payload length
-            replace_u16(&mut ipv6_packet, 4, &((RA_FIXED_HEADER_SIZE + new_options.len()) as u16).to_be_bytes())?;
+    /// Block until a message may be ready or `timeout` elapses.
+    fn wait(&mut self, timeout: Duration);
 
-            // Recalc checksum
-            ipv6_packet.get_mut(IPV6_PAYLOAD_START + 2 .. IPV6_PAYLOAD_START + 4)?.fill(0);
-            let new_checksum = icmpv6_udp_checksum(&ipv6_packet)?;
-            replace_u16(&mut ipv6_packet, IPV6_PAYLOAD_START + 2, &new_checksum)?;
-        },
-        // UDP (DHCPv6)
-        //
-        // * https://datatracker.ietf.org/doc/html/rfc8415
-        17 => {
-            const UDP_FIXED_HEADER_SIZE: usize = 8;
+    /// Send the message's verdict (and new payload and mark, if set) back.
+    fn verdict(&mut self, message: Self::Message) -> io::Result<()>;
+}
 
-            // Confirm it's reply
-            if *ipv6_packet.get(IPV6_PAYLOAD_START + UDP_FIXED_HEADER_SIZE)? != 7 {
-                return None;
-            }
+impl PacketQueue for Queue {
+    type Message = Message;
 
-            // Copy + filter out options
-            const OPT_DNS: &[u8] = &[0x00, 0x17];
-            const DHCP_FIXED_HEADER_SIZE: usize = 4;
-            const DHCP_OPTIONS_START: usize = IPV6_PAYLOAD_START + UDP_FIXED_HEADER_SIZE + DHCP_FIXED_HEADER_SIZE;
-            let mut at_option_start = DHCP_OPTIONS_START;
-            let mut new_options = vec![];
-            let mut found_dns = false;
-            new_options.reserve(ipv6_packet.len() - IPV6_PAYLOAD_START);
-            loop {
-                if at_option_start == ipv6_packet.len() {
-                    break;
-                }
-                let at_option_type = ipv6_packet.get(at_option_start .. at_option_start + 2)?;
-                let at_option_length = read_u16(&ipv6_packet, at_option_start + 2)? as usize + 4;
-                shed!{
-                    'next_option _;
-                    if at_option_type == OPT_DNS {
-                        // Drop existing DNS
-                        found_dns = true;
-                        break 'next_option;
-                    }
-                    // Keep anything not DNS
-                    new_options.extend_from_slice(ipv6_packet.get(at_option_start .. at_option_start + at_option_length)?);
-                }
-                at_option_start += at_option_length;
-            }
-            if !found_dns {
-                return Some(source.to_vec());
-            }
+    fn try_recv(&mut self) -> io::Result<Option<Message>> {
+        match self.recv() {
+            Ok(m) => return Ok(Some(m)),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(None),
+            Err(e) => return Err(e),
+        }
+    }
 
-            // Generate custom DNS option
-            new_options.extend_from_slice(OPT_DNS);
-            new_options.extend_from_slice(
-                // Length (16 bytes, 1 ip)
-                &[0x00, 0x10],
-            );
-            let ip_bytes = ip.octets();
-            new_options.extend(ip_bytes);
+    fn wait(&mut self, timeout: Duration) {
+        let mut pollfd = libc::pollfd {
+            fd: self.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
 
-            // Replace options
-            splice(&mut ipv6_packet, DHCP_OPTIONS_START, None, &new_options)?;
+        // Errors (ex: EINTR from a signal) just mean we check sooner
+        unsafe {
+            libc::poll(&mut pollfd, 1, timeout.as_millis() as i32);
+        }
+    }
 
-            // Update payload length in udp header
-            let new_len = UDP_FIXED_HEADER_SIZE + DHCP_FIXED_HEADER_SIZE + new_options.len();
-            replace_u16(&mut ipv6_packet, IPV6_PAYLOAD_START + 4, &(new_len as u16).to_be_bytes())?;
+    fn verdict(&mut self, message: Message) -> io::Result<()> {
+        return Queue::verdict(self, message);
+    }
+}
 
-            // Update payload length in ipv6 header
-            replace_u16(&mut ipv6_packet, 4, &(new_len as u16).to_be_bytes())?;
+/// Errors from reading the queue that don't mean it's broken: `ENOBUFS` (the kernel
+/// dropped messages because we fell behind, those are lost but later ones will
+/// arrive) and `EINTR`.
+pub fn is_transient_recv_error(e: &io::Error) -> bool {
+    return e.kind() == ErrorKind::Interrupted || e.raw_os_error() == Some(libc::ENOBUFS);
+}
 
-            // Recalc checksum
-            ipv6_packet.get_mut(IPV6_PAYLOAD_START + 6 .. IPV6_PAYLOAD_START + 8)?.fill(0);
-            let new_checksum = icmpv6_udp_checksum(&ipv6_packet)?;
-            replace_u16(&mut ipv6_packet, IPV6_PAYLOAD_START + 6, &new_checksum)?;
-        },
-        _ => {
-            return None;
-        },
+/// Wait for the next message. Returns `None` once `stop` is set. The queue must be
+/// non-blocking. `on_wake` is called each time around, including while idle (ex: for
+/// a heartbeat).
+pub fn recv<Q: PacketQueue>(
+    queue: &mut Q,
+    stop: &AtomicBool,
+    mut on_wake: impl FnMut(),
+) -> io::Result<Option<Q::Message>> {
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+        on_wake();
+        if let Some(m) = queue.try_recv()? {
+            return Ok(Some(m));
+        }
+        queue.wait(STOP_CHECK_PERIOD);
     }
+}
 
-    // Done
-    return Some(ipv6_packet);
+/// Call `f` from a background thread whenever `signal` arrives. Signals arriving
+/// close together may only call `f` once.
+pub fn spawn_signal_handler(signal: libc::c_int, mut f: impl FnMut() + Send + 'static) -> io::Result<()> {
+    // Self-pipe, only a write is safe in the handler itself
+    let (mut signal_rx, signal_tx) = UnixStream::pair()?;
+    signal_tx.set_nonblocking(true)?;
+    unsafe {
+        signal_hook_registry::register(signal, move || {
+            _ = (&signal_tx).write(&[0]);
+        })
+    }?;
+    spawn(move || {
+        let mut buf = [0u8; 64];
+        loop {
+            match signal_rx.read(&mut buf) {
+                Ok(0) => return,
+                Ok(_) => f(),
+                Err(e) if e.kind() == ErrorKind::Interrupted => { },
+                Err(_) => return,
+            }
+        }
+    });
+    return Ok(());
 }
@@ -0,0 +1,212 @@
+use std::{
+    io,
+    mem::{
+        size_of,
+        zeroed,
+    },
+    net::Ipv6Addr,
+    os::fd::{
+        AsRawFd,
+        FromRawFd,
+        OwnedFd,
+    },
+};
+
+fn open_route_socket(groups: u32) -> io::Result<OwnedFd> {
+    let fd = unsafe {
+        libc::socket(libc::AF_NETLINK, libc::SOCK_RAW | libc::SOCK_CLOEXEC, libc::NETLINK_ROUTE)
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let fd = unsafe {
+        OwnedFd::from_raw_fd(fd)
+    };
+    let mut addr: libc::sockaddr_nl = unsafe {
+        zeroed()
+    };
+    addr.nl_family = libc::AF_NETLINK as u16;
+    addr.nl_groups = groups;
+    let res = unsafe {
+        libc::bind(
+            fd.as_raw_fd(),
+            &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+            size_of::<libc::sockaddr_nl>() as u32,
+        )
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    return Ok(fd);
+}
+
+/// Call `on_change` whenever the kernel adds or removes an IPv6 address on any
+/// interface. Blocks forever, only returning on error.
+pub fn watch_ipv6_addr_changes(mut on_change: impl FnMut()) -> io::Error {
+    let fd = match open_route_socket(libc::RTMGRP_IPV6_IFADDR as u32) {
+        Ok(fd) => fd,
+        Err(e) => return e,
+    };
+    let mut buf = [0u8; 8192];
+    loop {
+        let res = unsafe {
+            libc::recv(fd.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0)
+        };
+        if res < 0 {
+            let e = io::Error::last_os_error();
+            match e.raw_os_error() {
+                Some(libc::EINTR) => continue,
+                // Notifications were lost, but something changed
+                Some(libc::ENOBUFS) => { },
+                _ => return e,
+            }
+        }
+
+        // Only subscribed to address changes, so no need to parse - the caller
+        // re-enumerates addresses anyway.
+        on_change();
+    }
+}
+
+// Netlink message layout, see rtnetlink(7)
+const NLMSG_HEADER_SIZE: usize = 16;
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+const NLM_F_REQUEST: u16 = 1;
+const NLM_F_DUMP: u16 = 0x300;
+const RTM_NEWADDR: u16 = 20;
+const RTM_GETADDR: u16 = 22;
+const IFADDRMSG_SIZE: usize = 8;
+const IFA_ADDRESS: u16 = 1;
+const IFA_CACHEINFO: u16 = 6;
+const IFA_FLAGS: u16 = 8;
+pub const IFA_F_TEMPORARY: u32 = 0x01;
+pub const IFA_F_DADFAILED: u32 = 0x08;
+pub const IFA_F_DEPRECATED: u32 = 0x20;
+pub const IFA_F_TENTATIVE: u32 = 0x40;
+
+/// An address from an `RTM_GETADDR` dump.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AddrInfo {
+    pub addr: Ipv6Addr,
+    /// `IFA_F_*`
+    pub flags: u32,
+    /// Seconds, `u32::MAX` for forever.
+    pub valid_lifetime: u32,
+    /// When the address was added, in hundredths of a second since boot.
+    pub created: u32,
+}
+
+fn align4(len: usize) -> usize {
+    return (len + 3) & !3;
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    return Some(u16::from_ne_bytes(data.get(at .. at + 2)?.try_into().unwrap()));
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    return Some(u32::from_ne_bytes(data.get(at .. at + 4)?.try_into().unwrap()));
+}
+
+/// Parse one `recv` worth of an `RTM_GETADDR` dump, adding the addresses on
+/// interface `index` to `out`. Returns `true` once the end of the dump is reached.
+fn parse_addr_dump(buf: &[u8], index: u32, out: &mut Vec<AddrInfo>) -> io::Result<bool> {
+    let bad = || io::Error::new(io::ErrorKind::InvalidData, "Malformed netlink address message");
+    let mut at = 0;
+    while at + NLMSG_HEADER_SIZE <= buf.len() {
+        let msg_len = read_u32(buf, at).ok_or_else(bad)? as usize;
+        let msg_type = read_u16(buf, at + 4).ok_or_else(bad)?;
+        let msg = buf.get(at .. at + msg_len).filter(|_| msg_len >= NLMSG_HEADER_SIZE).ok_or_else(bad)?;
+        at += align4(msg_len);
+        match msg_type {
+            NLMSG_DONE => return Ok(true),
+            NLMSG_ERROR => {
+                let errno = read_u32(msg, NLMSG_HEADER_SIZE).ok_or_else(bad)? as i32;
+                return Err(io::Error::from_raw_os_error(-errno));
+            },
+            RTM_NEWADDR => { },
+            _ => continue,
+        }
+
+        // Ifaddrmsg: family, prefix length, flags, scope, index
+        let body = &msg[NLMSG_HEADER_SIZE..];
+        if *body.first().ok_or_else(bad)? != libc::AF_INET6 as u8 || read_u32(body, 4).ok_or_else(bad)? != index {
+            continue;
+        }
+        let mut addr = None;
+
+        // Only the low 8 bits, the full flags are in an attribute on newer kernels
+        let mut flags = *body.get(2).ok_or_else(bad)? as u32;
+        let mut valid_lifetime = u32::MAX;
+        let mut created = 0;
+        let mut attr_at = IFADDRMSG_SIZE;
+        while attr_at + 4 <= body.len() {
+            let attr_len = read_u16(body, attr_at).ok_or_else(bad)? as usize;
+            let attr_type = read_u16(body, attr_at + 2).ok_or_else(bad)?;
+            let attr = body.get(attr_at + 4 .. attr_at + attr_len).filter(|_| attr_len >= 4).ok_or_else(bad)?;
+            attr_at += align4(attr_len);
+            match attr_type {
+                IFA_ADDRESS => {
+                    addr = Some(Ipv6Addr::from(<[u8; 16]>::try_from(attr).map_err(|_| bad())?));
+                },
+                IFA_CACHEINFO => {
+                    // Ifa_cacheinfo: preferred, valid, created, updated
+                    valid_lifetime = read_u32(attr, 4).ok_or_else(bad)?;
+                    created = read_u32(attr, 8).ok_or_else(bad)?;
+                },
+                IFA_FLAGS => {
+                    flags = read_u32(attr, 0).ok_or_else(bad)?;
+                },
+                _ => { },
+            }
+        }
+        if let Some(addr) = addr {
+            out.push(AddrInfo {
+                addr,
+                flags,
+                valid_lifetime,
+                created,
+            });
+        }
+    }
+    return Ok(false);
+}
+
+/// List the IPv6 addresses on interface `index` with their flags and lifetimes,
+/// which `network_interface` doesn't expose.
+pub fn ipv6_addrs(index: u32) -> io::Result<Vec<AddrInfo>> {
+    let fd = open_route_socket(0)?;
+
+    // Nlmsghdr + ifaddrmsg, dumping all IPv6 addresses
+    const REQ_SIZE: usize = NLMSG_HEADER_SIZE + IFADDRMSG_SIZE;
+    let mut req = [0u8; REQ_SIZE];
+    req[0 .. 4].copy_from_slice(&(REQ_SIZE as u32).to_ne_bytes());
+    req[4 .. 6].copy_from_slice(&RTM_GETADDR.to_ne_bytes());
+    req[6 .. 8].copy_from_slice(&(NLM_F_REQUEST | NLM_F_DUMP).to_ne_bytes());
+    req[8 .. 12].copy_from_slice(&1u32.to_ne_bytes());
+    req[NLMSG_HEADER_SIZE] = libc::AF_INET6 as u8;
+    let res = unsafe {
+        libc::send(fd.as_raw_fd(), req.as_ptr() as *const libc::c_void, req.len(), 0)
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut out = vec![];
+    let mut buf = [0u8; 16384];
+    loop {
+        let res = unsafe {
+            libc::recv(fd.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0)
+        };
+        if res < 0 {
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() == Some(libc::EINTR) {
+                continue;
+            }
+            return Err(e);
+        }
+        if res == 0 || parse_addr_dump(&buf[.. res as usize], index, &mut out)? {
+            return Ok(out);
+        }
+    }
+}
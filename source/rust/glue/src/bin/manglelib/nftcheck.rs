@@ -0,0 +1,131 @@
+//! A best-effort startup check that the nftables rules queueing to us skip packets
+//! we've already marked, since otherwise `repeat` verdicts loop.
+use {
+    super::events::{
+        event,
+        EventLog,
+        LogLevel,
+    },
+    glue::command::run,
+    std::process::Command,
+};
+
+/// Something wrong with the ruleset, see `check_ruleset`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum NftProblem {
+    /// No rule queues to any of our queues.
+    NoQueueRule,
+    /// The rule queues to one of our queues, but neither it nor an earlier rule in the
+    /// chain matches on the mark.
+    Unmarked {
+        chain: String,
+        rule: String,
+    },
+}
+
+/// The queues a `queue` statement sends to (`queue num 1`, `queue to 1-3`, ...), or
+/// `None` if they can't be worked out (ex: a map).
+fn rule_queues(rule: &str) -> Option<Vec<u16>> {
+    let mut words = rule.split_whitespace().skip_while(|w| *w != "queue").skip(1);
+    while let Some(word) = words.next() {
+        if word != "num" && word != "to" {
+            continue;
+        }
+        let target = words.next()?;
+        return match target.split_once('-') {
+            Some((start, end)) => Some((start.parse().ok()? ..= end.parse().ok()?).collect()),
+            None => Some(vec![target.parse().ok()?]),
+        };
+    }
+
+    // No number, the default
+    return Some(vec![0]);
+}
+
+fn is_queue_rule(rule: &str) -> bool {
+    return rule.split_whitespace().any(|w| w == "queue");
+}
+
+fn matches_mark(rule: &str) -> bool {
+    return rule.split_whitespace().any(|w| w == "mark");
+}
+
+/// Look for rules queueing to `queues` (in `nft list ruleset` output) that don't
+/// check the mark themselves or after an earlier rule in the same chain that
+/// accepts/returns based on the mark. Only a heuristic - it doesn't follow jumps or
+/// check which mark.
+pub fn check_ruleset(ruleset: &str, queues: &[u16]) -> Vec<NftProblem> {
+    let mut out = vec![];
+    let mut found_queue_rule = false;
+    let mut chain = String::new();
+    let mut chain_skips_marked = false;
+    for line in ruleset.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix("chain ") {
+            chain = name.trim_end_matches('{').trim().to_string();
+            chain_skips_marked = false;
+            continue;
+        }
+        if is_queue_rule(line) {
+            // Unknown targets might be ours
+            if !rule_queues(line).is_none_or(|targets| targets.iter().any(|q| queues.contains(q))) {
+                continue;
+            }
+            found_queue_rule = true;
+            if !matches_mark(line) && !chain_skips_marked {
+                out.push(NftProblem::Unmarked {
+                    chain: chain.clone(),
+                    rule: line.to_string(),
+                });
+            }
+            continue;
+        }
+        if matches_mark(line) && (line.ends_with("accept") || line.ends_with("return")) {
+            chain_skips_marked = true;
+        }
+    }
+    if !found_queue_rule {
+        out.push(NftProblem::NoQueueRule);
+    }
+    return out;
+}
+
+/// Check the active ruleset with `nft`, logging problems. Does nothing if `nft`
+/// isn't available.
+pub fn check(log: &EventLog, queues: &[u16], nf_mark: u32) {
+    let ruleset = match run(Command::new("nft").args(["list", "ruleset"])) {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).into_owned(),
+        Err(e) => {
+            // Not available or not permitted, skip the check
+            log.err(LogLevel::Debug, "nft_check_skipped", e);
+            return;
+        },
+    };
+    for problem in check_ruleset(&ruleset, queues) {
+        match problem {
+            NftProblem::NoQueueRule => {
+                event!(
+                    log,
+                    LogLevel::Warn,
+                    "nft_no_queue_rule",
+                    "No nftables rule queues packets to our queues, nothing will be rewritten",
+                    queues = format!("{:?}", queues)
+                );
+            },
+            NftProblem::Unmarked { chain, rule } => {
+                event!(
+                    log,
+                    LogLevel::Warn,
+                    "nft_unmarked_queue_rule",
+                    "nftables rule queues packets without skipping our mark, rewritten packets may loop",
+                    chain = chain,
+                    rule = rule,
+                    nf_mark = nf_mark
+                );
+            },
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! `--print-nftables`, a starting ruleset for feeding packets to the queues.
+use std::fmt::Write;
+
+/// What to generate rules for.
+pub struct NftGenConfig<'a> {
+    /// Sorted, deduplicated.
+    pub queues: &'a [u16],
+    pub nf_mark: u32,
+    /// The bits of the mark that are ours, see `--nf-mark-mask`.
+    pub nf_mark_mask: u32,
+    /// Only queue packets leaving by these, or all if empty.
+    pub interfaces: &'a [String],
+    /// Also queue IPv4 DHCP responses.
+    pub dhcp4: bool,
+}
+
+/// An nftables ruleset that queues outgoing/forwarded RAs and DHCPv6 (and DHCPv4)
+/// responses, skipping packets we've already rewritten and marked. Returns `None`
+/// if the queues can't be expressed as one `queue num` range.
+pub fn ruleset(config: &NftGenConfig) -> Option<String> {
+    let (first, last) = (*config.queues.first()?, *config.queues.last()?);
+    if usize::from(last - first) + 1 != config.queues.len() {
+        return None;
+    }
+    let queue = if first == last {
+        format!("queue num {} bypass", first)
+    } else {
+        format!("queue num {}-{} fanout,bypass", first, last)
+    };
+    let oif = if config.interfaces.is_empty() {
+        "".to_string()
+    } else {
+        format!(
+            "oifname {{ {} }} ",
+            config.interfaces.iter().map(|i| format!("\"{}\"", i)).collect::<Vec<_>>().join(", ")
+        )
+    };
+    let mut out = String::new();
+    writeln!(out, "table inet spaghatteway {{").unwrap();
+    writeln!(out, "\tchain rewrite {{").unwrap();
+    writeln!(out, "\t\t# Already rewritten, don't queue again").unwrap();
+    if config.nf_mark_mask == u32::MAX {
+        writeln!(out, "\t\tmeta mark 0x{:08x} accept", config.nf_mark).unwrap();
+    } else {
+        writeln!(out, "\t\tmeta mark & 0x{:08x} == 0x{:08x} accept", config.nf_mark_mask, config.nf_mark).unwrap();
+    }
+    writeln!(out, "\t\t{}icmpv6 type nd-router-advert {}", oif, queue).unwrap();
+    writeln!(out, "\t\t{}udp sport 547 {}", oif, queue).unwrap();
+    if config.dhcp4 {
+        writeln!(out, "\t\t{}udp sport 67 {}", oif, queue).unwrap();
+    }
+    writeln!(out, "\t}}").unwrap();
+
+    // Locally sent (ex: radvd on this host) and routed
+    for hook in ["output", "forward"] {
+        writeln!(out).unwrap();
+        writeln!(out, "\tchain {} {{", hook).unwrap();
+        writeln!(out, "\t\ttype filter hook {} priority filter; policy accept;", hook).unwrap();
+        writeln!(out, "\t\tjump rewrite").unwrap();
+        writeln!(out, "\t}}").unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+    return Some(out);
+}
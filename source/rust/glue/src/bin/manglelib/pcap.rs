@@ -0,0 +1,154 @@
+use {
+    super::events::{
+        event,
+        EventLog,
+        LogLevel,
+    },
+    loga::{
+        ea,
+        ErrContext,
+        ResultContext,
+    },
+    pcap_file::{
+        pcap::{
+            PcapHeader,
+            PcapPacket,
+            PcapWriter,
+        },
+        DataLink,
+    },
+    std::{
+        fs::File,
+        io::{
+            BufWriter,
+            Write,
+        },
+        path::Path,
+        sync::mpsc::{
+            sync_channel,
+            RecvTimeoutError,
+            SyncSender,
+            TrySendError,
+        },
+        thread::spawn,
+        time::{
+            Duration,
+            Instant,
+            SystemTime,
+            UNIX_EPOCH,
+        },
+    },
+};
+
+/// How many packet pairs can be waiting to be written before new ones are dropped.
+const QUEUE_SIZE: usize = 1024;
+const FLUSH_PERIOD: Duration = Duration::from_secs(1);
+
+// Linux cooked capture (SLL) header - the packet type distinguishes the original
+// ("to another host") and the rewritten ("sent by us") packet in wireshark.
+//
+// * https://www.tcpdump.org/linktypes/LINKTYPE_LINUX_SLL.html
+const SLL_HEADER_SIZE: usize = 16;
+const SLL_PACKET_TYPE_ORIGINAL: u16 = 3;
+const SLL_PACKET_TYPE_MODIFIED: u16 = 4;
+const ARPHRD_NONE: u16 = 0xfffe;
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+
+struct Capture {
+    time: SystemTime,
+    original: Vec<u8>,
+    modified: Vec<u8>,
+}
+
+/// Prefix an IP packet with an SLL header.
+fn sll_frame(packet_type: u16, packet: &[u8]) -> Vec<u8> {
+    let ethertype = match packet.first().map(|b| b >> 4) {
+        Some(4) => ETHERTYPE_IPV4,
+        _ => ETHERTYPE_IPV6,
+    };
+    let mut out = Vec::with_capacity(SLL_HEADER_SIZE + packet.len());
+    out.extend(packet_type.to_be_bytes());
+    out.extend(ARPHRD_NONE.to_be_bytes());
+
+    // No link-layer address
+    out.extend([0; 10]);
+    out.extend(ethertype.to_be_bytes());
+    out.extend_from_slice(packet);
+    return out;
+}
+
+/// Writes original and rewritten packets to a pcap file from a background thread.
+/// Best-effort: if the writer falls behind packets are skipped, and after a write
+/// error nothing more is written.
+pub struct PcapOut {
+    tx: SyncSender<Capture>,
+}
+
+impl PcapOut {
+    /// Create (replacing) the pcap file at `path` and start the writer thread.
+    pub fn open(log: &EventLog, path: &Path) -> Result<PcapOut, loga::Error> {
+        let file = File::create(path).context_with("Error creating pcap file", ea!(path = path.display()))?;
+        let mut writer = PcapWriter::with_header(BufWriter::new(file), PcapHeader {
+            datalink: DataLink::LINUX_SLL,
+            ..Default::default()
+        }).context_with("Error writing pcap header", ea!(path = path.display()))?;
+        let (tx, rx) = sync_channel::<Capture>(QUEUE_SIZE);
+        let log = log.clone();
+        let path = path.display().to_string();
+        spawn(move || {
+            let mut last_flush = Instant::now();
+            let res = (|| -> Result<(), loga::Error> {
+                loop {
+                    match rx.recv_timeout(FLUSH_PERIOD) {
+                        Ok(capture) => {
+                            let timestamp = capture.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+                            for (packet_type, packet) in [
+                                (SLL_PACKET_TYPE_ORIGINAL, &capture.original),
+                                (SLL_PACKET_TYPE_MODIFIED, &capture.modified),
+                            ] {
+                                let frame = sll_frame(packet_type, packet);
+                                writer
+                                    .write_packet(&PcapPacket::new(timestamp, frame.len() as u32, &frame))
+                                    .context("Error writing packet")?;
+                            }
+                        },
+                        Err(RecvTimeoutError::Timeout) => { },
+                        Err(RecvTimeoutError::Disconnected) => {
+                            writer.get_mut().flush().context("Error flushing")?;
+                            return Ok(());
+                        },
+                    }
+                    if last_flush.elapsed() >= FLUSH_PERIOD {
+                        writer.get_mut().flush().context("Error flushing")?;
+                        last_flush = Instant::now();
+                    }
+                }
+            })();
+            if let Err(e) = res {
+                log.err(
+                    LogLevel::Warn,
+                    "pcap_failed",
+                    e.context_with("Error writing pcap file, no more packets will be captured", ea!(path = path)),
+                );
+            }
+        });
+        return Ok(PcapOut { tx });
+    }
+
+    /// Queue a packet and its rewritten version to be written. Never blocks.
+    pub fn capture(&self, log: &EventLog, original: &[u8], modified: &[u8]) {
+        match self.tx.try_send(Capture {
+            time: SystemTime::now(),
+            original: original.to_vec(),
+            modified: modified.to_vec(),
+        }) {
+            Ok(()) => { },
+            Err(TrySendError::Full(_)) => {
+                event!(log, LogLevel::Debug, "pcap_skipped", "Pcap writer is behind, not capturing packet");
+            },
+            // Writer stopped after an error, already logged
+            Err(TrySendError::Disconnected(_)) => { },
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! `--ra-source`, which routers' RAs to rewrite.
+use {
+    super::select::Ipv6Prefix,
+    std::{
+        net::Ipv6Addr,
+        str::FromStr,
+    },
+};
+
+/// Sources (`--ra-source` entries) whose RAs are or aren't rewritten.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RaSourceFilter {
+    pub allow: Vec<Ipv6Prefix>,
+    pub deny: Vec<Ipv6Prefix>,
+}
+
+impl RaSourceFilter {
+    /// Add an `--ra-source` entry, `allow=prefix` or `deny=prefix`. A plain address
+    /// (without `/len`) is the same as `/128`.
+    pub fn add(&mut self, entry: &str) -> Result<(), String> {
+        let Some((action, prefix)) = entry.split_once('=') else {
+            return Err(format!("Missing allow= or deny= in RA source [{}]", entry));
+        };
+        let prefix = if prefix.contains('/') {
+            Ipv6Prefix::from_str(prefix)?
+        } else {
+            Ipv6Prefix {
+                addr: Ipv6Addr::from_str(
+                    prefix,
+                ).map_err(|e| format!("Invalid address in RA source [{}]: {}", entry, e))?,
+                len: 128,
+            }
+        };
+        match action {
+            "allow" => self.allow.push(prefix),
+            "deny" => self.deny.push(prefix),
+            _ => return Err(format!("Unknown action [{}] in RA source [{}], expected allow or deny", action, entry)),
+        }
+        return Ok(());
+    }
+
+    /// Whether to rewrite RAs from `source`: it must not match any `deny` entry and, if
+    /// there are `allow` entries, must match one of them.
+    pub fn allows(&self, source: &Ipv6Addr) -> bool {
+        if self.deny.iter().any(|p| p.contains(source)) {
+            return false;
+        }
+        return self.allow.is_empty() || self.allow.iter().any(|p| p.contains(source));
+    }
+}
@@ -0,0 +1,57 @@
+use std::{
+    collections::HashMap,
+    net::Ipv6Addr,
+    time::Instant,
+};
+
+/// Stop tracking sources once there are this many, if they've gone quiet.
+const MAX_TRACKED: usize = 1024;
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+/// A token bucket per source address, allowing bursts of up to a second's worth.
+pub struct RateLimiter {
+    per_sec: f64,
+    buckets: HashMap<Ipv6Addr, Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(per_sec: u32) -> RateLimiter {
+        return RateLimiter {
+            per_sec: per_sec as f64,
+            buckets: HashMap::new(),
+        };
+    }
+
+    fn refill(per_sec: f64, bucket: &mut Bucket, now: Instant) {
+        let elapsed = now.saturating_duration_since(bucket.last).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_sec).min(per_sec);
+        bucket.last = now;
+    }
+
+    /// Take a token for a packet from `source`. Returns `false` if it's over the
+    /// limit.
+    pub fn allow(&mut self, source: Ipv6Addr, now: Instant) -> bool {
+        if !self.buckets.contains_key(&source) && self.buckets.len() >= MAX_TRACKED {
+            // Full buckets are the same as untracked
+            let per_sec = self.per_sec;
+            self.buckets.retain(|_, b| {
+                RateLimiter::refill(per_sec, b, now);
+                return b.tokens < per_sec;
+            });
+        }
+        let bucket = self.buckets.entry(source).or_insert(Bucket {
+            tokens: self.per_sec,
+            last: now,
+        });
+        RateLimiter::refill(self.per_sec, bucket, now);
+        if bucket.tokens < 1. {
+            return false;
+        }
+        bucket.tokens -= 1.;
+        return true;
+    }
+}
@@ -0,0 +1,260 @@
+use {
+    super::{
+        events::{
+            event,
+            EventLog,
+            LogLevel,
+        },
+        PacketQueue,
+        QueuedPacket,
+    },
+    glue::ra_dns::fragment::{
+        is_fragment,
+        Datagram,
+        Reassembler,
+        Reassembly,
+    },
+    nfq::Verdict,
+    std::{
+        io,
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+};
+
+/// Limits on fragments held waiting for the rest of their datagram, so a hostile
+/// sender can't use up memory or the kernel queue.
+const MAX_DATAGRAMS: usize = 16;
+const MAX_FRAGMENTS: usize = 16;
+
+/// * https://datatracker.ietf.org/doc/html/rfc8200#section-4.5
+const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A datagram reassembled from several queued fragments.
+pub struct Reassembled<M> {
+    datagram: Datagram,
+    messages: Vec<M>,
+    outdev: u32,
+    /// Of the first fragment received.
+    mark: u32,
+    new_payload: Option<Vec<u8>>,
+    nf_mark: Option<u32>,
+    verdict: Verdict,
+}
+
+/// A message from `ReassemblingQueue`.
+pub enum QueuedDatagram<M> {
+    /// An unfragmented packet, as queued.
+    Single(M),
+    Reassembled(Reassembled<M>),
+}
+
+impl<M: QueuedPacket> QueuedPacket for QueuedDatagram<M> {
+    fn get_payload(&self) -> &[u8] {
+        match self {
+            QueuedDatagram::Single(m) => return m.get_payload(),
+            QueuedDatagram::Reassembled(r) => return &r.datagram.packet,
+        }
+    }
+
+    fn get_outdev(&self) -> u32 {
+        match self {
+            QueuedDatagram::Single(m) => return m.get_outdev(),
+            QueuedDatagram::Reassembled(r) => return r.outdev,
+        }
+    }
+
+    fn get_nfmark(&self) -> u32 {
+        match self {
+            QueuedDatagram::Single(m) => return m.get_nfmark(),
+            QueuedDatagram::Reassembled(r) => return r.mark,
+        }
+    }
+
+    fn set_payload(&mut self, payload: &[u8]) {
+        match self {
+            QueuedDatagram::Single(m) => m.set_payload(payload),
+            QueuedDatagram::Reassembled(r) => r.new_payload = Some(payload.to_vec()),
+        }
+    }
+
+    fn set_nfmark(&mut self, mark: u32) {
+        match self {
+            QueuedDatagram::Single(m) => m.set_nfmark(mark),
+            QueuedDatagram::Reassembled(r) => r.nf_mark = Some(mark),
+        }
+    }
+
+    fn set_verdict(&mut self, verdict: Verdict) {
+        match self {
+            QueuedDatagram::Single(m) => m.set_verdict(verdict),
+            QueuedDatagram::Reassembled(r) => r.verdict = verdict,
+        }
+    }
+
+    fn is_reassembled(&self) -> bool {
+        return matches!(self, QueuedDatagram::Reassembled(_));
+    }
+}
+
+/// Wraps a queue, holding IPv6 fragments until their whole datagram has arrived so
+/// it can be rewritten as one packet. Rewritten datagrams are split into fragments
+/// again, no larger than the largest original fragment, and sent in place of the
+/// held fragments.
+///
+/// Fragments of a datagram need to arrive on the same queue, which they do with
+/// `fanout` (hashed by address).
+pub struct ReassemblingQueue<'a, Q: PacketQueue> {
+    log: &'a EventLog,
+    inner: &'a mut Q,
+    reassembler: Reassembler<Q::Message>,
+    /// For fragments that can't be reassembled or rewritten.
+    fail_verdict: Verdict,
+}
+
+impl<'a, Q: PacketQueue> ReassemblingQueue<'a, Q> {
+    pub fn new(log: &'a EventLog, inner: &'a mut Q, fail_verdict: Verdict) -> ReassemblingQueue<'a, Q> {
+        return ReassemblingQueue {
+            log,
+            inner,
+            reassembler: Reassembler::new(MAX_DATAGRAMS, MAX_FRAGMENTS, REASSEMBLY_TIMEOUT),
+            fail_verdict,
+        };
+    }
+
+    fn fail(&mut self, messages: Vec<Q::Message>) -> io::Result<()> {
+        for mut message in messages {
+            message.set_verdict(self.fail_verdict);
+            self.inner.verdict(message)?;
+        }
+        return Ok(());
+    }
+
+    fn expire(&mut self) -> io::Result<()> {
+        let expired = self.reassembler.expire(Instant::now());
+        if !expired.is_empty() {
+            event!(
+                self.log,
+                LogLevel::Debug,
+                "reassembly_expired",
+                "Discarding fragments of an incomplete datagram",
+                fragments = expired.len()
+            );
+        }
+        return self.fail(expired);
+    }
+}
+
+impl<'a, Q: PacketQueue> PacketQueue for ReassemblingQueue<'a, Q> {
+    type Message = QueuedDatagram<Q::Message>;
+
+    fn try_recv(&mut self) -> io::Result<Option<QueuedDatagram<Q::Message>>> {
+        loop {
+            let Some(message) = self.inner.try_recv()? else {
+                self.expire()?;
+                return Ok(None);
+            };
+            if !is_fragment(message.get_payload()) {
+                return Ok(Some(QueuedDatagram::Single(message)));
+            }
+            self.expire()?;
+            let packet = message.get_payload().to_vec();
+            match self.reassembler.add(Instant::now(), &packet, message) {
+                Ok(Reassembly::Incomplete) => { },
+                Ok(Reassembly::Complete { datagram, items: messages }) => {
+                    let outdev = messages[0].get_outdev();
+                    let mark = messages[0].get_nfmark();
+                    return Ok(Some(QueuedDatagram::Reassembled(Reassembled {
+                        datagram,
+                        messages,
+                        outdev,
+                        mark,
+                        new_payload: None,
+                        nf_mark: None,
+                        verdict: Verdict::Accept,
+                    })));
+                },
+                Ok(Reassembly::Failed { error, items: messages }) => {
+                    event!(
+                        self.log,
+                        LogLevel::Debug,
+                        "reassembly_failed",
+                        "Couldn't reassemble fragmented datagram",
+                        error = error
+                    );
+                    self.fail(messages)?;
+                },
+                // Checked above
+                Err((_, message)) => {
+                    return Ok(Some(QueuedDatagram::Single(message)));
+                },
+            }
+        }
+    }
+
+    fn wait(&mut self, timeout: Duration) {
+        self.inner.wait(timeout);
+    }
+
+    fn verdict(&mut self, message: QueuedDatagram<Q::Message>) -> io::Result<()> {
+        let r = match message {
+            QueuedDatagram::Single(m) => return self.inner.verdict(m),
+            QueuedDatagram::Reassembled(r) => r,
+        };
+        let Some(new_payload) = r.new_payload else {
+            for mut message in r.messages {
+                message.set_verdict(r.verdict);
+                self.inner.verdict(message)?;
+            }
+            return Ok(());
+        };
+        let mut datagram = r.datagram;
+        datagram.packet = new_payload;
+
+        // Only the held messages can be sent, no new packets
+        let fragments = match datagram.fragment() {
+            Ok(f) if f.len() <= r.messages.len() => f,
+            Ok(f) => {
+                event!(
+                    self.log,
+                    LogLevel::Warn,
+                    "refragment_failed",
+                    "Rewritten datagram needs more fragments than were received, not rewriting",
+                    received = r.messages.len(),
+                    needed = f.len()
+                );
+                return self.fail(r.messages);
+            },
+            Err(e) => {
+                event!(
+                    self.log,
+                    LogLevel::Warn,
+                    "refragment_failed",
+                    "Couldn't fragment rewritten datagram",
+                    error = e
+                );
+                return self.fail(r.messages);
+            },
+        };
+        let mut fragments = fragments.into_iter();
+        for mut message in r.messages {
+            match fragments.next() {
+                Some(fragment) => {
+                    message.set_payload(&fragment);
+                    if let Some(nf_mark) = r.nf_mark {
+                        message.set_nfmark(nf_mark);
+                    }
+                    message.set_verdict(r.verdict);
+                },
+                // Fewer fragments than before
+                None => {
+                    message.set_verdict(Verdict::Drop);
+                },
+            }
+            self.inner.verdict(message)?;
+        }
+        return Ok(());
+    }
+}
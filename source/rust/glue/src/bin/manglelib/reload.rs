@@ -0,0 +1,43 @@
+//! Settings that can be replaced while running (SIGHUP).
+use std::sync::{
+    atomic::{
+        AtomicU64,
+        Ordering,
+    },
+    Arc,
+    Mutex,
+};
+
+/// A value that can be replaced while threads are using it. Readers on hot paths
+/// keep their own copy and check `generation` to see if it's stale, rather than
+/// locking each time.
+pub struct Reloadable<T> {
+    generation: AtomicU64,
+    value: Mutex<Arc<T>>,
+}
+
+impl<T> Reloadable<T> {
+    pub fn new(value: T) -> Reloadable<T> {
+        return Reloadable {
+            generation: AtomicU64::new(0),
+            value: Mutex::new(Arc::new(value)),
+        };
+    }
+
+    /// Changes each time the value is replaced. Read this before `get` - if they race
+    /// the copy is only refreshed again unnecessarily.
+    pub fn generation(&self) -> u64 {
+        return self.generation.load(Ordering::Acquire);
+    }
+
+    pub fn get(&self) -> Arc<T> {
+        return self.value.lock().unwrap().clone();
+    }
+
+    /// Replace the value, returning the old one.
+    pub fn set(&self, value: T) -> Arc<T> {
+        let old = std::mem::replace(&mut *self.value.lock().unwrap(), Arc::new(value));
+        self.generation.fetch_add(1, Ordering::Release);
+        return old;
+    }
+}
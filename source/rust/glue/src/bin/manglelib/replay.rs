@@ -0,0 +1,116 @@
+//! `--replay-in`/`--replay-out`, rewriting captured traffic offline.
+use {
+    glue::ra_dns::{
+        modify,
+        ModifyConfig,
+        ModifyError,
+    },
+    loga::{
+        ea,
+        ResultContext,
+    },
+    pcap_file::{
+        pcap::{
+            PcapPacket,
+            PcapReader,
+            PcapWriter,
+        },
+        DataLink,
+    },
+    std::io::{
+        Read,
+        Write,
+    },
+};
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+
+/// What happened to the packets in a replay.
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct ReplayCounts {
+    pub modified: usize,
+    /// Not IP, not a packet `modify` rewrites, or nothing to change.
+    pub unchanged: usize,
+    /// `modify` failed (ex: truncated), written unchanged.
+    pub failed: usize,
+}
+
+fn read_u16(frame: &[u8], at: usize) -> Option<u16> {
+    return Some(u16::from_be_bytes(frame.get(at .. at + 2)?.try_into().unwrap()));
+}
+
+/// The size of the link-layer header before the IP packet, or `None` if the frame
+/// isn't IP.
+///
+/// * https://www.tcpdump.org/linktypes.html
+fn link_header_size(datalink: DataLink, frame: &[u8]) -> Option<usize> {
+    let (ethertype, size) = match datalink {
+        DataLink::RAW | DataLink::IPV4 | DataLink::IPV6 => return Some(0),
+        DataLink::ETHERNET => {
+            match read_u16(frame, 12)? {
+                // One VLAN tag
+                ETHERTYPE_VLAN => (read_u16(frame, 16)?, 18),
+                ethertype => (ethertype, 14),
+            }
+        },
+        DataLink::LINUX_SLL => (read_u16(frame, 14)?, 16),
+        DataLink::LINUX_SLL2 => (read_u16(frame, 0)?, 20),
+        _ => return None,
+    };
+    if ethertype != ETHERTYPE_IPV4 && ethertype != ETHERTYPE_IPV6 {
+        return None;
+    }
+    return Some(size);
+}
+
+/// Rewrite every packet in the pcap `input` with `modify`, writing all packets
+/// (rewritten or not) to `output` with the same link-layer headers.
+pub fn replay(input: impl Read, output: impl Write, config: &ModifyConfig) -> Result<ReplayCounts, loga::Error> {
+    let mut reader = PcapReader::new(input).context("Error reading pcap header")?;
+    let header = reader.header();
+    match header.datalink {
+        DataLink::RAW |
+        DataLink::IPV4 |
+        DataLink::IPV6 |
+        DataLink::ETHERNET |
+        DataLink::LINUX_SLL |
+        DataLink::LINUX_SLL2 => { },
+        datalink => {
+            return Err(loga::err_with("Unsupported pcap link type", ea!(datalink = format!("{:?}", datalink))));
+        },
+    }
+    let mut writer = PcapWriter::with_header(output, header).context("Error writing pcap header")?;
+    let mut counts = ReplayCounts::default();
+    let mut frame = vec![];
+    let mut index = 0usize;
+    while let Some(packet) = reader.next_packet() {
+        let packet = packet.context_with("Error reading packet", ea!(index = index))?;
+        index += 1;
+        frame.clear();
+        frame.extend_from_slice(&packet.data);
+        let rewritten = link_header_size(header.datalink, &frame).map(|size| {
+            return (size, modify(&frame[size..], config));
+        });
+        match rewritten {
+            Some((size, Ok(ip_packet))) if ip_packet != frame[size..] => {
+                frame.truncate(size);
+                frame.extend(ip_packet);
+                counts.modified += 1;
+            },
+            None | Some((_, Ok(_))) | Some((_, Err(ModifyError::NotApplicable))) => {
+                counts.unchanged += 1;
+            },
+            Some((_, Err(_))) => {
+                counts.failed += 1;
+            },
+        }
+        let orig_len = (packet.orig_len as usize + frame.len()).saturating_sub(packet.data.len());
+        writer
+            .write_packet(&PcapPacket::new(packet.timestamp, orig_len as u32, &frame))
+            .context("Error writing packet")?;
+    }
+    writer.get_mut().flush().context("Error flushing output")?;
+    return Ok(counts);
+}
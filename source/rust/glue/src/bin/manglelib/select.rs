@@ -0,0 +1,186 @@
+use {
+    super::netlink::{
+        AddrInfo,
+        IFA_F_DADFAILED,
+        IFA_F_DEPRECATED,
+        IFA_F_TEMPORARY,
+        IFA_F_TENTATIVE,
+    },
+    aargvark::Aargvark,
+    glue::unstable_ip::UnstableIpv6,
+    serde::Deserialize,
+    std::{
+        cmp::Ordering,
+        net::Ipv6Addr,
+        str::FromStr,
+    },
+};
+
+/// An IPv6 subnet, written `addr/len`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ipv6Prefix {
+    pub addr: Ipv6Addr,
+    pub len: u8,
+}
+
+impl Ipv6Prefix {
+    fn mask(&self) -> u128 {
+        return u128::MAX.checked_shl(128 - self.len as u32).unwrap_or(0);
+    }
+
+    pub fn contains(&self, ip: &Ipv6Addr) -> bool {
+        let mask = self.mask();
+        return u128::from(*ip) & mask == u128::from(self.addr) & mask;
+    }
+}
+
+impl FromStr for Ipv6Prefix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((addr, len)) = s.split_once('/') else {
+            return Err(format!("Missing /length in prefix [{}]", s));
+        };
+        let addr = Ipv6Addr::from_str(addr).map_err(|e| format!("Invalid address in prefix [{}]: {}", s, e))?;
+        let len = u8::from_str(len).map_err(|e| format!("Invalid length in prefix [{}]: {}", s, e))?;
+        if len > 128 {
+            return Err(format!("Prefix length in [{}] is longer than 128", s));
+        }
+        return Ok(Ipv6Prefix {
+            addr,
+            len,
+        });
+    }
+}
+
+/// Parse an `--interface-dns` entry, `interface=addr`.
+pub fn parse_interface_dns(s: &str) -> Result<(String, Ipv6Addr), String> {
+    let Some((name, addr)) = s.split_once('=') else {
+        return Err(format!("Missing =address in interface DNS [{}]", s));
+    };
+    if name.is_empty() {
+        return Err(format!("Missing interface name in interface DNS [{}]", s));
+    }
+    let addr = Ipv6Addr::from_str(addr).map_err(|e| format!("Invalid address in interface DNS [{}]: {}", s, e))?;
+    return Ok((name.to_string(), addr));
+}
+
+/// Parse a `--dns-ip`, an address optionally followed by `%zone` (ex: `fe80::1%eth0`).
+/// Only link-local addresses can have a zone.
+pub fn parse_dns_ip(s: &str) -> Result<(Ipv6Addr, Option<String>), String> {
+    let (addr, zone) = match s.split_once('%') {
+        Some((addr, zone)) => (addr, Some(zone)),
+        None => (s, None),
+    };
+    let addr = Ipv6Addr::from_str(addr).map_err(|e| format!("Invalid DNS address [{}]: {}", s, e))?;
+    let Some(zone) = zone else {
+        return Ok((addr, None));
+    };
+    if zone.is_empty() {
+        return Err(format!("Empty zone in DNS address [{}]", s));
+    }
+    if !addr.unstable_is_unicast_link_local() {
+        return Err(format!("DNS address [{}] has a zone but isn't link-local", s));
+    }
+    return Ok((addr, Some(zone.to_string())));
+}
+
+/// Whether an interface address with `IFA_F_*` `flags` can be advertised at all.
+/// Global addresses can, unique-local (`fc00::/7`) ones only if `allow_ula`.
+/// Link-local addresses never can, nor can addresses that are still in or failed
+/// duplicate address detection, since traffic to them may fail. Temporary (privacy)
+/// addresses rotate every few hours so they only can if `allow_temporary`. Deprecated
+/// addresses can, but only if there's nothing better (see `preference`).
+pub fn is_candidate(ip: &Ipv6Addr, flags: u32, allow_ula: bool, allow_temporary: bool) -> bool {
+    if flags & (IFA_F_TENTATIVE | IFA_F_DADFAILED) != 0 {
+        return false;
+    }
+    if !allow_temporary && flags & IFA_F_TEMPORARY != 0 {
+        return false;
+    }
+    if ip.unstable_is_unicast_link_local() {
+        return false;
+    }
+    if ip.unstable_is_global() {
+        return true;
+    }
+    return allow_ula && ip.unstable_is_unique_local();
+}
+
+/// How to choose between several advertisable addresses on an interface.
+#[derive(Aargvark, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AddressSelect {
+    /// The numerically lowest
+    Lowest,
+    /// The numerically highest
+    Highest,
+    /// The one added to the interface first
+    Oldest,
+    /// The one with the longest valid lifetime (ex: the new address after a prefix
+    /// change, not the old one that's about to disappear)
+    LongestLifetime,
+}
+
+/// With `longest-lifetime`, how many times longer another address's lifetime must be
+/// to switch to it from the current one.
+const LIFETIME_SWITCH_FACTOR: u32 = 2;
+
+/// A scoped-down RFC 6724 source address preference, greater is preferred. First
+/// addresses that aren't deprecated (rule 3), then ones that aren't temporary privacy
+/// addresses (rule 7, reversed since temporary addresses rotate), then ones in
+/// `ra_prefix`, the prefix in the RAs clients get (rule 8).
+///
+/// * https://datatracker.ietf.org/doc/html/rfc6724#section-5
+pub fn preference(addr: &AddrInfo, ra_prefix: Option<&Ipv6Prefix>) -> (bool, bool, bool) {
+    return (
+        addr.flags & IFA_F_DEPRECATED == 0,
+        addr.flags & IFA_F_TEMPORARY == 0,
+        ra_prefix.is_some_and(|p| p.contains(&addr.addr)),
+    );
+}
+
+/// Choose the address to advertise from an interface's global addresses. Only
+/// addresses in `prefix` are considered. They're ranked by `preference` (with
+/// `ra_prefix`), then `policy`, and ties are broken by the lowest address so the
+/// choice doesn't depend on enumeration order.
+///
+/// If `current` (the address being advertised) is still a candidate it's kept unless
+/// another is strictly preferred ignoring the tie break, so the choice doesn't flap
+/// between equally good addresses. Lifetimes count down between RA refreshes, so
+/// which of two similar lifetimes is longer can alternate; with `longest-lifetime`
+/// another address is only preferred over `current` if its lifetime is
+/// `LIFETIME_SWITCH_FACTOR` times longer.
+pub fn select_ip(
+    candidates: impl IntoIterator<Item = AddrInfo>,
+    prefix: Option<&Ipv6Prefix>,
+    ra_prefix: Option<&Ipv6Prefix>,
+    policy: AddressSelect,
+    current: Option<Ipv6Addr>,
+) -> Option<Ipv6Addr> {
+    // Greater is preferred. With `from_current`, `b` is the current address.
+    let compare_rank = |a: &AddrInfo, b: &AddrInfo, from_current: bool| -> Ordering {
+        let policy_order = match policy {
+            AddressSelect::Lowest => b.addr.cmp(&a.addr),
+            AddressSelect::Highest => a.addr.cmp(&b.addr),
+            AddressSelect::Oldest => b.created.cmp(&a.created),
+            AddressSelect::LongestLifetime if from_current => {
+                a.valid_lifetime.cmp(&b.valid_lifetime.saturating_mul(LIFETIME_SWITCH_FACTOR))
+            },
+            AddressSelect::LongestLifetime => a.valid_lifetime.cmp(&b.valid_lifetime),
+        };
+        return preference(a, ra_prefix).cmp(&preference(b, ra_prefix)).then(policy_order);
+    };
+    let compare = |a: &AddrInfo, b: &AddrInfo| -> Ordering {
+        return compare_rank(a, b, false).then(b.addr.cmp(&a.addr));
+    };
+    let candidates =
+        candidates.into_iter().filter(|a| prefix.map(|p| p.contains(&a.addr)).unwrap_or(true)).collect::<Vec<_>>();
+    let best = candidates.iter().max_by(|a, b| compare(a, b))?;
+    if let Some(current) = candidates.iter().find(|a| Some(a.addr) == current) {
+        if compare_rank(best, current, true) != Ordering::Greater {
+            return Some(current.addr);
+        }
+    }
+    return Some(best.addr);
+}
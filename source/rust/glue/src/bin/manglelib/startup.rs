@@ -0,0 +1,128 @@
+//! `--startup-timeout`, what to do if no interface gets a global IP soon after
+//! starting.
+use {
+    super::{
+        events::{
+            event,
+            EventLog,
+            LogLevel,
+        },
+        health::Health,
+    },
+    aargvark::Aargvark,
+    serde::Deserialize,
+    std::{
+        sync::{
+            atomic::{
+                AtomicBool,
+                Ordering,
+            },
+            Arc,
+        },
+        thread::{
+            sleep,
+            spawn,
+            JoinHandle,
+        },
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+};
+
+#[derive(Aargvark, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum StartupTimeoutAction {
+    /// Stop and exit with an error (ex: for a supervisor to alert on)
+    Exit,
+    /// Pass packets through unmodified (with the router's DNS) until an interface has a
+    /// global IP
+    Passthrough,
+    /// Keep handling packets per `--pre-ip` (dropping or passing them through), only
+    /// warning
+    Continue,
+}
+
+/// Waits for any watched interface to get a global IP (`health.have_ip`), acting per
+/// `action` if none does within `timeout`.
+#[derive(Clone)]
+pub struct StartupWatch {
+    pub log: EventLog,
+    pub stop: Arc<AtomicBool>,
+    pub health: Arc<Health>,
+    pub timeout: Duration,
+    pub action: StartupTimeoutAction,
+    /// Set if it timed out and `action` is `Exit`.
+    pub timed_out: Arc<AtomicBool>,
+    /// Set while packets should be passed through, with `Passthrough`.
+    pub passthrough: Arc<AtomicBool>,
+    /// How often to check for an IP.
+    pub check_period: Duration,
+}
+
+impl StartupWatch {
+    /// Wait for an IP until `stop` or `until`, returning whether one was found.
+    fn wait_for_ip(&self, until: Option<Instant>) -> bool {
+        loop {
+            if self.health.have_ip.load(Ordering::Relaxed) {
+                return true;
+            }
+            if self.stop.load(Ordering::Relaxed) || until.is_some_and(|until| Instant::now() >= until) {
+                return false;
+            }
+            sleep(self.check_period);
+        }
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        return spawn(move || {
+            let log = &self.log;
+            if self.wait_for_ip(Some(Instant::now() + self.timeout)) || self.stop.load(Ordering::Relaxed) {
+                return;
+            }
+            match self.action {
+                StartupTimeoutAction::Exit => {
+                    if !self.stop.swap(true, Ordering::Relaxed) {
+                        event!(
+                            log,
+                            LogLevel::Error,
+                            "startup_timeout",
+                            "No interface got a global IP within the startup timeout, stopping",
+                            timeout = self.timeout.as_secs()
+                        );
+                        self.timed_out.store(true, Ordering::Relaxed);
+                    }
+                },
+                StartupTimeoutAction::Passthrough => {
+                    event!(
+                        log,
+                        LogLevel::Warn,
+                        "startup_timeout",
+                        "No interface got a global IP within the startup timeout, passing packets through unmodified",
+                        timeout = self.timeout.as_secs()
+                    );
+                    self.passthrough.store(true, Ordering::Relaxed);
+                    if self.wait_for_ip(None) {
+                        event!(
+                            log,
+                            LogLevel::Info,
+                            "startup_passthrough_ended",
+                            "Found a global IP, switching from passing packets through to rewriting them"
+                        );
+                    }
+                    self.passthrough.store(false, Ordering::Relaxed);
+                },
+                StartupTimeoutAction::Continue => {
+                    event!(
+                        log,
+                        LogLevel::Warn,
+                        "startup_timeout",
+                        "No interface got a global IP within the startup timeout, still not rewriting packets",
+                        timeout = self.timeout.as_secs()
+                    );
+                },
+            }
+        });
+    }
+}
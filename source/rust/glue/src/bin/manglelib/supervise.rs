@@ -0,0 +1,115 @@
+//! `--watcher-restart`, what to do when a background thread stops unexpectedly.
+use {
+    super::events::{
+        event,
+        EventLog,
+        LogLevel,
+    },
+    aargvark::Aargvark,
+    serde::Deserialize,
+    std::{
+        any::Any,
+        panic::{
+            catch_unwind,
+            AssertUnwindSafe,
+        },
+        sync::{
+            atomic::{
+                AtomicBool,
+                Ordering,
+            },
+            Arc,
+        },
+        thread::{
+            sleep,
+            spawn,
+            JoinHandle,
+        },
+        time::Duration,
+    },
+};
+
+#[derive(Aargvark, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum WatcherRestart {
+    /// Start it again
+    Respawn,
+    /// Stop everything and exit with an error (ex: for systemd to restart)
+    Exit,
+}
+
+/// Runs threads that should live until `stop`, acting per `policy` if one returns
+/// or panics before then.
+#[derive(Clone)]
+pub struct Supervisor {
+    pub log: EventLog,
+    pub stop: Arc<AtomicBool>,
+    /// Set if a thread stopped early and `policy` is `Exit`.
+    pub died: Arc<AtomicBool>,
+    pub policy: WatcherRestart,
+    /// Wait before respawning, so a thread that fails immediately doesn't spin.
+    pub respawn_delay: Duration,
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        return message.to_string();
+    }
+    if let Some(message) = payload.downcast_ref::<String>() {
+        return message.clone();
+    }
+    return "(unknown panic)".to_string();
+}
+
+impl Supervisor {
+    /// Run `f` in a thread until `stop`, rerunning it if it stops early and the policy
+    /// is `Respawn`. `label` identifies it in events.
+    pub fn spawn(&self, label: String, mut f: impl FnMut() + Send + 'static) -> JoinHandle<()> {
+        let supervisor = self.clone();
+        return spawn(move || {
+            let log = &supervisor.log;
+            loop {
+                let res = catch_unwind(AssertUnwindSafe(&mut f));
+                if supervisor.stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                let error = match res {
+                    Ok(()) => "returned".to_string(),
+                    Err(payload) => format!("panicked: {}", panic_message(payload.as_ref())),
+                };
+                match supervisor.policy {
+                    WatcherRestart::Respawn => {
+                        event!(
+                            log,
+                            LogLevel::Warn,
+                            "watcher_died",
+                            "Interface watcher stopped unexpectedly, restarting",
+                            interface = label,
+                            error = error
+                        );
+                        sleep(supervisor.respawn_delay);
+                        if supervisor.stop.load(Ordering::Relaxed) {
+                            return;
+                        }
+                    },
+                    WatcherRestart::Exit => {
+                        // Otherwise the interface's IP would go stale while packets kept being
+                        // rewritten with it
+                        if !supervisor.stop.swap(true, Ordering::Relaxed) {
+                            event!(
+                                log,
+                                LogLevel::Error,
+                                "watcher_died",
+                                "Interface watcher stopped unexpectedly, stopping",
+                                interface = label,
+                                error = error
+                            );
+                            supervisor.died.store(true, Ordering::Relaxed);
+                        }
+                        return;
+                    },
+                }
+            }
+        });
+    }
+}
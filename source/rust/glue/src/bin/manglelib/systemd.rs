@@ -0,0 +1,79 @@
+use {
+    super::events::{
+        EventLog,
+        LogLevel,
+    },
+    loga::ErrContext,
+    sd_notify::NotifyState,
+    std::sync::Mutex,
+};
+
+struct State {
+    queues_unbound: usize,
+    waiting_for_ip: bool,
+    ready_sent: bool,
+    stopping_sent: bool,
+}
+
+/// Tells systemd (`Type=notify`) when the daemon is processing packets. All
+/// notifications are no-ops if `NOTIFY_SOCKET` isn't set.
+pub struct Notifier {
+    log: EventLog,
+    state: Mutex<State>,
+}
+
+impl Notifier {
+    /// Ready once `queues` queues are bound and, if `waiting_for_ip`, the first
+    /// global IP is found.
+    pub fn new(log: &EventLog, queues: usize, waiting_for_ip: bool) -> Notifier {
+        return Notifier {
+            log: log.clone(),
+            state: Mutex::new(State {
+                queues_unbound: queues,
+                waiting_for_ip,
+                ready_sent: false,
+                stopping_sent: false,
+            }),
+        };
+    }
+
+    fn send(&self, state: &[NotifyState]) {
+        if let Err(e) = sd_notify::notify(false, state) {
+            self.log.err(LogLevel::Warn, "error", e.context("Error sending systemd notification"));
+        }
+    }
+
+    fn update_ready(&self, f: impl FnOnce(&mut State)) {
+        let mut state = self.state.lock().unwrap();
+        f(&mut state);
+        if state.ready_sent || state.queues_unbound > 0 || state.waiting_for_ip {
+            return;
+        }
+        state.ready_sent = true;
+        self.send(&[NotifyState::Ready]);
+    }
+
+    pub fn queue_bound(&self) {
+        self.update_ready(|r| r.queues_unbound = r.queues_unbound.saturating_sub(1));
+    }
+
+    pub fn ip_found(&self) {
+        self.update_ready(|r| r.waiting_for_ip = false);
+    }
+
+    pub fn status(&self, status: &str) {
+        self.send(&[NotifyState::Status(status)]);
+    }
+
+    /// Only sends once, so can be called from each worker.
+    pub fn stopping(&self) {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.stopping_sent {
+                return;
+            }
+            state.stopping_sent = true;
+        }
+        self.send(&[NotifyState::Stopping]);
+    }
+}
@@ -0,0 +1,78 @@
+use {
+    crate::manglelib::control::{
+        handle_line,
+        ControlHandler,
+    },
+    std::{
+        net::Ipv6Addr,
+        sync::Mutex,
+    },
+};
+
+#[derive(Default)]
+struct MockHandler {
+    rechecks: Mutex<usize>,
+    set_dns: Mutex<Vec<(Ipv6Addr, Option<String>)>>,
+}
+
+impl ControlHandler for MockHandler {
+    fn status(&self) -> serde_json::Value {
+        return serde_json::json!({
+            "interfaces": {
+                "br0": {
+                    "ip": null,
+                },
+            },
+        });
+    }
+
+    fn recheck(&self) {
+        *self.rechecks.lock().unwrap() += 1;
+    }
+
+    fn set_dns(&self, ip: Ipv6Addr, interface: Option<&str>) -> Result<(), String> {
+        if interface == Some("eth9") {
+            return Err("Not a watched interface [eth9]".to_string());
+        }
+        self.set_dns.lock().unwrap().push((ip, interface.map(|i| i.to_string())));
+        return Ok(());
+    }
+}
+
+#[test]
+fn test_control_status() {
+    let handler = MockHandler::default();
+    assert_eq!(handle_line(&handler, "status"), r#"{"interfaces":{"br0":{"ip":null}}}"#);
+    assert_eq!(handle_line(&handler, "  status\r"), r#"{"interfaces":{"br0":{"ip":null}}}"#);
+}
+
+#[test]
+fn test_control_recheck() {
+    let handler = MockHandler::default();
+    assert_eq!(handle_line(&handler, "recheck"), "ok");
+    assert_eq!(*handler.rechecks.lock().unwrap(), 1);
+}
+
+#[test]
+fn test_control_set_dns() {
+    let handler = MockHandler::default();
+    assert_eq!(handle_line(&handler, "set-dns fd00::53"), "ok");
+    assert_eq!(handle_line(&handler, "set-dns fd00::54 br0"), "ok");
+    assert_eq!(handle_line(&handler, "set-dns fd00::55 eth9"), "error: Not a watched interface [eth9]");
+    assert_eq!(*handler.set_dns.lock().unwrap(), vec![
+        (Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x53), None),
+        (Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x54), Some("br0".to_string())),
+    ]);
+}
+
+#[test]
+fn test_control_errors() {
+    let handler = MockHandler::default();
+    assert!(handle_line(&handler, "set-dns").starts_with("error: Usage"));
+    assert!(handle_line(&handler, "set-dns nope").starts_with("error: Invalid address [nope]"));
+    assert!(handle_line(&handler, "set-dns fd00::53 br0 extra").starts_with("error: Usage"));
+    assert_eq!(handle_line(&handler, "status now"), "error: status takes no arguments");
+    assert!(handle_line(&handler, "reboot").starts_with("error: Unknown command [reboot]"));
+    assert_eq!(handle_line(&handler, ""), "error: Empty command");
+    assert!(handler.set_dns.lock().unwrap().is_empty());
+}
@@ -0,0 +1,44 @@
+use {
+    crate::manglelib::dnscommand::{
+        parse_output,
+        run_dns_command,
+    },
+    std::{
+        net::Ipv6Addr,
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn test_dns_command_parse() {
+    let ip = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53);
+    assert_eq!(parse_output(b"2001:db8::53"), Ok(ip));
+    assert_eq!(parse_output(b"  2001:db8::53\n"), Ok(ip));
+    assert!(parse_output(b"").is_err());
+    assert!(parse_output(b"192.0.2.53\n").is_err());
+    assert!(parse_output(b"2001:db8::53\n2001:db8::54\n").is_err());
+    assert!(parse_output(b"\xff").is_err());
+}
+
+#[test]
+fn test_dns_command_run() {
+    assert_eq!(
+        run_dns_command("echo 2001:db8::53", TIMEOUT).unwrap(),
+        Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53)
+    );
+    assert!(run_dns_command("echo 2001:db8::53; exit 1", TIMEOUT).is_err());
+    assert!(run_dns_command("echo nope", TIMEOUT).is_err());
+}
+
+#[test]
+fn test_dns_command_timeout() {
+    // Killed rather than waited for, even with the output pipe held open by a child
+    let start = Instant::now();
+    assert!(run_dns_command("sleep 30; echo 2001:db8::53", Duration::from_millis(200)).is_err());
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
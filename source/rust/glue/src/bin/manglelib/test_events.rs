@@ -0,0 +1,61 @@
+use {
+    crate::manglelib::events::{
+        event,
+        EventLog,
+        LogFormat,
+        LogLevel,
+        LogSink,
+    },
+    std::fs::{
+        read_to_string,
+        rename,
+    },
+};
+
+#[test]
+fn test_events_file_text() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("log");
+    let log = EventLog::new(LogFormat::Text, LogLevel::Info, LogSink::file(&path).unwrap()).fork_queue(3);
+    event!(log, LogLevel::Debug, "hidden", "Below the level");
+    event!(log, LogLevel::Warn, "test", "Something happened", count = 2);
+    let got = read_to_string(&path).unwrap();
+    let lines = got.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].ends_with(" WARN Something happened queue=3 count=2"), "{}", lines[0]);
+}
+
+#[test]
+fn test_events_file_json_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("log");
+    let log = EventLog::new(LogFormat::Json, LogLevel::Info, LogSink::file(&path).unwrap());
+    event!(log, LogLevel::Info, "first", "First");
+
+    // Rotated, later events go to the new file once reopened
+    let rotated = dir.path().join("log.1");
+    rename(&path, &rotated).unwrap();
+    log.reopen().unwrap();
+    event!(log, LogLevel::Info, "second", "Second");
+    let event_of = |text: String| {
+        let line = text.lines().next().unwrap().to_string();
+        let value = serde_json::from_str::<serde_json::Value>(&line).unwrap();
+        return value["event"].as_str().unwrap().to_string();
+    };
+    assert_eq!(event_of(read_to_string(&rotated).unwrap()), "first");
+    assert_eq!(event_of(read_to_string(&path).unwrap()), "second");
+}
+
+#[test]
+fn test_events_error_level() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("log");
+    let log = EventLog::new(LogFormat::Text, LogLevel::Error, LogSink::file(&path).unwrap());
+    event!(log, LogLevel::Warn, "hidden", "Below the level");
+    log.err(LogLevel::Warn, "hidden", loga::err("Also below the level"));
+    event!(log, LogLevel::Error, "test", "Something failed");
+    let got = read_to_string(&path).unwrap();
+    let lines = got.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].ends_with(" ERROR Something failed"), "{}", lines[0]);
+}
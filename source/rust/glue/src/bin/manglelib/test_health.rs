@@ -0,0 +1,76 @@
+use {
+    super::health::Health,
+    std::{
+        sync::atomic::Ordering,
+        time::{
+            Duration,
+            SystemTime,
+        },
+    },
+};
+
+#[test]
+fn test_healthy() {
+    let health = Health::default();
+    let now = SystemTime::now();
+    health.have_ip.store(true, Ordering::Relaxed);
+    health.beat("interface eth0", now);
+    assert_eq!(health.problem(now + Duration::from_secs(10), Duration::from_secs(30)), None);
+}
+
+#[test]
+fn test_no_ip() {
+    let health = Health::default();
+    let now = SystemTime::now();
+    health.beat("interface eth0", now);
+    assert_eq!(health.problem(now, Duration::from_secs(30)).as_deref(), Some("no global IP"));
+}
+
+#[test]
+fn test_stalled() {
+    let health = Health::default();
+    let now = SystemTime::now();
+    health.have_ip.store(true, Ordering::Relaxed);
+    health.beat("interface eth0", now);
+    assert_eq!(
+        health.problem(now + Duration::from_secs(31), Duration::from_secs(30)).as_deref(),
+        Some("interface eth0 stalled")
+    );
+}
+
+#[test]
+fn test_one_stalled() {
+    // A live watcher doesn't hide a stalled one
+    let health = Health::default();
+    let now = SystemTime::now();
+    health.have_ip.store(true, Ordering::Relaxed);
+    health.beat("interface eth0", now);
+    health.beat("interface eth1", now);
+    health.beat("interface eth0", now + Duration::from_secs(30));
+    assert_eq!(
+        health.problem(now + Duration::from_secs(31), Duration::from_secs(30)).as_deref(),
+        Some("interface eth1 stalled")
+    );
+}
+
+#[test]
+fn test_queue_stalled() {
+    // Static addresses, only the queues beat
+    let health = Health::default();
+    let now = SystemTime::now();
+    health.have_ip.store(true, Ordering::Relaxed);
+    health.beat("queue 3", now);
+    assert_eq!(health.problem(now + Duration::from_secs(10), Duration::from_secs(30)), None);
+    assert_eq!(
+        health.problem(now + Duration::from_secs(31), Duration::from_secs(30)).as_deref(),
+        Some("queue 3 stalled")
+    );
+}
+
+#[test]
+fn test_no_watchers() {
+    // Static addresses, nothing beats
+    let health = Health::default();
+    health.have_ip.store(true, Ordering::Relaxed);
+    assert_eq!(health.problem(SystemTime::now(), Duration::from_secs(30)), None);
+}
@@ -0,0 +1,52 @@
+use {
+    crate::manglelib::{
+        events::{
+            EventLog,
+            LogFormat,
+            LogLevel,
+            LogSink,
+        },
+        hook::spawn_ip_change_hook,
+    },
+    std::{
+        fs::read_to_string,
+        net::Ipv6Addr,
+        thread::sleep,
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+};
+
+#[test]
+fn test_hook_debounce() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    let log = EventLog::new(LogFormat::Text, LogLevel::Warn, LogSink::Stderr);
+    let hook =
+        spawn_ip_change_hook(
+            &log,
+            format!("echo \"$1 $SPAGHATTEWAY_INTERFACE\" >> '{}'", out.display()),
+            "br0".to_string(),
+            Duration::from_millis(200),
+        );
+
+    // A flap, only the final value should be reported
+    hook.send(Some(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1))).unwrap();
+    hook.send(None).unwrap();
+    hook.send(Some(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2))).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while read_to_string(&out).unwrap_or_default().is_empty() && Instant::now() < deadline {
+        sleep(Duration::from_millis(50));
+    }
+    assert_eq!(read_to_string(&out).unwrap(), "2001:db8::2 br0\n");
+
+    // Loss
+    hook.send(None).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while read_to_string(&out).unwrap().lines().count() < 2 && Instant::now() < deadline {
+        sleep(Duration::from_millis(50));
+    }
+    assert_eq!(read_to_string(&out).unwrap(), "2001:db8::2 br0\nnone br0\n");
+}
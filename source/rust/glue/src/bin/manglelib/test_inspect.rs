@@ -0,0 +1,52 @@
+use {
+    crate::manglelib::inspect::{
+        describe,
+        parse_hex,
+        to_hex,
+    },
+    glue::ra_dns::{
+        modify,
+        ModifyConfig,
+    },
+    std::net::Ipv6Addr,
+};
+
+const PAYLOAD_RA: &[u8] = include_bytes!("../../ra_dns/testdata/ra1");
+
+#[test]
+fn test_parse_hex() {
+    assert_eq!(parse_hex("6b80ff"), Some(vec![0x6b, 0x80, 0xff]));
+    assert_eq!(parse_hex(" 0x6B80 FF\n"), Some(vec![0x6b, 0x80, 0xff]));
+    assert_eq!(parse_hex("6b:80:ff"), Some(vec![0x6b, 0x80, 0xff]));
+    assert_eq!(parse_hex("6b8"), None);
+    assert_eq!(parse_hex("6g"), None);
+    assert_eq!(parse_hex(&to_hex(PAYLOAD_RA)).as_deref(), Some(PAYLOAD_RA));
+}
+
+#[test]
+fn test_describe_ra() {
+    let config = ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        dns_search: vec!["home.arpa".to_string()],
+        ..Default::default()
+    };
+    let got = describe(PAYLOAD_RA, &config);
+    let want = [
+        "Message: RA".to_string(),
+        "RA from fe80::4a2e:72ff:fe63:7d10, hop limit 64, flags M -, router lifetime 1800s".to_string(),
+        "  option 1, 8 bytes".to_string(),
+        "  option 5, 8 bytes".to_string(),
+        "  option 25, 24 bytes: 2001:db8::53".to_string(),
+        "Rewritten options: 1 5 25 31".to_string(),
+        "Added options: 31".to_string(),
+        format!("Rewritten: {}", to_hex(&modify(PAYLOAD_RA, &config).unwrap())),
+        "".to_string(),
+    ].join("\n");
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_describe_not_rewritten() {
+    let got = describe(&[0x60, 0, 0], &ModifyConfig::default());
+    assert_eq!(got, "Message: Unknown\nNot rewritten: Packet is truncated\n");
+}
@@ -0,0 +1,91 @@
+use {
+    crate::manglelib::metrics::{
+        IfaceMetrics,
+        Metrics,
+    },
+    std::{
+        net::Ipv6Addr,
+        time::{
+            Duration,
+            UNIX_EPOCH,
+        },
+    },
+};
+
+#[test]
+fn test_render() {
+    let metrics = Metrics::default();
+    Metrics::inc(&metrics.packets_modified);
+    Metrics::inc(&metrics.packets_modified);
+    Metrics::inc(&metrics.parse_failures);
+    metrics.interfaces.lock().unwrap().insert("br0".to_string(), IfaceMetrics {
+        ip: Some(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+        last_change: Some(UNIX_EPOCH + Duration::from_secs(1700000000)),
+    });
+    metrics.interfaces.lock().unwrap().insert("br1".to_string(), IfaceMetrics::default());
+    let got = metrics.render();
+    let want = [
+        "# HELP spaghatteway_packets_modified_total Packets rewritten and reinjected",
+        "# TYPE spaghatteway_packets_modified_total counter",
+        "spaghatteway_packets_modified_total 2",
+        "# HELP spaghatteway_packets_dropped_total Packets dropped",
+        "# TYPE spaghatteway_packets_dropped_total counter",
+        "spaghatteway_packets_dropped_total 0",
+        "# HELP spaghatteway_packets_dry_run_modified_total Packets that would have been rewritten, in dry-run mode",
+        "# TYPE spaghatteway_packets_dry_run_modified_total counter",
+        "spaghatteway_packets_dry_run_modified_total 0",
+        "# HELP spaghatteway_parse_failures_total Packets that couldn't be parsed or rewritten",
+        "# TYPE spaghatteway_parse_failures_total counter",
+        "spaghatteway_parse_failures_total 1",
+        "# HELP spaghatteway_packets_rate_limited_total RAs over --max-ra-per-sec, not rewritten",
+        "# TYPE spaghatteway_packets_rate_limited_total counter",
+        "spaghatteway_packets_rate_limited_total 0",
+        "# HELP spaghatteway_packets_bad_checksum_total Packets failing --verify-checksum, not rewritten",
+        "# TYPE spaghatteway_packets_bad_checksum_total counter",
+        "spaghatteway_packets_bad_checksum_total 0",
+        "# HELP spaghatteway_recv_errors_recovered_total Transient netfilter queue read errors (ex: ENOBUFS), skipped",
+        "# TYPE spaghatteway_recv_errors_recovered_total counter",
+        "spaghatteway_recv_errors_recovered_total 0",
+        "# HELP spaghatteway_packets_already_marked_total Packets already marked with --nf-mark, accepted unmodified",
+        "# TYPE spaghatteway_packets_already_marked_total counter",
+        "spaghatteway_packets_already_marked_total 0",
+        "# HELP spaghatteway_global_ip_known Whether the interface currently has a global IP",
+        "# TYPE spaghatteway_global_ip_known gauge",
+        "spaghatteway_global_ip_known{interface=\"br0\"} 1",
+        "spaghatteway_global_ip_known{interface=\"br1\"} 0",
+        "# HELP spaghatteway_last_ip_change_timestamp_seconds When the interface's global IP last changed",
+        "# TYPE spaghatteway_last_ip_change_timestamp_seconds gauge",
+        "spaghatteway_last_ip_change_timestamp_seconds{interface=\"br0\"} 1700000000",
+        "spaghatteway_last_ip_change_timestamp_seconds{interface=\"br1\"} 0",
+        "",
+    ].join("\n");
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_dump() {
+    let metrics = Metrics::default();
+    Metrics::inc(&metrics.packets_modified);
+    Metrics::inc(&metrics.packets_dropped);
+    Metrics::inc(&metrics.packets_dropped);
+    metrics.interfaces.lock().unwrap().insert("br0".to_string(), IfaceMetrics {
+        ip: Some(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+        last_change: Some(UNIX_EPOCH + Duration::from_secs(1700000000)),
+    });
+    metrics.interfaces.lock().unwrap().insert("br1".to_string(), IfaceMetrics::default());
+    let got = metrics.dump(UNIX_EPOCH + Duration::from_secs(1700000120));
+    let want = [
+        "packets_modified: 1",
+        "packets_dropped: 2",
+        "packets_dry_run_modified: 0",
+        "parse_failures: 0",
+        "packets_rate_limited: 0",
+        "packets_bad_checksum: 0",
+        "recv_errors_recovered: 0",
+        "packets_already_marked: 0",
+        "interface br0: ip 2001:db8::1, changed 120s ago",
+        "interface br1: ip none, never changed",
+        "",
+    ].join("\n");
+    assert_eq!(got, want);
+}
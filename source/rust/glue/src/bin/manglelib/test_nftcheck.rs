@@ -0,0 +1,76 @@
+use crate::manglelib::nftcheck::{
+    check_ruleset,
+    NftProblem,
+};
+
+#[test]
+fn test_nftcheck_mark_in_rule() {
+    let ruleset = r#"
+table ip6 spagh {
+	chain out {
+		type filter hook output priority filter; policy accept;
+		meta mark != 0x00000002 icmpv6 type nd-router-advert queue to 7
+	}
+}
+"#;
+    assert_eq!(check_ruleset(ruleset, &[7]), vec![]);
+}
+
+#[test]
+fn test_nftcheck_mark_earlier_in_chain() {
+    let ruleset = r#"
+table inet filter {
+	chain output {
+		type filter hook output priority filter; policy accept;
+		meta mark 0x00000002 accept
+		icmpv6 type nd-router-advert queue num 7
+	}
+}
+"#;
+    assert_eq!(check_ruleset(ruleset, &[7]), vec![]);
+}
+
+#[test]
+fn test_nftcheck_unmarked() {
+    let ruleset = r#"
+table inet filter {
+	chain input {
+		meta mark 0x00000002 accept
+	}
+	chain output {
+		type filter hook output priority filter; policy accept;
+		icmpv6 type nd-router-advert queue flags bypass to 0-3
+	}
+}
+"#;
+    assert_eq!(check_ruleset(ruleset, &[2]), vec![NftProblem::Unmarked {
+        chain: "output".to_string(),
+        rule: "icmpv6 type nd-router-advert queue flags bypass to 0-3".to_string(),
+    }]);
+}
+
+#[test]
+fn test_nftcheck_other_queue() {
+    let ruleset = r#"
+table inet filter {
+	chain output {
+		icmpv6 type nd-router-advert queue num 7
+	}
+}
+"#;
+    assert_eq!(check_ruleset(ruleset, &[8]), vec![NftProblem::NoQueueRule]);
+    assert_eq!(check_ruleset("", &[0]), vec![NftProblem::NoQueueRule]);
+}
+
+#[test]
+fn test_nftcheck_default_queue() {
+    let ruleset = r#"
+table inet filter {
+	chain output {
+		icmpv6 type nd-router-advert queue
+	}
+}
+"#;
+    assert_eq!(check_ruleset(ruleset, &[0]).len(), 1);
+    assert_eq!(check_ruleset(ruleset, &[1]), vec![NftProblem::NoQueueRule]);
+}
@@ -0,0 +1,84 @@
+use crate::manglelib::{
+    nftcheck::check_ruleset,
+    nftgen::{
+        ruleset,
+        NftGenConfig,
+    },
+};
+
+#[test]
+fn test_nftgen_single_queue() {
+    let interfaces = vec!["eth1".to_string(), "eth2".to_string()];
+    let got = ruleset(&NftGenConfig {
+        queues: &[7],
+        nf_mark: 2,
+        nf_mark_mask: u32::MAX,
+        interfaces: &interfaces,
+        dhcp4: false,
+    }).unwrap();
+    assert_eq!(
+        got,
+        [
+            "table inet spaghatteway {",
+            "\tchain rewrite {",
+            "\t\t# Already rewritten, don't queue again",
+            "\t\tmeta mark 0x00000002 accept",
+            "\t\toifname { \"eth1\", \"eth2\" } icmpv6 type nd-router-advert queue num 7 bypass",
+            "\t\toifname { \"eth1\", \"eth2\" } udp sport 547 queue num 7 bypass",
+            "\t}",
+            "",
+            "\tchain output {",
+            "\t\ttype filter hook output priority filter; policy accept;",
+            "\t\tjump rewrite",
+            "\t}",
+            "",
+            "\tchain forward {",
+            "\t\ttype filter hook forward priority filter; policy accept;",
+            "\t\tjump rewrite",
+            "\t}",
+            "}",
+            "",
+        ].join("\n")
+    );
+    assert_eq!(check_ruleset(&got, &[7]), vec![]);
+}
+
+#[test]
+fn test_nftgen_queue_range() {
+    let got = ruleset(&NftGenConfig {
+        queues: &[0, 1, 2],
+        nf_mark: 0x100,
+        nf_mark_mask: u32::MAX,
+        interfaces: &[],
+        dhcp4: true,
+    }).unwrap();
+    assert!(got.contains("\t\tmeta mark 0x00000100 accept\n"));
+    assert!(got.contains("\t\ticmpv6 type nd-router-advert queue num 0-2 fanout,bypass\n"));
+    assert!(got.contains("\t\tudp sport 67 queue num 0-2 fanout,bypass\n"));
+    assert_eq!(check_ruleset(&got, &[0, 1, 2]), vec![]);
+}
+
+#[test]
+fn test_nftgen_noncontiguous() {
+    let config = NftGenConfig {
+        queues: &[0, 2],
+        nf_mark: 1,
+        nf_mark_mask: u32::MAX,
+        interfaces: &[],
+        dhcp4: false,
+    };
+    assert_eq!(ruleset(&config), None);
+}
+
+#[test]
+fn test_nftgen_mark_mask() {
+    let got = ruleset(&NftGenConfig {
+        queues: &[0],
+        nf_mark: 0x100,
+        nf_mark_mask: 0xf00,
+        interfaces: &[],
+        dhcp4: false,
+    }).unwrap();
+    assert!(got.contains("\t\tmeta mark & 0x00000f00 == 0x00000100 accept\n"));
+    assert_eq!(check_ruleset(&got, &[0]), vec![]);
+}
@@ -0,0 +1,791 @@
+use {
+    crate::{
+        manglelib::{
+            events::{
+                EventLog,
+                LogFormat,
+                LogLevel,
+                LogSink,
+            },
+            health::Health,
+            metrics::Metrics,
+            rasource::RaSourceFilter,
+            reassembly::ReassemblingQueue,
+            reload::Reloadable,
+            select::AddressSelect,
+            systemd::Notifier,
+            PacketQueue,
+            QueuedPacket,
+        },
+        process_queue,
+        IfaceState,
+        DEFAULT_MAX_PACKET,
+        LiveConfig,
+        ParseFailureAction,
+        VerdictConfig,
+        WatchConfig,
+        Worker,
+    },
+    glue::ra_dns::{
+        fragment::{
+            Reassembler,
+            Reassembly,
+        },
+        ra_source,
+        ModifyConfig,
+    },
+    nfq::Verdict,
+    std::{
+        collections::{
+            HashMap,
+            HashSet,
+            VecDeque,
+        },
+        io,
+        net::Ipv6Addr,
+        sync::{
+            atomic::{
+                AtomicBool,
+                AtomicU64,
+                Ordering,
+            },
+            Mutex,
+        },
+        time::{
+            Duration,
+            Instant,
+            SystemTime,
+        },
+    },
+};
+
+/// The library test fixtures, an RA with an RDNSS option and a DHCPv6 Reply.
+const PAYLOAD_RA: &[u8] = include_bytes!("../../ra_dns/testdata/ra1");
+const PAYLOAD_DHCP: &[u8] = include_bytes!("../../ra_dns/testdata/dhcp1");
+const OUTDEV: u32 = 7;
+const OTHER_OUTDEV: u32 = 8;
+const NF_MARK: u32 = 0x1234;
+
+/// A queued packet, recording what the loop did with it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MockPacket {
+    pub payload: Vec<u8>,
+    pub outdev: u32,
+    /// The mark when queued.
+    pub mark: u32,
+    pub new_payload: Option<Vec<u8>>,
+    pub nfmark: Option<u32>,
+    pub verdict: Option<Verdict>,
+}
+
+impl MockPacket {
+    pub fn new(payload: &[u8], outdev: u32) -> MockPacket {
+        return MockPacket {
+            payload: payload.to_vec(),
+            outdev,
+            mark: 0,
+            new_payload: None,
+            nfmark: None,
+            verdict: None,
+        };
+    }
+}
+
+impl QueuedPacket for MockPacket {
+    fn get_payload(&self) -> &[u8] {
+        return &self.payload;
+    }
+
+    fn get_outdev(&self) -> u32 {
+        return self.outdev;
+    }
+
+    fn get_nfmark(&self) -> u32 {
+        return self.mark;
+    }
+
+    fn set_payload(&mut self, payload: &[u8]) {
+        self.new_payload = Some(payload.to_vec());
+    }
+
+    fn set_nfmark(&mut self, mark: u32) {
+        self.nfmark = Some(mark);
+    }
+
+    fn set_verdict(&mut self, verdict: Verdict) {
+        self.verdict = Some(verdict);
+    }
+}
+
+/// Feeds scripted packets to the loop and records the verdicts, stopping the loop
+/// once out of packets.
+struct MockQueue<'a> {
+    stop: &'a AtomicBool,
+    packets: VecDeque<MockPacket>,
+    /// Returned before any packets.
+    errors: VecDeque<io::Error>,
+    verdicts: Vec<MockPacket>,
+    /// Called with the packet's index before each packet is received.
+    before_recv: &'a mut dyn FnMut(usize),
+}
+
+impl<'a> PacketQueue for MockQueue<'a> {
+    type Message = MockPacket;
+
+    fn try_recv(&mut self) -> io::Result<Option<MockPacket>> {
+        if let Some(e) = self.errors.pop_front() {
+            return Err(e);
+        }
+        if !self.packets.is_empty() {
+            (self.before_recv)(self.verdicts.len());
+        }
+        let packet = self.packets.pop_front();
+        if packet.is_none() {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+        return Ok(packet);
+    }
+
+    fn wait(&mut self, _timeout: Duration) { }
+
+    fn verdict(&mut self, message: MockPacket) -> io::Result<()> {
+        self.verdicts.push(message);
+        return Ok(());
+    }
+}
+
+fn live_config(extra_dns_ips: Vec<Ipv6Addr>) -> LiveConfig {
+    return LiveConfig {
+        modify_config: ModifyConfig {
+            max_packet: Some(DEFAULT_MAX_PACKET),
+            ..Default::default()
+        },
+        extra_dns_ips,
+        dns_rotate: false,
+        watch_config: WatchConfig {
+            recheck_period: 60,
+            dns_prefix: None,
+            allow_ula: false,
+            allow_temporary: false,
+            address_select: AddressSelect::Lowest,
+            withdraw_on_loss: None,
+        },
+        ra_sources: RaSourceFilter::default(),
+    };
+}
+
+/// A daemon watching one interface (`OUTDEV`).
+struct Harness {
+    log: EventLog,
+    metrics: Metrics,
+    notifier: Notifier,
+    live: Reloadable<LiveConfig>,
+    verdict_config: VerdictConfig,
+    interface_dns: HashMap<u32, Ipv6Addr>,
+    out_interfaces: Option<HashSet<u32>>,
+    iface_states: Mutex<HashMap<String, IfaceState>>,
+}
+
+impl Harness {
+    fn new() -> Harness {
+        let log = EventLog::new(LogFormat::Text, LogLevel::Warn, LogSink::Stderr);
+        return Harness {
+            metrics: Metrics::default(),
+            notifier: Notifier::new(&log, 0, false),
+            live: Reloadable::new(live_config(vec![])),
+            verdict_config: VerdictConfig {
+                nf_mark: NF_MARK,
+                nf_mark_mask: u32::MAX,
+                modified_verdict: Verdict::Repeat,
+                on_parse_failure: ParseFailureAction::Accept,
+                on_rate_limit: ParseFailureAction::Accept,
+                pre_ip: ParseFailureAction::Drop,
+                on_bad_checksum: ParseFailureAction::Drop,
+                dry_run: false,
+                pcap_out: None,
+            },
+            interface_dns: HashMap::new(),
+            out_interfaces: None,
+            iface_states: Mutex::new([("eth0".to_string(), IfaceState {
+                index: Some(OUTDEV),
+                ip: None,
+                withdraw: None,
+                ra_prefix: None,
+            })].into_iter().collect()),
+            log,
+        };
+    }
+
+    fn set_state(&self, ip: Option<Ipv6Addr>, withdraw: Option<(Ipv6Addr, Instant)>) {
+        let mut iface_states = self.iface_states.lock().unwrap();
+        let state = iface_states.get_mut("eth0").unwrap();
+        state.ip = ip;
+        state.withdraw = withdraw;
+    }
+
+    /// Run the loop over `packets`, returning them with their verdicts.
+    fn run(&self, packets: Vec<MockPacket>) -> Vec<MockPacket> {
+        return self.run_with(packets, |_| { });
+    }
+
+    fn worker<'a>(&'a self, stop: &'a AtomicBool) -> Worker<'a> {
+        return Worker {
+            log: &self.log,
+            stop,
+            metrics: &self.metrics,
+            notifier: &self.notifier,
+            live: &self.live,
+            verdict_config: &self.verdict_config,
+            rate_limiter: None,
+            interface_dns: &self.interface_dns,
+            out_interfaces: self.out_interfaces.as_ref(),
+            iface_states: Some(&self.iface_states),
+            remaining_packets: None,
+            startup_passthrough: None,
+            health: None,
+        };
+    }
+
+    /// Like `run`, but calls `before_recv` with the index of each packet before the
+    /// loop receives it.
+    fn run_with(&self, packets: Vec<MockPacket>, mut before_recv: impl FnMut(usize)) -> Vec<MockPacket> {
+        let stop = AtomicBool::new(false);
+        let mut queue = MockQueue {
+            stop: &stop,
+            packets: packets.into_iter().collect(),
+            errors: VecDeque::new(),
+            verdicts: vec![],
+            before_recv: &mut before_recv,
+        };
+        process_queue(self.worker(&stop), &self.log, 0, &mut queue).unwrap();
+        return queue.verdicts;
+    }
+
+    /// Like `run`, but the queue returns `errors` before the packets.
+    fn run_errors(&self, errors: Vec<io::Error>, packets: Vec<MockPacket>) -> Result<Vec<MockPacket>, loga::Error> {
+        let stop = AtomicBool::new(false);
+        let mut queue = MockQueue {
+            stop: &stop,
+            packets: packets.into_iter().collect(),
+            errors: errors.into_iter().collect(),
+            verdicts: vec![],
+            before_recv: &mut |_| { },
+        };
+        process_queue(self.worker(&stop), &self.log, 0, &mut queue)?;
+        return Ok(queue.verdicts);
+    }
+
+    /// Like `run`, with fragment reassembly.
+    fn run_reassembling(&self, packets: Vec<MockPacket>) -> Vec<MockPacket> {
+        let stop = AtomicBool::new(false);
+        let mut queue = MockQueue {
+            stop: &stop,
+            packets: packets.into_iter().collect(),
+            errors: VecDeque::new(),
+            verdicts: vec![],
+            before_recv: &mut |_| { },
+        };
+        process_queue(
+            self.worker(&stop),
+            &self.log,
+            0,
+            &mut ReassemblingQueue::new(&self.log, &mut queue, Verdict::Drop),
+        ).unwrap();
+        return queue.verdicts;
+    }
+}
+
+/// Split an IPv6 packet without extension headers into two fragments, `at` bytes
+/// into the payload.
+fn split(packet: &[u8], at: usize) -> [Vec<u8>; 2] {
+    let (header, payload) = packet.split_at(40);
+    let fragment = |offset: usize, data: &[u8], more: bool| {
+        let mut out = header.to_vec();
+        out[4 .. 6].copy_from_slice(&((8 + data.len()) as u16).to_be_bytes());
+        out[6] = 44;
+        out.extend([header[6], 0]);
+        out.extend((offset as u16 | more as u16).to_be_bytes());
+        out.extend(1u32.to_be_bytes());
+        out.extend_from_slice(data);
+        return out;
+    };
+    return [fragment(0, &payload[.. at], true), fragment(at, &payload[at..], false)];
+}
+
+fn contains_ip(packet: &[u8], ip: Ipv6Addr) -> bool {
+    return packet.windows(16).any(|w| w == ip.octets());
+}
+
+#[test]
+fn test_queue_ip_transitions() {
+    let ip = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+    let harness = Harness::new();
+
+    // No IP yet, drop rather than advertise the router's DNS
+    let got = harness.run(vec![MockPacket::new(PAYLOAD_RA, OUTDEV)]);
+    assert_eq!(got.len(), 1);
+    assert_eq!(got[0].verdict, Some(Verdict::Drop));
+    assert_eq!(got[0].new_payload, None);
+    assert_eq!(harness.metrics.packets_dropped.load(Ordering::Relaxed), 1);
+
+    // Got an IP, rewrite and mark for reinjection
+    harness.set_state(Some(ip), None);
+    let got = harness.run(vec![MockPacket::new(PAYLOAD_RA, OUTDEV)]);
+    assert_eq!(got.len(), 1);
+    assert_eq!(got[0].verdict, Some(Verdict::Repeat));
+    assert_eq!(got[0].nfmark, Some(NF_MARK));
+    assert!(contains_ip(got[0].new_payload.as_ref().unwrap(), ip));
+    assert_eq!(harness.metrics.packets_modified.load(Ordering::Relaxed), 1);
+
+    // Lost the IP, back to dropping
+    harness.set_state(None, None);
+    let got = harness.run(vec![MockPacket::new(PAYLOAD_RA, OUTDEV)]);
+    assert_eq!(got.len(), 1);
+    assert_eq!(got[0].verdict, Some(Verdict::Drop));
+    assert_eq!(harness.metrics.packets_dropped.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn test_queue_unwatched_interface() {
+    let harness = Harness::new();
+    harness.set_state(Some(Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1)), None);
+    let got = harness.run(vec![MockPacket::new(PAYLOAD_RA, OTHER_OUTDEV)]);
+    assert_eq!(got.len(), 1);
+    assert_eq!(got[0].verdict, Some(Verdict::Accept));
+    assert_eq!(got[0].new_payload, None);
+}
+
+#[test]
+fn test_queue_withdraw() {
+    let ip = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+    let harness = Harness::new();
+
+    // Lost the IP, but still withdrawing it
+    harness.set_state(None, Some((ip, Instant::now() + Duration::from_secs(60))));
+    let got = harness.run(vec![MockPacket::new(PAYLOAD_RA, OUTDEV)]);
+    assert_eq!(got.len(), 1);
+    assert_eq!(got[0].verdict, Some(Verdict::Repeat));
+    assert!(contains_ip(got[0].new_payload.as_ref().unwrap(), ip));
+
+    // Done withdrawing
+    harness.set_state(None, Some((ip, Instant::now() - Duration::from_secs(1))));
+    let got = harness.run(vec![MockPacket::new(PAYLOAD_RA, OUTDEV)]);
+    assert_eq!(got[0].verdict, Some(Verdict::Drop));
+}
+
+#[test]
+fn test_queue_rapid_ip_changes() {
+    let ips = [1, 2, 3, 4].map(|i| Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, i));
+    let harness = Harness::new();
+
+    // Several changes between packets, each packet should use the latest
+    let got = harness.run_with(vec![MockPacket::new(PAYLOAD_RA, OUTDEV); 4], |i| match i {
+        0 => {
+            harness.set_state(Some(ips[0]), None);
+            harness.set_state(Some(ips[1]), None);
+        },
+        1 => {
+            harness.set_state(Some(ips[2]), None);
+        },
+        2 => {
+            harness.set_state(None, None);
+            harness.set_state(Some(ips[3]), None);
+        },
+        3 => {
+            harness.set_state(Some(ips[0]), None);
+            harness.set_state(None, None);
+        },
+        _ => unreachable!(),
+    });
+    assert_eq!(got.len(), 4);
+    for (packet, want_ip) in Iterator::zip(got.iter(), [Some(ips[1]), Some(ips[2]), Some(ips[3]), None]) {
+        match want_ip {
+            Some(want_ip) => {
+                assert_eq!(packet.verdict, Some(Verdict::Repeat));
+                let new_payload = packet.new_payload.as_ref().unwrap();
+                for ip in ips {
+                    assert_eq!(contains_ip(new_payload, ip), ip == want_ip);
+                }
+            },
+            None => {
+                assert_eq!(packet.verdict, Some(Verdict::Drop));
+            },
+        }
+    }
+}
+
+#[test]
+fn test_queue_fragments() {
+    let ip = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+    let harness = Harness::new();
+    let [first, second] = split(PAYLOAD_DHCP, 88);
+
+    // No IP yet, both dropped
+    let got = harness.run_reassembling(vec![MockPacket::new(&first, OUTDEV), MockPacket::new(&second, OUTDEV)]);
+    assert_eq!(got.len(), 2);
+    for packet in &got {
+        assert_eq!(packet.verdict, Some(Verdict::Drop));
+    }
+
+    // Rewritten as a whole, sent as fragments in place of the originals
+    harness.set_state(Some(ip), None);
+    let got = harness.run_reassembling(vec![MockPacket::new(&first, OUTDEV), MockPacket::new(&second, OUTDEV)]);
+    assert_eq!(got.len(), 2);
+    let now = Instant::now();
+    let mut reassembler = Reassembler::new(1, 2, Duration::from_secs(1));
+    let mut reassembled = None;
+    for packet in got {
+        assert_eq!(packet.verdict, Some(Verdict::Repeat));
+        assert_eq!(packet.nfmark, Some(NF_MARK));
+        match reassembler.add(now, packet.new_payload.as_ref().unwrap(), ()) {
+            Ok(Reassembly::Incomplete) => { },
+            Ok(Reassembly::Complete { datagram, .. }) => reassembled = Some(datagram.packet),
+            _ => panic!(),
+        }
+    }
+    assert!(contains_ip(&reassembled.unwrap(), ip));
+}
+
+#[test]
+fn test_queue_fragments_over_max_packet() {
+    let ip = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+    let extra_ip = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x53);
+    let harness = Harness::new();
+    harness.live.set(live_config(vec![extra_ip]));
+    harness.set_state(Some(ip), None);
+
+    // Padded with unknown options past `--max-packet`, so it has to be fragmented
+    let mut big_ra = PAYLOAD_RA.to_vec();
+    while big_ra.len() <= DEFAULT_MAX_PACKET {
+        big_ra.extend([0xfd, 1, 0, 0, 0, 0, 0, 0]);
+    }
+    let payload_len = (big_ra.len() - 40) as u16;
+    big_ra[4 .. 6].copy_from_slice(&payload_len.to_be_bytes());
+
+    // Rewritten even though the datagram grows past the limit, the first fragment has
+    // room for the extra address
+    let [first, second] = split(&big_ra, 1000);
+    let got = harness.run_reassembling(vec![MockPacket::new(&first, OUTDEV), MockPacket::new(&second, OUTDEV)]);
+    assert_eq!(got.len(), 2);
+    let now = Instant::now();
+    let mut reassembler = Reassembler::new(1, 2, Duration::from_secs(1));
+    let mut reassembled = None;
+    for packet in got {
+        assert_eq!(packet.verdict, Some(Verdict::Repeat));
+        let payload = packet.new_payload.as_ref().unwrap();
+        assert!(payload.len() <= first.len());
+        match reassembler.add(now, payload, ()) {
+            Ok(Reassembly::Incomplete) => { },
+            Ok(Reassembly::Complete { datagram, .. }) => reassembled = Some(datagram.packet),
+            _ => panic!(),
+        }
+    }
+    let reassembled = reassembled.unwrap();
+    assert_eq!(reassembled.len(), big_ra.len() + 16);
+    assert!(contains_ip(&reassembled, extra_ip));
+}
+
+#[test]
+fn test_queue_recv_errors() {
+    let harness = Harness::new();
+
+    // Transient errors are counted and skipped
+    let got =
+        harness
+            .run_errors(
+                vec![io::Error::from_raw_os_error(libc::ENOBUFS), io::Error::from(io::ErrorKind::Interrupted)],
+                vec![MockPacket::new(PAYLOAD_RA, OUTDEV)],
+            )
+            .unwrap();
+    assert_eq!(got.len(), 1);
+    assert_eq!(got[0].verdict, Some(Verdict::Drop));
+    assert_eq!(harness.metrics.recv_errors_recovered.load(Ordering::Relaxed), 2);
+
+    // Anything else stops the loop
+    assert!(
+        harness
+            .run_errors(vec![io::Error::from_raw_os_error(libc::EBADF)], vec![MockPacket::new(PAYLOAD_RA, OUTDEV)])
+            .is_err()
+    );
+    assert_eq!(harness.metrics.recv_errors_recovered.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn test_queue_max_packets() {
+    let harness = Harness::new();
+    let stop = AtomicBool::new(false);
+    let remaining_packets = AtomicU64::new(2);
+    let mut queue = MockQueue {
+        stop: &stop,
+        packets: (0 .. 3).map(|_| MockPacket::new(PAYLOAD_RA, OUTDEV)).collect(),
+        errors: VecDeque::new(),
+        verdicts: vec![],
+        before_recv: &mut |_| { },
+    };
+    process_queue(Worker {
+        remaining_packets: Some(&remaining_packets),
+        ..harness.worker(&stop)
+    }, &harness.log, 0, &mut queue).unwrap();
+    assert_eq!(queue.verdicts.len(), 2);
+    assert_eq!(queue.packets.len(), 1);
+}
+
+#[test]
+fn test_queue_startup_passthrough() {
+    let harness = Harness::new();
+    let stop = AtomicBool::new(false);
+    let startup_passthrough = AtomicBool::new(true);
+    let mut queue = MockQueue {
+        stop: &stop,
+        packets: [MockPacket::new(PAYLOAD_RA, OUTDEV)].into_iter().collect(),
+        errors: VecDeque::new(),
+        verdicts: vec![],
+        before_recv: &mut |_| { },
+    };
+    process_queue(Worker {
+        startup_passthrough: Some(&startup_passthrough),
+        ..harness.worker(&stop)
+    }, &harness.log, 0, &mut queue).unwrap();
+
+    // No IP, passed through unmodified rather than dropped
+    assert_eq!(queue.verdicts.len(), 1);
+    assert_eq!(queue.verdicts[0].verdict, Some(Verdict::Accept));
+    assert_eq!(queue.verdicts[0].new_payload, None);
+    assert_eq!(harness.metrics.packets_dropped.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn test_queue_health() {
+    // Beats while idle, named by queue
+    let harness = Harness::new();
+    let stop = AtomicBool::new(false);
+    let health = Health::default();
+    health.have_ip.store(true, Ordering::Relaxed);
+    let mut queue = MockQueue {
+        stop: &stop,
+        packets: VecDeque::new(),
+        errors: VecDeque::new(),
+        verdicts: vec![],
+        before_recv: &mut |_| { },
+    };
+    let now = SystemTime::now();
+    process_queue(Worker {
+        health: Some(&health),
+        ..harness.worker(&stop)
+    }, &harness.log, 3, &mut queue).unwrap();
+    assert_eq!(health.problem(now, Duration::from_secs(30)), None);
+    assert_eq!(
+        health.problem(now + Duration::from_secs(60), Duration::from_secs(30)).as_deref(),
+        Some("queue 3 stalled")
+    );
+}
+
+#[test]
+fn test_queue_pre_ip() {
+    let ip = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+    for (pre_ip, want) in [(ParseFailureAction::Drop, Verdict::Drop), (ParseFailureAction::Accept, Verdict::Accept)] {
+        let mut harness = Harness::new();
+        harness.verdict_config.pre_ip = pre_ip;
+
+        // Waiting for the first IP
+        let got = harness.run(vec![MockPacket::new(PAYLOAD_RA, OUTDEV)]);
+        assert_eq!(got[0].verdict, Some(want));
+        assert_eq!(got[0].new_payload, None);
+
+        // Rewriting
+        harness.set_state(Some(ip), None);
+        let got = harness.run(vec![MockPacket::new(PAYLOAD_RA, OUTDEV)]);
+        assert_eq!(got[0].verdict, Some(Verdict::Repeat));
+
+        // Lost the IP
+        harness.set_state(None, None);
+        let got = harness.run(vec![MockPacket::new(PAYLOAD_RA, OUTDEV)]);
+        assert_eq!(got[0].verdict, Some(want));
+        assert_eq!(got[0].new_payload, None);
+    }
+}
+
+#[test]
+fn test_queue_already_marked() {
+    let ip = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+    let harness = Harness::new();
+    harness.set_state(Some(ip), None);
+    let got = harness.run(vec![MockPacket {
+        mark: NF_MARK,
+        ..MockPacket::new(PAYLOAD_RA, OUTDEV)
+    }, MockPacket::new(PAYLOAD_RA, OUTDEV)]);
+    assert_eq!(got.len(), 2);
+
+    // Passed through untouched
+    assert_eq!(got[0].verdict, Some(Verdict::Accept));
+    assert_eq!(got[0].new_payload, None);
+    assert_eq!(got[0].nfmark, None);
+    assert_eq!(harness.metrics.packets_already_marked.load(Ordering::Relaxed), 1);
+
+    // Unmarked packets are still rewritten
+    assert_eq!(got[1].verdict, Some(Verdict::Repeat));
+    assert!(contains_ip(got[1].new_payload.as_ref().unwrap(), ip));
+}
+
+#[test]
+fn test_queue_mark_mask() {
+    let ip = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+    let mut harness = Harness::new();
+    harness.verdict_config.nf_mark = 0x0100;
+    harness.verdict_config.nf_mark_mask = 0xff00;
+    harness.set_state(Some(ip), None);
+    let got = harness.run(vec![MockPacket {
+        mark: 0x12_0034,
+        ..MockPacket::new(PAYLOAD_RA, OUTDEV)
+    }, MockPacket {
+        mark: 0x12_0134,
+        ..MockPacket::new(PAYLOAD_RA, OUTDEV)
+    }]);
+    assert_eq!(got.len(), 2);
+
+    // Our bits replaced, other tools' kept
+    assert_eq!(got[0].verdict, Some(Verdict::Repeat));
+    assert_eq!(got[0].nfmark, Some(0x12_0134));
+
+    // Our bits already set, other bits don't matter
+    assert_eq!(got[1].verdict, Some(Verdict::Accept));
+    assert_eq!(got[1].nfmark, None);
+    assert_eq!(harness.metrics.packets_already_marked.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn test_queue_reload() {
+    let ip = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+    let extra_ip = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x53);
+    let harness = Harness::new();
+    harness.set_state(Some(ip), None);
+    let got = harness.run_with(vec![MockPacket::new(PAYLOAD_RA, OUTDEV), MockPacket::new(PAYLOAD_RA, OUTDEV)], |i| {
+        if i == 1 {
+            harness.live.set(live_config(vec![extra_ip]));
+        }
+    });
+    assert_eq!(got.len(), 2);
+    assert!(!contains_ip(got[0].new_payload.as_ref().unwrap(), extra_ip));
+
+    // Picked up between packets
+    assert!(contains_ip(got[1].new_payload.as_ref().unwrap(), ip));
+    assert!(contains_ip(got[1].new_payload.as_ref().unwrap(), extra_ip));
+}
+
+#[test]
+fn test_queue_dns_rotate() {
+    let ip = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+    let extra_ip = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x53);
+    let harness = Harness::new();
+    harness.live.set(LiveConfig {
+        dns_rotate: true,
+        ..live_config(vec![extra_ip])
+    });
+    harness.set_state(Some(ip), None);
+    let got = harness.run(vec![MockPacket::new(PAYLOAD_RA, OUTDEV), MockPacket::new(PAYLOAD_RA, OUTDEV)]);
+    assert_eq!(got.len(), 2);
+    let position = |packet: &MockPacket, ip: Ipv6Addr| {
+        return packet.new_payload.as_ref().unwrap().windows(16).position(|w| w == ip.octets()).unwrap();
+    };
+
+    // Both listed each time, but a different one first
+    assert!(position(&got[0], ip) < position(&got[0], extra_ip));
+    assert!(position(&got[1], extra_ip) < position(&got[1], ip));
+}
+
+#[test]
+fn test_queue_ra_source() {
+    let ip = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+    let router = ra_source(PAYLOAD_RA).unwrap();
+    let harness = Harness::new();
+    harness.set_state(Some(ip), None);
+    for (entry, rewritten) in [
+        (format!("allow={}", router), true),
+        ("allow=fe80::ffff".to_string(), false),
+        (format!("deny={}", router), false),
+        ("deny=fe80::ffff".to_string(), true),
+    ] {
+        let mut live = live_config(vec![]);
+        live.ra_sources.add(&entry).unwrap();
+        harness.live.set(live);
+        let got = harness.run(vec![MockPacket::new(PAYLOAD_RA, OUTDEV), MockPacket::new(PAYLOAD_DHCP, OUTDEV)]);
+        assert_eq!(got.len(), 2);
+        if rewritten {
+            assert_eq!(got[0].verdict, Some(Verdict::Repeat), "{}", entry);
+            assert!(contains_ip(got[0].new_payload.as_ref().unwrap(), ip));
+        } else {
+            assert_eq!(got[0].verdict, Some(Verdict::Accept), "{}", entry);
+            assert_eq!(got[0].new_payload, None);
+        }
+
+        // DHCPv6 isn't filtered
+        assert_eq!(got[1].verdict, Some(Verdict::Repeat), "{}", entry);
+    }
+}
+
+#[test]
+fn test_queue_out_interface() {
+    let ip = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+    let mut harness = Harness::new();
+    harness.interface_dns.insert(OTHER_OUTDEV, ip);
+    harness.out_interfaces = Some([OUTDEV].into_iter().collect());
+    harness.set_state(Some(ip), None);
+    let got = harness.run(vec![MockPacket::new(PAYLOAD_RA, OUTDEV), MockPacket::new(PAYLOAD_RA, OTHER_OUTDEV)]);
+    assert_eq!(got.len(), 2);
+    assert_eq!(got[0].verdict, Some(Verdict::Repeat));
+    assert!(contains_ip(got[0].new_payload.as_ref().unwrap(), ip));
+
+    // Would have been rewritten with the `--interface-dns` address
+    assert_eq!(got[1].verdict, Some(Verdict::Accept));
+    assert_eq!(got[1].new_payload, None);
+    assert_eq!(got[1].nfmark, None);
+}
+
+#[test]
+fn test_queue_bad_checksum() {
+    let ip = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+    let harness = Harness::new();
+    harness.set_state(Some(ip), None);
+    let mut live = live_config(vec![]);
+    live.modify_config.verify_checksum = true;
+    harness.live.set(live);
+    let mut corrupt = PAYLOAD_RA.to_vec();
+    corrupt[40 + 6] ^= 0x01;
+    let got = harness.run(vec![MockPacket::new(PAYLOAD_RA, OUTDEV), MockPacket::new(&corrupt, OUTDEV)]);
+    assert_eq!(got.len(), 2);
+    assert_eq!(got[0].verdict, Some(Verdict::Repeat));
+    assert_eq!(got[1].verdict, Some(Verdict::Drop));
+    assert_eq!(got[1].new_payload, None);
+    assert_eq!(harness.metrics.packets_bad_checksum.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn test_queue_max_packet() {
+    let ip = Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+    let extra_ip = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x53);
+    let harness = Harness::new();
+    harness.set_state(Some(ip), None);
+    let live = |max_packet: usize| {
+        let mut live = live_config(vec![extra_ip]);
+        live.modify_config.dns_search = vec!["example.com".to_string()];
+        live.modify_config.max_packet = Some(max_packet);
+        return live;
+    };
+
+    // Room for the extra address but not the search list, which is left out
+    harness.live.set(live(PAYLOAD_RA.len() + 16));
+    let got = harness.run(vec![MockPacket::new(PAYLOAD_RA, OUTDEV)]);
+    assert_eq!(got.len(), 1);
+    assert_eq!(got[0].verdict, Some(Verdict::Repeat));
+    let payload = got[0].new_payload.as_ref().unwrap();
+    assert_eq!(payload.len(), PAYLOAD_RA.len() + 16);
+    assert!(contains_ip(payload, ip));
+    assert!(contains_ip(payload, extra_ip));
+
+    // No room for either, pass the original through
+    harness.live.set(live(PAYLOAD_RA.len()));
+    let got = harness.run(vec![MockPacket::new(PAYLOAD_RA, OUTDEV)]);
+    assert_eq!(got.len(), 1);
+    assert_eq!(got[0].verdict, Some(Verdict::Accept));
+    assert_eq!(got[0].new_payload, None);
+}
@@ -0,0 +1,57 @@
+use {
+    crate::manglelib::rasource::RaSourceFilter,
+    std::net::Ipv6Addr,
+};
+
+const ROUTER: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+const NEIGHBOR: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+const OTHER_LINK: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 1, 0, 0, 0, 1);
+
+fn filter(entries: &[&str]) -> RaSourceFilter {
+    let mut out = RaSourceFilter::default();
+    for entry in entries {
+        out.add(entry).unwrap();
+    }
+    return out;
+}
+
+#[test]
+fn test_ra_source_empty() {
+    let filter = filter(&[]);
+    assert!(filter.allows(&ROUTER));
+    assert!(filter.allows(&NEIGHBOR));
+}
+
+#[test]
+fn test_ra_source_allow() {
+    let filter = filter(&["allow=fe80::1"]);
+    assert!(filter.allows(&ROUTER));
+    assert!(!filter.allows(&NEIGHBOR));
+    assert!(!filter.allows(&OTHER_LINK));
+}
+
+#[test]
+fn test_ra_source_deny() {
+    let filter = filter(&["deny=fe80::2"]);
+    assert!(filter.allows(&ROUTER));
+    assert!(!filter.allows(&NEIGHBOR));
+    assert!(filter.allows(&OTHER_LINK));
+}
+
+#[test]
+fn test_ra_source_deny_wins() {
+    let filter = filter(&["allow=fe80::/64", "deny=fe80::2"]);
+    assert!(filter.allows(&ROUTER));
+    assert!(!filter.allows(&NEIGHBOR));
+    assert!(!filter.allows(&OTHER_LINK));
+}
+
+#[test]
+fn test_ra_source_invalid() {
+    let mut filter = RaSourceFilter::default();
+    assert!(filter.add("fe80::1").is_err());
+    assert!(filter.add("permit=fe80::1").is_err());
+    assert!(filter.add("allow=fe80::x").is_err());
+    assert!(filter.add("allow=fe80::/129").is_err());
+    assert_eq!(filter, RaSourceFilter::default());
+}
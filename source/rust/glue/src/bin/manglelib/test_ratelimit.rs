@@ -0,0 +1,31 @@
+use {
+    crate::manglelib::ratelimit::RateLimiter,
+    std::{
+        net::Ipv6Addr,
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+};
+
+#[test]
+fn test_rate_limit() {
+    let a = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    let b = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+    let start = Instant::now();
+    let mut limiter = RateLimiter::new(2);
+
+    // Burst up to the limit
+    assert!(limiter.allow(a, start));
+    assert!(limiter.allow(a, start));
+    assert!(!limiter.allow(a, start));
+
+    // Other sources aren't affected
+    assert!(limiter.allow(b, start));
+
+    // Refills over time
+    assert!(!limiter.allow(a, start + Duration::from_millis(100)));
+    assert!(limiter.allow(a, start + Duration::from_millis(600)));
+    assert!(!limiter.allow(a, start + Duration::from_millis(600)));
+}
@@ -0,0 +1,14 @@
+use crate::manglelib::reload::Reloadable;
+
+#[test]
+fn test_reloadable() {
+    let reloadable = Reloadable::new(1);
+    let generation = reloadable.generation();
+    let held = reloadable.get();
+    assert_eq!(*reloadable.set(2), 1);
+    assert_ne!(reloadable.generation(), generation);
+    assert_eq!(*reloadable.get(), 2);
+
+    // Existing readers keep what they had
+    assert_eq!(*held, 1);
+}
@@ -0,0 +1,76 @@
+use {
+    crate::manglelib::replay::{
+        replay,
+        ReplayCounts,
+    },
+    glue::ra_dns::{
+        modify,
+        ModifyConfig,
+    },
+    pcap_file::{
+        pcap::{
+            PcapHeader,
+            PcapPacket,
+            PcapReader,
+            PcapWriter,
+        },
+        DataLink,
+    },
+    std::{
+        net::Ipv6Addr,
+        time::Duration,
+    },
+};
+
+const PAYLOAD_RA: &[u8] = include_bytes!("../../ra_dns/testdata/ra1");
+
+/// Ethernet header for IPv6 (`0x86dd`), or ARP (`0x0806`).
+fn ethernet(ethertype: u16, payload: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x33, 0x33, 0, 0, 0, 1, 0x48, 0x2e, 0x72, 0x63, 0x7d, 0x10];
+    out.extend(ethertype.to_be_bytes());
+    out.extend_from_slice(payload);
+    return out;
+}
+
+#[test]
+fn test_replay_ethernet() {
+    let frames = [
+        ethernet(0x86dd, PAYLOAD_RA),
+        ethernet(0x0806, &[0; 28]),
+        ethernet(0x86dd, &[0x60, 0, 0]),
+    ];
+    let mut input = PcapWriter::with_header(vec![], PcapHeader {
+        datalink: DataLink::ETHERNET,
+        ..Default::default()
+    }).unwrap();
+    for (i, frame) in frames.iter().enumerate() {
+        input.write_packet(&PcapPacket::new(Duration::from_secs(i as u64), frame.len() as u32, frame)).unwrap();
+    }
+    let input = input.into_writer();
+    let config = ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        ..Default::default()
+    };
+    let mut output = vec![];
+    let counts = replay(&input[..], &mut output, &config).unwrap();
+    assert_eq!(counts, ReplayCounts {
+        modified: 1,
+        unchanged: 1,
+        failed: 1,
+    });
+
+    // Same packets, with the RA rewritten behind the same ethernet header
+    let mut reader = PcapReader::new(&output[..]).unwrap();
+    assert_eq!(reader.header().datalink, DataLink::ETHERNET);
+    let mut got = vec![];
+    while let Some(packet) = reader.next_packet() {
+        let packet = packet.unwrap();
+        assert_eq!(packet.orig_len as usize, packet.data.len());
+        got.push((packet.timestamp, packet.data.to_vec()));
+    }
+    assert_eq!(got, vec![
+        (Duration::from_secs(0), ethernet(0x86dd, &modify(PAYLOAD_RA, &config).unwrap())),
+        (Duration::from_secs(1), frames[1].clone()),
+        (Duration::from_secs(2), frames[2].clone()),
+    ]);
+}
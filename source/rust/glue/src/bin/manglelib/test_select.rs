@@ -0,0 +1,294 @@
+use {
+    crate::manglelib::{
+        netlink::{
+            AddrInfo,
+            IFA_F_DADFAILED,
+            IFA_F_DEPRECATED,
+            IFA_F_TEMPORARY,
+            IFA_F_TENTATIVE,
+        },
+        select::{
+            is_candidate,
+            parse_dns_ip,
+            parse_interface_dns,
+            preference,
+            select_ip,
+            AddressSelect,
+            Ipv6Prefix,
+        },
+    },
+    std::{
+        net::Ipv6Addr,
+        str::FromStr,
+    },
+};
+
+fn ip(s: &str) -> Ipv6Addr {
+    return Ipv6Addr::from_str(s).unwrap();
+}
+
+fn addr(s: &str, valid_lifetime: u32, created: u32) -> AddrInfo {
+    return AddrInfo {
+        addr: ip(s),
+        flags: 0,
+        valid_lifetime,
+        created,
+    };
+}
+
+#[test]
+fn test_parse_prefix() {
+    assert_eq!(Ipv6Prefix::from_str("2001:db8::/32").unwrap(), Ipv6Prefix {
+        addr: ip("2001:db8::"),
+        len: 32,
+    });
+    assert!(Ipv6Prefix::from_str("2001:db8::").is_err());
+    assert!(Ipv6Prefix::from_str("2001:db8::/129").is_err());
+    assert!(Ipv6Prefix::from_str("2001:db8::/x").is_err());
+}
+
+#[test]
+fn test_parse_interface_dns() {
+    assert_eq!(parse_interface_dns("eth1=fd00:1::53").unwrap(), ("eth1".to_string(), ip("fd00:1::53")));
+    assert!(parse_interface_dns("eth1").is_err());
+    assert!(parse_interface_dns("=fd00:1::53").is_err());
+    assert!(parse_interface_dns("eth1=fd00:1::53/64").is_err());
+}
+
+#[test]
+fn test_parse_dns_ip() {
+    assert_eq!(parse_dns_ip("2001:db8::53").unwrap(), (ip("2001:db8::53"), None));
+    assert_eq!(parse_dns_ip("fe80::1").unwrap(), (ip("fe80::1"), None));
+    assert_eq!(parse_dns_ip("fe80::1%eth0").unwrap(), (ip("fe80::1"), Some("eth0".to_string())));
+    assert!(parse_dns_ip("fe80::1%").is_err());
+    assert!(parse_dns_ip("2001:db8::53%eth0").is_err());
+    assert!(parse_dns_ip("fe80::1/64").is_err());
+}
+
+#[test]
+fn test_prefix_contains() {
+    let prefix = Ipv6Prefix::from_str("2001:db8:1::/48").unwrap();
+    assert!(prefix.contains(&ip("2001:db8:1::1")));
+    assert!(prefix.contains(&ip("2001:db8:1:ffff::1")));
+    assert!(!prefix.contains(&ip("2001:db8:2::1")));
+    assert!(Ipv6Prefix::from_str("::/0").unwrap().contains(&ip("2001:db8:2::1")));
+    assert!(Ipv6Prefix::from_str("2001:db8::1/128").unwrap().contains(&ip("2001:db8::1")));
+    assert!(!Ipv6Prefix::from_str("2001:db8::1/128").unwrap().contains(&ip("2001:db8::2")));
+}
+
+#[test]
+fn test_select_ip() {
+    let candidates = [
+        addr("2001:db8:2::5", u32::MAX, 0),
+        addr("2001:db8:1::9", u32::MAX, 0),
+        addr("2001:db8:1::3", u32::MAX, 0),
+        addr("2600::1", u32::MAX, 0),
+    ];
+    let prefix = Ipv6Prefix::from_str("2001:db8:1::/48").unwrap();
+    assert_eq!(select_ip(candidates, Some(&prefix), None, AddressSelect::Lowest, None), Some(ip("2001:db8:1::3")));
+    assert_eq!(select_ip(candidates, None, None, AddressSelect::Lowest, None), Some(ip("2001:db8:1::3")));
+    assert_eq!(select_ip(candidates, Some(&prefix), None, AddressSelect::Highest, None), Some(ip("2001:db8:1::9")));
+    assert_eq!(select_ip(candidates, None, None, AddressSelect::Highest, None), Some(ip("2600::1")));
+    let prefix = Ipv6Prefix::from_str("2001:db8:3::/48").unwrap();
+    assert_eq!(select_ip(candidates, Some(&prefix), None, AddressSelect::Lowest, None), None);
+}
+
+#[test]
+fn test_select_ip_lifetime() {
+    // Prefix rollover, the old address is still valid for a bit
+    let candidates = [addr("2001:db8:1::5", 120, 100), addr("2001:db8:2::5", 86400, 500), addr("2001:db8:3::5", 0, 50)];
+    assert_eq!(select_ip(candidates, None, None, AddressSelect::LongestLifetime, None), Some(ip("2001:db8:2::5")));
+    let prefix = Ipv6Prefix::from_str("2001:db8:1::/48").unwrap();
+    assert_eq!(
+        select_ip(candidates, Some(&prefix), None, AddressSelect::LongestLifetime, None),
+        Some(ip("2001:db8:1::5"))
+    );
+
+    // Ties still go to the lowest
+    let candidates = [addr("2001:db8:2::5", 600, 0), addr("2001:db8:1::5", 600, 0)];
+    assert_eq!(select_ip(candidates, None, None, AddressSelect::LongestLifetime, None), Some(ip("2001:db8:1::5")));
+}
+
+#[test]
+fn test_select_ip_oldest() {
+    let candidates =
+        [addr("2001:db8:1::5", 120, 900), addr("2001:db8:2::5", 86400, 300), addr("2001:db8:3::5", 0, 600)];
+    assert_eq!(select_ip(candidates, None, None, AddressSelect::Oldest, None), Some(ip("2001:db8:2::5")));
+
+    // Enumeration order doesn't matter
+    let mut reversed = candidates;
+    reversed.reverse();
+    for policy in [
+        AddressSelect::Lowest,
+        AddressSelect::Highest,
+        AddressSelect::Oldest,
+        AddressSelect::LongestLifetime,
+    ] {
+        assert_eq!(select_ip(candidates, None, None, policy, None), select_ip(reversed, None, None, policy, None));
+    }
+}
+
+#[test]
+fn test_is_candidate() {
+    // Global
+    assert!(is_candidate(&ip("2600::1"), 0, false, false));
+    assert!(is_candidate(&ip("2600::1"), 0, true, false));
+
+    // ULA
+    assert!(!is_candidate(&ip("fd00::53"), 0, false, false));
+    assert!(is_candidate(&ip("fd00::53"), 0, true, false));
+    assert!(is_candidate(&ip("fc12::53"), 0, true, false));
+
+    // Link-local
+    assert!(!is_candidate(&ip("fe80::1"), 0, false, false));
+    assert!(!is_candidate(&ip("fe80::1"), 0, true, false));
+
+    // Not usable yet or any more
+    assert!(!is_candidate(&ip("2600::1"), IFA_F_TENTATIVE, false, false));
+    assert!(!is_candidate(&ip("fd00::53"), IFA_F_DADFAILED | IFA_F_TENTATIVE, true, false));
+
+    // Usable, but only a last resort
+    assert!(is_candidate(&ip("2600::1"), IFA_F_DEPRECATED, false, false));
+
+    // Rotates, unless allowed
+    assert!(!is_candidate(&ip("2600::1"), IFA_F_TEMPORARY, false, false));
+    assert!(is_candidate(&ip("2600::1"), IFA_F_TEMPORARY, false, true));
+    assert!(!is_candidate(&ip("2600::1"), IFA_F_TEMPORARY | IFA_F_TENTATIVE, false, true));
+
+    // Unrelated flags (permanent, nodad)
+    assert!(is_candidate(&ip("2600::1"), 0x80 | 0x02, false, false));
+}
+
+#[test]
+fn test_select_ip_sticky() {
+    // A fixed set, in any order, settles on one address and keeps it
+    let candidates = [addr("2001:db8:1::5", 600, 0), addr("2001:db8:2::5", 600, 0)];
+    let mut reversed = candidates;
+    reversed.reverse();
+    for policy in [
+        AddressSelect::Lowest,
+        AddressSelect::Highest,
+        AddressSelect::Oldest,
+        AddressSelect::LongestLifetime,
+    ] {
+        let first = select_ip(candidates, None, None, policy, None);
+        let mut current = first;
+        for round in 0 .. 10 {
+            current = select_ip(if round % 2 == 0 {
+                candidates
+            } else {
+                reversed
+            }, None, None, policy, current);
+            assert_eq!(current, first);
+        }
+    }
+
+    // Kept over an address that's equally preferred by the policy, even one the tie
+    // break would pick
+    assert_eq!(
+        select_ip(candidates, None, None, AddressSelect::LongestLifetime, Some(ip("2001:db8:2::5"))),
+        Some(ip("2001:db8:2::5"))
+    );
+
+    // Switches to a strictly preferred address
+    let candidates = [addr("2001:db8:1::5", 600, 0), addr("2001:db8:2::5", 86400, 0)];
+    assert_eq!(
+        select_ip(candidates, None, None, AddressSelect::LongestLifetime, Some(ip("2001:db8:1::5"))),
+        Some(ip("2001:db8:2::5"))
+    );
+
+    // Or when it's gone or out of the prefix
+    let candidates = [addr("2001:db8:2::5", 600, 0), addr("2001:db8:3::5", 600, 0)];
+    assert_eq!(
+        select_ip(candidates, None, None, AddressSelect::LongestLifetime, Some(ip("2001:db8:1::5"))),
+        Some(ip("2001:db8:2::5"))
+    );
+    let prefix = Ipv6Prefix::from_str("2001:db8:3::/48").unwrap();
+    assert_eq!(
+        select_ip(candidates, Some(&prefix), None, AddressSelect::LongestLifetime, Some(ip("2001:db8:2::5"))),
+        Some(ip("2001:db8:3::5"))
+    );
+}
+
+#[test]
+fn test_select_ip_sticky_lifetime() {
+    // Two addresses refreshed at different times, which one has the longer lifetime
+    // alternates
+    let mut current = None;
+    for round in 0 .. 10 {
+        let (a, b) = if round % 2 == 0 {
+            (86400, 86000)
+        } else {
+            (86000, 86400)
+        };
+        let candidates = [addr("2001:db8:1::5", a, 0), addr("2001:db8:2::5", b, 0)];
+        current = select_ip(candidates, None, None, AddressSelect::LongestLifetime, current);
+        assert_eq!(current, Some(ip("2001:db8:1::5")));
+    }
+
+    // But not when the current one is running out
+    let candidates = [addr("2001:db8:1::5", 7200, 0), addr("2001:db8:2::5", 86400, 0)];
+    assert_eq!(
+        select_ip(candidates, None, None, AddressSelect::LongestLifetime, current),
+        Some(ip("2001:db8:2::5"))
+    );
+}
+
+fn flagged(s: &str, flags: u32) -> AddrInfo {
+    return AddrInfo {
+        flags,
+        ..addr(s, 600, 0)
+    };
+}
+
+#[test]
+fn test_select_ip_temporary() {
+    // The lowest is a rotating privacy address
+    let candidates = [flagged("2001:db8:1::5", IFA_F_TEMPORARY), flagged("2001:db8:1::9", 0)];
+    assert_eq!(select_ip(candidates, None, None, AddressSelect::Lowest, None), Some(ip("2001:db8:1::9")));
+
+    // Even over the current address
+    assert_eq!(
+        select_ip(candidates, None, None, AddressSelect::Lowest, Some(ip("2001:db8:1::5"))),
+        Some(ip("2001:db8:1::9"))
+    );
+
+    // But better than nothing
+    let candidates = [flagged("2001:db8:1::5", IFA_F_TEMPORARY)];
+    assert_eq!(select_ip(candidates, None, None, AddressSelect::Lowest, None), Some(ip("2001:db8:1::5")));
+}
+
+#[test]
+fn test_select_ip_deprecated() {
+    let candidates = [flagged("2001:db8:1::5", IFA_F_DEPRECATED), flagged("2001:db8:1::9", IFA_F_TEMPORARY)];
+    assert_eq!(select_ip(candidates, None, None, AddressSelect::Lowest, None), Some(ip("2001:db8:1::9")));
+    let candidates = [flagged("2001:db8:1::5", IFA_F_DEPRECATED)];
+    assert_eq!(select_ip(candidates, None, None, AddressSelect::Lowest, None), Some(ip("2001:db8:1::5")));
+}
+
+#[test]
+fn test_select_ip_ra_prefix() {
+    let candidates =
+        [flagged("2001:db8:1::5", 0), flagged("2001:db8:2::5", 0), flagged("2001:db8:3::5", IFA_F_TEMPORARY)];
+    let ra_prefix = Ipv6Prefix::from_str("2001:db8:2::/64").unwrap();
+    assert_eq!(
+        select_ip(candidates, None, Some(&ra_prefix), AddressSelect::Lowest, None),
+        Some(ip("2001:db8:2::5"))
+    );
+
+    // Stability still comes first
+    let ra_prefix = Ipv6Prefix::from_str("2001:db8:3::/64").unwrap();
+    assert_eq!(
+        select_ip(candidates, None, Some(&ra_prefix), AddressSelect::Lowest, None),
+        Some(ip("2001:db8:1::5"))
+    );
+}
+
+#[test]
+fn test_preference() {
+    let ra_prefix = Ipv6Prefix::from_str("2001:db8:1::/64").unwrap();
+    assert_eq!(preference(&flagged("2001:db8:1::5", 0), Some(&ra_prefix)), (true, true, true));
+    assert_eq!(preference(&flagged("2001:db8:1::5", 0), None), (true, true, false));
+    assert_eq!(preference(&flagged("2001:db8:2::5", IFA_F_TEMPORARY), Some(&ra_prefix)), (true, false, false));
+    assert_eq!(preference(&flagged("2001:db8:1::5", IFA_F_DEPRECATED), Some(&ra_prefix)), (false, true, true));
+}
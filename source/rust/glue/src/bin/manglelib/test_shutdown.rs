@@ -0,0 +1,62 @@
+use {
+    crate::manglelib::{
+        recv,
+        test_queue::MockPacket,
+        PacketQueue,
+    },
+    std::{
+        io,
+        sync::atomic::{
+            AtomicBool,
+            Ordering,
+        },
+        time::Duration,
+    },
+};
+
+/// A queue that never has messages, and signals shutdown after a few waits.
+struct IdleQueue<'a> {
+    stop: &'a AtomicBool,
+    waits: usize,
+}
+
+impl<'a> PacketQueue for IdleQueue<'a> {
+    type Message = MockPacket;
+
+    fn try_recv(&mut self) -> io::Result<Option<MockPacket>> {
+        return Ok(None);
+    }
+
+    fn wait(&mut self, _timeout: Duration) {
+        self.waits += 1;
+        if self.waits == 3 {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn verdict(&mut self, _message: MockPacket) -> io::Result<()> {
+        unreachable!();
+    }
+}
+
+#[test]
+fn test_stop_while_waiting() {
+    let stop = AtomicBool::new(false);
+    let mut queue = IdleQueue {
+        stop: &stop,
+        waits: 0,
+    };
+    assert_eq!(recv(&mut queue, &stop, || { }).unwrap(), None);
+    assert_eq!(queue.waits, 3);
+}
+
+#[test]
+fn test_stop_before_recv() {
+    let stop = AtomicBool::new(true);
+    let mut queue = IdleQueue {
+        stop: &stop,
+        waits: 0,
+    };
+    assert_eq!(recv(&mut queue, &stop, || { }).unwrap(), None);
+    assert_eq!(queue.waits, 0);
+}
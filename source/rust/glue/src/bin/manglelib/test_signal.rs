@@ -0,0 +1,23 @@
+use {
+    crate::manglelib::spawn_signal_handler,
+    std::{
+        sync::mpsc::channel,
+        time::Duration,
+    },
+};
+
+#[test]
+fn test_signal_handler() {
+    let (tx, rx) = channel();
+    spawn_signal_handler(libc::SIGUSR2, move || {
+        _ = tx.send(());
+    }).unwrap();
+    unsafe {
+        libc::raise(libc::SIGUSR2);
+    }
+    rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    unsafe {
+        libc::raise(libc::SIGUSR2);
+    }
+    rx.recv_timeout(Duration::from_secs(5)).unwrap();
+}
@@ -0,0 +1,79 @@
+use {
+    crate::manglelib::{
+        events::{
+            EventLog,
+            LogFormat,
+            LogLevel,
+            LogSink,
+        },
+        health::Health,
+        startup::{
+            StartupTimeoutAction,
+            StartupWatch,
+        },
+    },
+    std::{
+        sync::{
+            atomic::{
+                AtomicBool,
+                Ordering,
+            },
+            Arc,
+        },
+        thread::sleep,
+        time::Duration,
+    },
+};
+
+fn startup_watch(action: StartupTimeoutAction) -> StartupWatch {
+    return StartupWatch {
+        log: EventLog::new(LogFormat::Text, LogLevel::Warn, LogSink::Stderr),
+        stop: Arc::new(AtomicBool::new(false)),
+        health: Arc::new(Health::default()),
+        timeout: Duration::from_millis(20),
+        action,
+        timed_out: Arc::new(AtomicBool::new(false)),
+        passthrough: Arc::new(AtomicBool::new(false)),
+        check_period: Duration::from_millis(1),
+    };
+}
+
+#[test]
+fn test_startup_found_ip() {
+    let watch = startup_watch(StartupTimeoutAction::Exit);
+    watch.health.have_ip.store(true, Ordering::Relaxed);
+    watch.clone().spawn().join().unwrap();
+    assert!(!watch.stop.load(Ordering::Relaxed));
+    assert!(!watch.timed_out.load(Ordering::Relaxed));
+}
+
+#[test]
+fn test_startup_timeout_exit() {
+    let watch = startup_watch(StartupTimeoutAction::Exit);
+    watch.clone().spawn().join().unwrap();
+    assert!(watch.stop.load(Ordering::Relaxed));
+    assert!(watch.timed_out.load(Ordering::Relaxed));
+}
+
+#[test]
+fn test_startup_timeout_continue() {
+    let watch = startup_watch(StartupTimeoutAction::Continue);
+    watch.clone().spawn().join().unwrap();
+    assert!(!watch.stop.load(Ordering::Relaxed));
+    assert!(!watch.passthrough.load(Ordering::Relaxed));
+}
+
+#[test]
+fn test_startup_timeout_passthrough() {
+    let watch = startup_watch(StartupTimeoutAction::Passthrough);
+    let handle = watch.clone().spawn();
+    while !watch.passthrough.load(Ordering::Relaxed) {
+        sleep(Duration::from_millis(1));
+    }
+
+    // Rewriting again once there's an IP
+    watch.health.have_ip.store(true, Ordering::Relaxed);
+    handle.join().unwrap();
+    assert!(!watch.passthrough.load(Ordering::Relaxed));
+    assert!(!watch.stop.load(Ordering::Relaxed));
+}
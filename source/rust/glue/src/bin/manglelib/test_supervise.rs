@@ -0,0 +1,82 @@
+use {
+    crate::manglelib::{
+        events::{
+            EventLog,
+            LogFormat,
+            LogLevel,
+            LogSink,
+        },
+        supervise::{
+            Supervisor,
+            WatcherRestart,
+        },
+    },
+    std::{
+        sync::{
+            atomic::{
+                AtomicBool,
+                AtomicUsize,
+                Ordering,
+            },
+            Arc,
+        },
+        time::Duration,
+    },
+};
+
+fn supervisor(policy: WatcherRestart) -> Supervisor {
+    return Supervisor {
+        log: EventLog::new(LogFormat::Text, LogLevel::Warn, LogSink::Stderr),
+        stop: Arc::new(AtomicBool::new(false)),
+        died: Arc::new(AtomicBool::new(false)),
+        policy,
+        respawn_delay: Duration::from_millis(1),
+    };
+}
+
+#[test]
+fn test_supervise_respawn() {
+    let supervisor = supervisor(WatcherRestart::Respawn);
+    let runs = Arc::new(AtomicUsize::new(0));
+    supervisor.spawn("eth0".to_string(), {
+        let runs = runs.clone();
+        let stop = supervisor.stop.clone();
+        move || {
+            match runs.fetch_add(1, Ordering::Relaxed) {
+                0 => panic!("watcher bug"),
+                1 => { },
+                _ => stop.store(true, Ordering::Relaxed),
+            }
+        }
+    }).join().unwrap();
+
+    // Panicked, returned early, then stopped normally
+    assert_eq!(runs.load(Ordering::Relaxed), 3);
+    assert!(!supervisor.died.load(Ordering::Relaxed));
+}
+
+#[test]
+fn test_supervise_exit() {
+    let supervisor = supervisor(WatcherRestart::Exit);
+    let runs = Arc::new(AtomicUsize::new(0));
+    supervisor.spawn("eth0".to_string(), {
+        let runs = runs.clone();
+        move || {
+            runs.fetch_add(1, Ordering::Relaxed);
+            panic!("watcher bug");
+        }
+    }).join().unwrap();
+    assert_eq!(runs.load(Ordering::Relaxed), 1);
+    assert!(supervisor.stop.load(Ordering::Relaxed));
+    assert!(supervisor.died.load(Ordering::Relaxed));
+}
+
+#[test]
+fn test_supervise_stopping() {
+    let supervisor = supervisor(WatcherRestart::Exit);
+    let stop = supervisor.stop.clone();
+    supervisor.spawn("eth0".to_string(), move || stop.store(true, Ordering::Relaxed)).join().unwrap();
+
+    // Returning because of `stop` isn't dying
+    assert!(!supervisor.died.load(Ordering::Relaxed));
+}
@@ -0,0 +1,9 @@
+use crate::version;
+
+#[test]
+fn test_version() {
+    let version = version();
+    assert!(version.starts_with(&format!("mangle_ip_configure {} (commit ", env!("CARGO_PKG_VERSION"))));
+    assert!(!version.contains("commit ,"));
+    assert!(version.ends_with(')'));
+}
@@ -1,3 +1,12 @@
+//! Without the `std` feature only `ra_dns` is built, with `no_std` + `alloc`.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
 pub mod command;
+#[cfg(feature = "std")]
 pub mod admin;
+#[cfg(feature = "std")]
 pub mod unstable_ip;
+pub mod ra_dns;
@@ -0,0 +1,164 @@
+//! IPv4 DHCP (for legacy clients), only the DNS option is rewritten.
+//!
+//! * https://datatracker.ietf.org/doc/html/rfc2131
+//!
+//! * https://datatracker.ietf.org/doc/html/rfc2132
+use {
+    super::{
+        checksum_finish,
+        checksum_roll,
+        ModifyConfig,
+        ModifyError,
+    },
+    alloc::vec::Vec,
+};
+
+const IPV4_MIN_HEADER_SIZE: usize = 20;
+const UDP_FIXED_HEADER_SIZE: usize = 8;
+const BOOTP_FIXED_SIZE: usize = 236;
+const MAGIC_COOKIE: &[u8] = &[99, 130, 83, 99];
+const PORT_SERVER: u16 = 67;
+const OPT_PAD: u8 = 0;
+const OPT_DNS: u8 = 6;
+const OPT_MESSAGE_TYPE: u8 = 53;
+const OPT_END: u8 = 255;
+pub const DHCP4_OFFER: u8 = 2;
+pub const DHCP4_ACK: u8 = 5;
+
+fn read_u16(packet: &[u8], start: usize) -> Option<u16> {
+    return Some(u16::from_be_bytes(packet.get(start .. start + 2)?.try_into().unwrap()));
+}
+
+/// Calculate the UDP checksum of an IPv4 packet. The checksum field in the packet
+/// must be zeroed first.
+fn ipv4_udp_checksum(packet: &[u8], udp_start: usize) -> Option<[u8; 2]> {
+    let udp = packet.get(udp_start..)?;
+    let mut sum64 = 0u64;
+
+    // Source addr, dest addr (pseudo header)
+    checksum_roll(&mut sum64, packet.get(12 .. 20)?);
+
+    // Protocol (pseudo header)
+    sum64 += u16::from_ne_bytes([0x00, 17]) as u64;
+
+    // UDP length (pseudo header)
+    checksum_roll(&mut sum64, &(udp.len() as u16).to_be_bytes());
+
+    // Payload
+    checksum_roll(&mut sum64, udp);
+    return Some(checksum_finish(sum64));
+}
+
+/// Rewrite the DNS option in an IPv4 DHCPOFFER/DHCPACK (starting at the IPv4 header)
+/// with `config.dns_ips_v4`. Options overloaded into `sname`/`file` aren't
+/// touched.
+///
+/// Other DHCP messages, or ones without a DNS option, are written to `out`
+/// unchanged.
+pub(super) fn modify_into(out: &mut Vec<u8>, source: &[u8], config: &ModifyConfig) -> Result<(), ModifyError> {
+    if config.dns_ips_v4.is_empty() {
+        return Err(ModifyError::NotApplicable);
+    }
+
+    // IPv4 https://datatracker.ietf.org/doc/html/rfc791#section-3.1
+    let header_size = (*source.first().ok_or(ModifyError::Truncated)? & 0x0f) as usize * 4;
+    if header_size < IPV4_MIN_HEADER_SIZE {
+        return Err(ModifyError::Truncated);
+    }
+    let protocol = *source.get(9).ok_or(ModifyError::Truncated)?;
+    if protocol != 17 {
+        return Err(ModifyError::UnsupportedNextHeader(protocol));
+    }
+
+    // Only unfragmented packets (more fragments flag, fragment offset)
+    if read_u16(source, 6).ok_or(ModifyError::Truncated)? & 0x3fff != 0 {
+        return Err(ModifyError::NotApplicable);
+    }
+    let total_len = read_u16(source, 2).ok_or(ModifyError::Truncated)? as usize;
+    let source = source.get(.. total_len).ok_or(ModifyError::Truncated)?;
+
+    // UDP from the server port
+    let udp_start = header_size;
+    if read_u16(source, udp_start).ok_or(ModifyError::Truncated)? != PORT_SERVER {
+        return Err(ModifyError::NotApplicable);
+    }
+
+    // BOOTP + DHCP
+    let cookie_start = udp_start + UDP_FIXED_HEADER_SIZE + BOOTP_FIXED_SIZE;
+    if source.get(cookie_start .. cookie_start + MAGIC_COOKIE.len()).ok_or(ModifyError::Truncated)? != MAGIC_COOKIE {
+        return Err(ModifyError::NotApplicable);
+    }
+    let options_start = cookie_start + MAGIC_COOKIE.len();
+
+    // Copy + filter out options
+    out.reserve(source.len() + 64);
+    out.extend_from_slice(&source[.. options_start]);
+    let mut message_type = None;
+    let mut found_dns = false;
+    let mut at = options_start;
+    let trailer = loop {
+        let code = *source.get(at).ok_or(ModifyError::BadOptionLength)?;
+        match code {
+            OPT_PAD => {
+                out.push(code);
+                at += 1;
+            },
+            OPT_END => {
+                break &source[at + 1..];
+            },
+            _ => {
+                let length = *source.get(at + 1).ok_or(ModifyError::BadOptionLength)? as usize + 2;
+                let option = source.get(at .. at + length).ok_or(ModifyError::BadOptionLength)?;
+                if code == OPT_MESSAGE_TYPE {
+                    message_type = Some(*option.get(2).ok_or(ModifyError::BadOptionLength)?);
+                }
+                if code == OPT_DNS {
+                    found_dns = true;
+                } else {
+                    out.extend_from_slice(option);
+                }
+                at += length;
+            },
+        }
+    };
+    if !matches!(message_type, Some(DHCP4_OFFER) | Some(DHCP4_ACK)) || !found_dns {
+        out.clear();
+        out.extend_from_slice(source);
+        return Ok(());
+    }
+
+    // Generate custom DNS option
+    let dns_length = u8::try_from(4 * config.dns_ips_v4.len()).map_err(|_| ModifyError::BadOptionLength)?;
+    out.push(OPT_DNS);
+    out.push(dns_length);
+    for ip in &config.dns_ips_v4 {
+        out.extend(ip.octets());
+    }
+    out.push(OPT_END);
+
+    // Keep any padding after the end
+    out.extend_from_slice(trailer);
+
+    // Update total length, recalc header checksum
+    let total_length = u16::try_from(out.len()).map_err(|_| ModifyError::BadOptionLength)?;
+    out[2 .. 4].copy_from_slice(&total_length.to_be_bytes());
+    out[10 .. 12].fill(0);
+    let mut sum64 = 0u64;
+    checksum_roll(&mut sum64, &out[.. header_size]);
+    out[10 .. 12].copy_from_slice(&checksum_finish(sum64));
+
+    // Update UDP length, recalc checksum unless disabled (zero)
+    let udp_length = (out.len() - udp_start) as u16;
+    out[udp_start + 4 .. udp_start + 6].copy_from_slice(&udp_length.to_be_bytes());
+    if out[udp_start + 6 .. udp_start + 8] != [0, 0] {
+        out[udp_start + 6 .. udp_start + 8].fill(0);
+        let mut checksum = ipv4_udp_checksum(out, udp_start).ok_or(ModifyError::Truncated)?;
+
+        // Zero means no checksum, so send the other zero
+        if checksum == [0, 0] {
+            checksum = [0xff, 0xff];
+        }
+        out[udp_start + 6 .. udp_start + 8].copy_from_slice(&checksum);
+    }
+    return Ok(());
+}
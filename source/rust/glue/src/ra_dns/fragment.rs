@@ -0,0 +1,296 @@
+//! Reassembly of fragmented IPv6 packets, so fragmented RAs and DHCPv6 replies can
+//! be rewritten, and splitting them up again afterwards.
+//!
+//! * https://datatracker.ietf.org/doc/html/rfc8200#section-4.5
+use {
+    super::{
+        packet::{
+            Ipv6Packet,
+            IPV6_HEADER_SIZE,
+        },
+        ModifyError,
+    },
+    std::{
+        collections::HashMap,
+        net::Ipv6Addr,
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+};
+
+const NEXT_HEADER_FRAGMENT: u8 = 44;
+const FRAGMENT_HEADER_SIZE: usize = 8;
+
+/// The largest reassembled payload (IPv6 payload length is 16 bits).
+const MAX_PAYLOAD_SIZE: usize = u16::MAX as usize;
+
+/// Identifies the fragments of one datagram.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct FragmentKey {
+    pub src: Ipv6Addr,
+    pub dst: Ipv6Addr,
+    pub id: u32,
+}
+
+struct Fragment<'a> {
+    key: FragmentKey,
+    /// The IPv6 header and extension headers before the fragment header.
+    unfragmentable: &'a [u8],
+    /// Offset of the next header field pointing to the fragment header.
+    next_header_at: usize,
+    /// From the fragment header.
+    next_header: u8,
+    offset: usize,
+    more: bool,
+    data: &'a [u8],
+}
+
+fn parse_fragment(packet: &[u8]) -> Result<Option<Fragment<'_>>, ModifyError> {
+    let Some(ipv6_packet) = Ipv6Packet::new(packet) else {
+        return Ok(None);
+    };
+    let Some((fragment_at, next_header_at)) = ipv6_packet.fragment_header()? else {
+        return Ok(None);
+    };
+
+    // Offset (13 bits, in 8 byte units), 2 reserved bits, more fragments flag
+    let offset_more = ipv6_packet.payload_u16(fragment_at + 2).ok_or(ModifyError::Truncated)?;
+    if offset_more & 0xFFF9 == 0 {
+        // Atomic fragment, `modify` handles it directly
+        return Ok(None);
+    }
+    let data_start = IPV6_HEADER_SIZE + fragment_at + FRAGMENT_HEADER_SIZE;
+    let data_end = IPV6_HEADER_SIZE + ipv6_packet.payload_len() as usize;
+    return Ok(Some(Fragment {
+        key: FragmentKey {
+            src: ipv6_packet.src(),
+            dst: ipv6_packet.dst(),
+            id: ipv6_packet.payload_u32(fragment_at + 4).ok_or(ModifyError::Truncated)?,
+        },
+        unfragmentable: &packet[.. IPV6_HEADER_SIZE + fragment_at],
+        next_header_at,
+        next_header: ipv6_packet.payload_u8(fragment_at).ok_or(ModifyError::Truncated)?,
+        offset: (offset_more & 0xFFF8) as usize,
+        more: offset_more & 1 != 0,
+        data: packet.get(data_start .. data_end).ok_or(ModifyError::Truncated)?,
+    }));
+}
+
+/// True if `packet` is an IPv6 fragment that needs reassembly before it can be
+/// rewritten.
+pub fn is_fragment(packet: &[u8]) -> bool {
+    return matches!(parse_fragment(packet), Ok(Some(_)));
+}
+
+/// A reassembled packet, which can be rewritten (replace `packet`) then split into
+/// fragments again.
+#[derive(Debug)]
+pub struct Datagram {
+    /// The whole packet, without the fragment header.
+    pub packet: Vec<u8>,
+    id: u32,
+    unfragmentable_len: usize,
+    next_header_at: usize,
+    max_fragment_size: usize,
+}
+
+impl Datagram {
+    /// Split `packet` into fragments no larger than the largest of the original
+    /// fragments (so they fit the same path MTU), with the original identification.
+    pub fn fragment(&self) -> Result<Vec<Vec<u8>>, ModifyError> {
+        let unfragmentable = self.packet.get(.. self.unfragmentable_len).ok_or(ModifyError::Truncated)?;
+        let next_header = unfragmentable[self.next_header_at];
+        let data = &self.packet[self.unfragmentable_len..];
+        let chunk_size =
+            self.max_fragment_size.saturating_sub(self.unfragmentable_len + FRAGMENT_HEADER_SIZE) / 8 * 8;
+        if chunk_size == 0 {
+            return Err(ModifyError::BadFragments);
+        }
+        let mut out = vec![];
+        for (i, chunk) in data.chunks(chunk_size).enumerate() {
+            let offset = i * chunk_size;
+            let more = offset + chunk.len() < data.len();
+            let mut fragment = Vec::with_capacity(self.unfragmentable_len + FRAGMENT_HEADER_SIZE + chunk.len());
+            fragment.extend_from_slice(unfragmentable);
+            fragment[self.next_header_at] = NEXT_HEADER_FRAGMENT;
+            fragment.extend([next_header, 0]);
+            fragment.extend((offset as u16 | more as u16).to_be_bytes());
+            fragment.extend(self.id.to_be_bytes());
+            fragment.extend_from_slice(chunk);
+            let payload_len = fragment.len() - IPV6_HEADER_SIZE;
+            Ipv6Packet::new(&mut fragment[..]).unwrap().set_payload_len(payload_len as u16);
+            out.push(fragment);
+        }
+        return Ok(out);
+    }
+}
+
+/// The result of adding a fragment to a `Reassembler`.
+pub enum Reassembly<T> {
+    /// Waiting for more fragments.
+    Incomplete,
+    /// All fragments arrived. `items` are from `add`, in the order the fragments
+    /// arrived.
+    Complete {
+        datagram: Datagram,
+        items: Vec<T>,
+    },
+    /// The fragments can't be reassembled (ex: overlapping, too large, too many), the
+    /// datagram is discarded.
+    Failed {
+        error: ModifyError,
+        items: Vec<T>,
+    },
+}
+
+struct Pending<T> {
+    started: Instant,
+    /// From the first fragment (offset 0).
+    first: Option<(Vec<u8>, usize, u8)>,
+    /// Offset and data.
+    fragments: Vec<(usize, Vec<u8>)>,
+    /// Known once the last fragment arrives.
+    total: Option<usize>,
+    max_fragment_size: usize,
+    items: Vec<T>,
+}
+
+impl<T> Pending<T> {
+    fn add(&mut self, fragment: &Fragment, max_fragments: usize) -> Result<(), ModifyError> {
+        let end = fragment.offset + fragment.data.len();
+        if end > MAX_PAYLOAD_SIZE || self.fragments.len() >= max_fragments {
+            return Err(ModifyError::BadFragments);
+        }
+
+        // Only the last fragment can have a length that isn't a multiple of 8
+        if fragment.more && !fragment.data.len().is_multiple_of(8) {
+            return Err(ModifyError::BadFragments);
+        }
+        if !fragment.more {
+            if self.total.is_some() || self.fragments.iter().any(|(o, d)| o + d.len() > end) {
+                return Err(ModifyError::BadFragments);
+            }
+            self.total = Some(end);
+        } else if self.total.is_some_and(|total| end > total) {
+            return Err(ModifyError::BadFragments);
+        }
+
+        // Overlapping fragments discard the whole datagram (RFC 5722)
+        if self.fragments.iter().any(|(o, d)| fragment.offset < o + d.len() && *o < end) {
+            return Err(ModifyError::BadFragments);
+        }
+        if fragment.offset == 0 {
+            self.first = Some((fragment.unfragmentable.to_vec(), fragment.next_header_at, fragment.next_header));
+        }
+        self.fragments.push((fragment.offset, fragment.data.to_vec()));
+        return Ok(());
+    }
+}
+
+/// Collects fragments until a datagram is complete, holding an item (ex: the
+/// queued message) for each fragment. Bounded by the number of datagrams in
+/// progress and fragments per datagram, and incomplete datagrams time out.
+pub struct Reassembler<T> {
+    max_datagrams: usize,
+    max_fragments: usize,
+    timeout: Duration,
+    pending: HashMap<FragmentKey, Pending<T>>,
+}
+
+impl<T> Reassembler<T> {
+    pub fn new(max_datagrams: usize, max_fragments: usize, timeout: Duration) -> Reassembler<T> {
+        return Reassembler {
+            max_datagrams,
+            max_fragments,
+            timeout,
+            pending: HashMap::new(),
+        };
+    }
+
+    /// Discard datagrams that have been incomplete longer than the timeout, and the
+    /// oldest ones if at the limit so there's room for another, returning their items.
+    /// Call before `add`.
+    pub fn expire(&mut self, now: Instant) -> Vec<T> {
+        let mut out = vec![];
+        let expired =
+            self
+                .pending
+                .iter()
+                .filter(|(_, p)| now.duration_since(p.started) >= self.timeout)
+                .map(|(k, _)| *k)
+                .collect::<Vec<_>>();
+        for key in expired {
+            out.extend(self.pending.remove(&key).unwrap().items);
+        }
+        while self.pending.len() >= self.max_datagrams {
+            let oldest = *self.pending.iter().min_by_key(|(_, p)| p.started).unwrap().0;
+            out.extend(self.pending.remove(&oldest).unwrap().items);
+        }
+        return out;
+    }
+
+    /// Add a fragment (a whole IPv6 packet), with `item` to return when the datagram
+    /// is complete or discarded. Returns `Err` with `item` if `packet` isn't a
+    /// fragment.
+    pub fn add(&mut self, now: Instant, packet: &[u8], item: T) -> Result<Reassembly<T>, (ModifyError, T)> {
+        let fragment = match parse_fragment(packet) {
+            Ok(Some(f)) => f,
+            Ok(None) => return Err((ModifyError::NotApplicable, item)),
+            Err(e) => return Err((e, item)),
+        };
+        let pending = self.pending.entry(fragment.key).or_insert_with(|| Pending {
+            started: now,
+            first: None,
+            fragments: vec![],
+            total: None,
+            max_fragment_size: 0,
+            items: vec![],
+        });
+        pending.items.push(item);
+        pending.max_fragment_size = pending.max_fragment_size.max(packet.len());
+        if let Err(error) = pending.add(&fragment, self.max_fragments) {
+            let pending = self.pending.remove(&fragment.key).unwrap();
+            return Ok(Reassembly::Failed {
+                error,
+                items: pending.items,
+            });
+        }
+
+        // Complete once the data adds up, since fragments don't overlap
+        let Some(total) = pending.total else {
+            return Ok(Reassembly::Incomplete);
+        };
+        if pending.first.is_none() || pending.fragments.iter().map(|(_, d)| d.len()).sum::<usize>() != total {
+            return Ok(Reassembly::Incomplete);
+        }
+        let mut pending = self.pending.remove(&fragment.key).unwrap();
+        let (unfragmentable, next_header_at, next_header) = pending.first.take().unwrap();
+        let unfragmentable_len = unfragmentable.len();
+        if unfragmentable_len - IPV6_HEADER_SIZE + total > MAX_PAYLOAD_SIZE {
+            return Ok(Reassembly::Failed {
+                error: ModifyError::BadFragments,
+                items: pending.items,
+            });
+        }
+        let mut out = unfragmentable;
+        out[next_header_at] = next_header;
+        out.resize(unfragmentable_len + total, 0);
+        for (offset, data) in pending.fragments {
+            out[unfragmentable_len + offset .. unfragmentable_len + offset + data.len()].copy_from_slice(&data);
+        }
+        let payload_len = out.len() - IPV6_HEADER_SIZE;
+        Ipv6Packet::new(&mut out[..]).unwrap().set_payload_len(payload_len as u16);
+        return Ok(Reassembly::Complete {
+            datagram: Datagram {
+                packet: out,
+                id: fragment.key.id,
+                unfragmentable_len,
+                next_header_at,
+                max_fragment_size: pending.max_fragment_size,
+            },
+            items: pending.items,
+        });
+    }
+}
@@ -0,0 +1,1086 @@
+//! Rewriting of RA and DHCPv6 (and IPv4 DHCP) packets to inject DNS (and other)
+//! options.
+//!
+//! Only needs `core` and `alloc`, fragment reassembly needs the `std` feature.
+use {
+    alloc::{
+        string::String,
+        vec,
+        vec::Vec,
+    },
+    core::{
+        fmt::{
+            self,
+            Display,
+            Formatter,
+        },
+        net::{
+            Ipv4Addr,
+            Ipv6Addr,
+        },
+    },
+    flowcontrol::shed,
+    options::{
+        Dhcp6Options,
+        RaOptions,
+    },
+    packet::{
+        Ipv6Packet,
+        IPV6_HEADER_SIZE,
+    },
+};
+
+mod dhcp4;
+#[cfg(feature = "std")]
+pub mod fragment;
+pub mod options;
+pub mod packet;
+pub mod parse;
+pub use dhcp4::{
+    DHCP4_ACK,
+    DHCP4_OFFER,
+};
+
+#[cfg(test)]
+mod test_modify_dhcp_ex1;
+#[cfg(test)]
+mod test_modify_dhcp_advertise;
+#[cfg(test)]
+mod test_modify_dhcp_relay;
+#[cfg(test)]
+mod test_modify_dhcp4;
+#[cfg(test)]
+mod test_modify_version;
+#[cfg(all(test, feature = "std"))]
+mod test_fragment;
+#[cfg(test)]
+mod test_checksum;
+#[cfg(test)]
+mod test_checksum_incremental;
+#[cfg(test)]
+mod test_checksum_prop;
+#[cfg(test)]
+mod test_checksum_pnet;
+#[cfg(test)]
+mod test_ra_modify_mtu;
+#[cfg(test)]
+mod test_ra_inject_mtu;
+#[cfg(test)]
+mod test_ra_bare;
+#[cfg(test)]
+mod test_ra_flags;
+#[cfg(test)]
+mod test_modify_ra_ex1;
+#[cfg(test)]
+mod test_ra_dnssl;
+#[cfg(test)]
+mod test_ra_lifetime;
+#[cfg(test)]
+mod test_ra_ext_headers;
+#[cfg(test)]
+mod test_packet;
+#[cfg(test)]
+mod test_parse;
+#[cfg(test)]
+mod test_ra_options;
+#[cfg(test)]
+mod test_ra_option_order;
+#[cfg(test)]
+mod test_ra_pref64;
+#[cfg(test)]
+mod test_ra_rdnss;
+#[cfg(test)]
+mod test_dhcp6_options;
+#[cfg(test)]
+mod test_dhcp6_dns;
+#[cfg(test)]
+mod test_verify_checksum;
+#[cfg(test)]
+mod test_ra_consolidate_rdnss;
+#[cfg(test)]
+mod test_max_packet;
+#[cfg(test)]
+mod test_truncated;
+#[cfg(test)]
+mod test_util;
+
+/// Add `bytes` (as 16-bit words, the last padded with zero if odd) into the running
+/// one's complement sum `sum64`. Start with 0 and finish with `checksum_finish`.
+#[inline]
+pub fn checksum_roll(sum64: &mut u64, bytes: &[u8]) {
+    // Accumulate in u64 and fold once in `checksum_finish` - a u32 would overflow after
+    // ~64k words (128KiB, possible with jumbograms)
+    let mut iter = bytes.chunks_exact(2);
+    for x in &mut iter {
+        *sum64 += u16::from_ne_bytes(x.try_into().unwrap()) as u64;
+    }
+    if let Some(remainder) = iter.remainder().first() {
+        let pair = [*remainder, 0x00];
+        *sum64 += u16::from_ne_bytes(pair) as u64;
+    }
+}
+
+/// Fold carries back in until there are none left (adding the carry can itself
+/// carry).
+fn checksum_fold(sum64: u64) -> u16 {
+    let mut sum64 = sum64;
+    while (sum64 >> 16) != 0 {
+        sum64 = (sum64 & 0xFFFF) + (sum64 >> 16);
+    }
+    return sum64 as u16;
+}
+
+/// Fold and complement a sum from `checksum_roll` into the checksum bytes, in
+/// network order.
+pub fn checksum_finish(sum64: u64) -> [u8; 2] {
+    return (!checksum_fold(sum64).to_be()).to_be_bytes();
+}
+
+/// Changes made to the checksummed part of a packet, to update its checksum rather
+/// than recompute it over the whole packet (RFC 1624). Offsets are from the start
+/// of the ICMPv6/UDP header (or anywhere even, for the pseudo header), only whether
+/// they're odd matters.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc1624
+#[derive(Default)]
+struct ChecksumUpdate {
+    sum64: u64,
+    /// Kept bytes moved by an odd number of bytes, which swaps their contribution to
+    /// the sum, so the checksum needs to be recomputed.
+    misaligned: bool,
+}
+
+impl ChecksumUpdate {
+    fn sum(offset: usize, bytes: &[u8]) -> u16 {
+        let mut sum64 = 0;
+        checksum_roll(&mut sum64, bytes);
+        let sum = checksum_fold(sum64);
+        if offset % 2 == 1 {
+            return sum.swap_bytes();
+        }
+        return sum;
+    }
+
+    fn remove(&mut self, offset: usize, bytes: &[u8]) {
+        self.sum64 += !ChecksumUpdate::sum(offset, bytes) as u64;
+    }
+
+    fn add(&mut self, offset: usize, bytes: &[u8]) {
+        self.sum64 += ChecksumUpdate::sum(offset, bytes) as u64;
+    }
+
+    fn replace(&mut self, offset: usize, old: &[u8], new: &[u8]) {
+        self.remove(offset, old);
+        self.add(offset, new);
+    }
+
+    /// Bytes that were kept but are now at `new_offset`.
+    fn moved(&mut self, old_offset: usize, new_offset: usize) {
+        if (old_offset ^ new_offset) & 1 != 0 {
+            self.misaligned = true;
+        }
+    }
+
+    /// The updated checksum, or `None` if it needs to be recomputed. A zero checksum
+    /// (UDP without a checksum) is recomputed.
+    fn apply(&self, checksum: [u8; 2]) -> Option<[u8; 2]> {
+        if self.misaligned || checksum == [0, 0] {
+            return None;
+        }
+        return Some(checksum_finish(!u16::from_ne_bytes(checksum) as u64 + self.sum64));
+    }
+}
+
+/// Set the checksum of the rewritten IPv6 packet `out` (at `checksum_at` from the
+/// start of the ICMPv6/UDP header), updating the original `checksum` with `update`
+/// if possible.
+fn set_checksum(
+    out: &mut [u8],
+    upper_start: usize,
+    checksum_at: usize,
+    checksum: [u8; 2],
+    update: &ChecksumUpdate,
+) -> Result<(), ModifyError> {
+    let mut ipv6_packet = Ipv6Packet::new(out).ok_or(ModifyError::Truncated)?;
+    let new_checksum = match update.apply(checksum) {
+        Some(c) => c,
+        None => {
+            ipv6_packet.set_payload_u16(upper_start + checksum_at, 0).ok_or(ModifyError::Truncated)?;
+            icmpv6_udp_checksum(ipv6_packet.as_bytes()).ok_or(ModifyError::Truncated)?
+        },
+    };
+    ipv6_packet
+        .set_payload_u16(upper_start + checksum_at, u16::from_be_bytes(new_checksum))
+        .ok_or(ModifyError::Truncated)?;
+    return Ok(());
+}
+
+/// If `config.verify_checksum`, confirm the ICMPv6 or UDP checksum of the IPv6 packet
+/// `source` is correct.
+fn verify_checksum(source: &[u8], config: &ModifyConfig) -> Result<(), ModifyError> {
+    if !config.verify_checksum {
+        return Ok(());
+    }
+
+    // Summing with the carried checksum in place is the same as zeroing it and
+    // comparing, without copying the packet.  A zero UDP checksum (none) isn't allowed
+    // in IPv6 so also fails.
+    if icmpv6_udp_checksum(source).ok_or(ModifyError::Truncated)? != [0, 0] {
+        return Err(ModifyError::BadChecksum);
+    }
+    return Ok(());
+}
+
+/// Calculate the ICMPv6 or UDP checksum of a full IPv6 packet (starting at the IPv6
+/// header), finding the upper layer header after any extension headers. The checksum
+/// field in the packet must be zeroed first. Returns `None` if the packet is
+/// truncated.
+pub fn icmpv6_udp_checksum(source: &[u8]) -> Option<[u8; 2]> {
+    let packet = Ipv6Packet::new_any_version(source)?;
+    let (next_header, upper_start) = packet.upper_layer().ok()?;
+    let upper_len = packet.payload().len().checked_sub(upper_start)?;
+    return pseudo_header_checksum(source, next_header, upper_start, upper_len);
+}
+
+/// Calculate the checksum of the `next_header` upper layer packet (ex: ICMPv6, UDP)
+/// at `upper_start` (relative to the IPv6 payload, so after any extension headers)
+/// and `upper_len` bytes long in a full IPv6 packet. The checksum field must be
+/// zeroed first. Returns `None` if the packet is truncated.
+pub fn pseudo_header_checksum(source: &[u8], next_header: u8, upper_start: usize, upper_len: usize) -> Option<[u8; 2]> {
+    // * IPv6 pseudo-header https://datatracker.ietf.org/doc/html/rfc2460#section-8.1
+    //
+    // * ICMP https://datatracker.ietf.org/doc/html/rfc4443#section-2.3
+    //
+    //   Pseudo header + whole body
+    //
+    // * UDP https://datatracker.ietf.org/doc/html/rfc768
+    //
+    //   Pseudo header + whole body
+    //
+    // The pseudo header has the upper-layer length and protocol, excluding any
+    // extension headers, so neither comes from the IPv6 header.
+    let packet = Ipv6Packet::new_any_version(source)?;
+    let upper = packet.payload().get(upper_start .. upper_start.checked_add(upper_len)?)?;
+    let mut sum64 = 0u64;
+
+    // Upper layer length (pseudo header)
+    checksum_roll(&mut sum64, &(upper.len() as u32).to_be_bytes());
+
+    // Next header (pseudo header)
+    sum64 += u16::from_ne_bytes([0x00, next_header]) as u64;
+
+    // Source addr (pseudo header), dest addr (pseudo header)
+    checksum_roll(&mut sum64, &packet.src().octets());
+    checksum_roll(&mut sum64, &packet.dst().octets());
+
+    // Payload
+    checksum_roll(&mut sum64, upper);
+
+    // Then do some rfc magic
+    return Some(checksum_finish(sum64));
+}
+
+const NEXT_HEADER_ICMPV6: u8 = 58;
+const NEXT_HEADER_UDP: u8 = 17;
+const ICMPV6_TYPE_RA: u8 = 134;
+const RA_FIXED_HEADER_SIZE: usize = 16;
+const OPT_PREFIX_INFORMATION: u8 = 3;
+const OPT_RDNSS: u8 = 25;
+const OPT_PREF64: u8 = 38;
+const OPT_DHCP6_DNS: u16 = 0x17;
+const UDP_FIXED_HEADER_SIZE: usize = 8;
+const DHCP_FIXED_HEADER_SIZE: usize = 4;
+/// Message type, hop count, link address, peer address.
+const DHCP_RELAY_HEADER_SIZE: usize = 34;
+const OPT_DHCP6_RELAY_MSG: u16 = 9;
+/// Relays nested deeper than this are passed through (RFC 8415 HOP_COUNT_LIMIT).
+const DHCP_HOP_COUNT_LIMIT: u8 = 8;
+
+/// Whether the UDP datagram at `upper_start` is between `config.dhcp_ports`, so
+/// DHCPv6 and not some other UDP that ended up in the queue.  `None` if truncated.
+fn is_dhcp6<B: AsRef<[u8]>>(packet: &Ipv6Packet<B>, upper_start: usize, config: &ModifyConfig) -> Option<bool> {
+    let dhcp_ports = config.dhcp_ports.as_deref().unwrap_or(DEFAULT_DHCP_PORTS);
+    for port_at in [0, 2] {
+        if !dhcp_ports.contains(&packet.payload_u16(upper_start + port_at)?) {
+            return Some(false);
+        }
+    }
+    return Some(true);
+}
+
+/// List the option types in an RA (ICMPv6 option types) or DHCPv6 message (option
+/// codes), in order.  Returns `None` for other packets (including UDP not between
+/// `config.dhcp_ports`) or if the options can't be parsed.
+pub fn list_options(packet: &[u8], config: &ModifyConfig) -> Option<Vec<u16>> {
+    let packet = Ipv6Packet::new(packet)?;
+    let (next_header, upper_start) = packet.upper_layer().ok()?;
+    let mut out = vec![];
+    match next_header {
+        NEXT_HEADER_ICMPV6 => {
+            if packet.payload_u8(upper_start)? != ICMPV6_TYPE_RA {
+                return None;
+            }
+            for option in RaOptions::new(packet.payload().get(upper_start + RA_FIXED_HEADER_SIZE..)?) {
+                let (type_, _) = option?;
+                out.push(type_ as u16);
+            }
+        },
+        NEXT_HEADER_UDP => {
+            if !is_dhcp6(&packet, upper_start, config)? {
+                return None;
+            }
+            let dhcp_options_start = upper_start + UDP_FIXED_HEADER_SIZE + DHCP_FIXED_HEADER_SIZE;
+            for option in Dhcp6Options::new(packet.payload().get(dhcp_options_start..)?) {
+                let (code, _) = option?;
+                out.push(code);
+            }
+        },
+        _ => {
+            return None;
+        },
+    }
+    return Some(out);
+}
+
+/// The kind of IPv6 message `modify` rewrites, see `message_kind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    Ra,
+    /// With the DHCPv6 message type.
+    Dhcp6(u8),
+}
+
+/// Which kind of message `packet` is, or `None` for other packets (including UDP not
+/// between `config.dhcp_ports`).
+pub fn message_kind(packet: &[u8], config: &ModifyConfig) -> Option<MessageKind> {
+    let packet = Ipv6Packet::new(packet)?;
+    let (next_header, upper_start) = packet.upper_layer().ok()?;
+    match next_header {
+        NEXT_HEADER_ICMPV6 if packet.payload_u8(upper_start)? == ICMPV6_TYPE_RA => return Some(MessageKind::Ra),
+        NEXT_HEADER_UDP if is_dhcp6(&packet, upper_start, config)? => {
+            return Some(MessageKind::Dhcp6(packet.payload_u8(upper_start + UDP_FIXED_HEADER_SIZE)?));
+        },
+        _ => return None,
+    }
+}
+
+/// The source address of an RA, or `None` if `packet` isn't an RA.
+pub fn ra_source(packet: &[u8]) -> Option<Ipv6Addr> {
+    let packet = Ipv6Packet::new(packet)?;
+    let (next_header, upper_start) = packet.upper_layer().ok()?;
+    if next_header != NEXT_HEADER_ICMPV6 || packet.payload_u8(upper_start)? != ICMPV6_TYPE_RA {
+        return None;
+    }
+    return Some(packet.src());
+}
+
+/// The prefix (address, length) of the first Prefix Information option in an RA, or
+/// `None` if `packet` isn't an RA or has none.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc4861#section-4.6.2
+pub fn ra_prefix(packet: &[u8]) -> Option<(Ipv6Addr, u8)> {
+    let packet = Ipv6Packet::new(packet)?;
+    let (next_header, upper_start) = packet.upper_layer().ok()?;
+    if next_header != NEXT_HEADER_ICMPV6 || packet.payload_u8(upper_start)? != ICMPV6_TYPE_RA {
+        return None;
+    }
+    for option in RaOptions::new(packet.payload().get(upper_start + RA_FIXED_HEADER_SIZE..)?) {
+        let (type_, option) = option?;
+        if type_ == OPT_PREFIX_INFORMATION && option.len() >= 32 {
+            return Some((Ipv6Addr::from(<[u8; 16]>::try_from(&option[16 .. 32]).unwrap()), option[2]));
+        }
+    }
+    return None;
+}
+
+/// Why `modify` didn't produce a packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModifyError {
+    /// Not a packet `modify` rewrites (ex: not an RA, or DHCP from a client), safe to
+    /// pass through.
+    NotApplicable,
+    /// A header or field runs past the end of the packet.
+    Truncated,
+    /// The upper-layer protocol isn't supported, or the packet is a non-atomic
+    /// fragment (44).
+    UnsupportedNextHeader(u8),
+    /// An option's length is zero, too short for its contents, or runs past the end of
+    /// the packet.
+    BadOptionLength,
+    /// IPv6 fragments overlap, are misaligned, or exceed the maximum size or count.
+    BadFragments,
+    /// The ICMPv6/UDP checksum is wrong, with `verify_checksum`.
+    BadChecksum,
+    /// The rewritten packet would be larger than `max_packet` (and the original).
+    TooLarge,
+}
+
+impl Display for ModifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ModifyError::NotApplicable => return write!(f, "Not a packet to rewrite"),
+            ModifyError::Truncated => return write!(f, "Packet is truncated"),
+            ModifyError::UnsupportedNextHeader(n) => return write!(f, "Unsupported next header {}", n),
+            ModifyError::BadOptionLength => return write!(f, "Bad option length"),
+            ModifyError::BadFragments => return write!(f, "Bad fragments"),
+            ModifyError::BadChecksum => return write!(f, "Bad checksum"),
+            ModifyError::TooLarge => return write!(f, "Rewritten packet is too large"),
+        }
+    }
+}
+
+impl core::error::Error for ModifyError { }
+
+/// What to inject into packets passing through `modify`.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct ModifyConfig {
+    /// Addresses for the RA RDNSS and DHCPv6 DNS options, in order of preference.
+    pub dns_ips: Vec<Ipv6Addr>,
+    /// Domains for the RA DNSSL and DHCPv6 domain search list options.  If empty, any
+    /// existing ones are left alone.
+    pub dns_search: Vec<String>,
+    /// Lifetime (seconds) for the RDNSS/DNSSL options.  If not set, the lifetime from
+    /// the router's RDNSS is used.
+    pub rdnss_lifetime: Option<u32>,
+    /// Raise shorter RDNSS/DNSSL lifetimes (configured or the router's) to this.
+    pub min_rdnss_lifetime: Option<u32>,
+    /// Lower longer RDNSS/DNSSL lifetimes to this.  Takes precedence over
+    /// `min_rdnss_lifetime`.
+    pub max_rdnss_lifetime: Option<u32>,
+    /// Override/inject RA MTU
+    pub mtu: Option<u32>,
+    /// Override the RA Cur Hop Limit.
+    pub cur_hop_limit: Option<u8>,
+    /// Override the RA Router Lifetime (seconds).
+    pub router_lifetime: Option<u16>,
+    /// Override the RA Reachable Time (milliseconds).
+    pub reachable_time: Option<u32>,
+    /// Override the RA Retrans Timer (milliseconds).
+    pub retrans_timer: Option<u32>,
+    /// Keep the router's DNS servers, advertising `dns_ips` after them.  In RAs the
+    /// existing RDNSS options are kept and ours is appended, in DHCPv6 the addresses
+    /// are merged into one DNS option.
+    pub keep_existing_dns: bool,
+    /// With `keep_existing_dns`, merge the addresses from all the router's RDNSS
+    /// options and `dns_ips` into one RDNSS option (without duplicates) rather than
+    /// keeping each option.
+    pub consolidate_rdnss: bool,
+    /// DHCPv6 message types to rewrite, others are passed through unmodified.  If not
+    /// set, `DEFAULT_DHCP_MESSAGE_TYPES`.  For Relay-Replies this is checked against
+    /// the relayed message.
+    pub dhcp_message_types: Option<Vec<u8>>,
+    /// UDP ports DHCPv6 messages are sent between, both the source and destination
+    /// must be one of these.  If not set, `DEFAULT_DHCP_PORTS`.
+    pub dhcp_ports: Option<Vec<u16>>,
+    /// Addresses for the IPv4 DHCP DNS option.  If empty, IPv4 packets aren't
+    /// handled.
+    pub dns_ips_v4: Vec<Ipv4Addr>,
+    /// Set (`true`) or clear (`false`) the RA Other configuration flag.  RAs where
+    /// this changes the flag are rewritten even if nothing else changes.  If not set,
+    /// the router's value is kept.
+    pub set_other_flag: Option<bool>,
+    /// Set or clear the RA Managed address configuration flag, like
+    /// `set_other_flag`.
+    pub set_managed_flag: Option<bool>,
+    /// If `set_other_flag` isn't set, set the Other flag in RAs that are rewritten for
+    /// other reasons (ex: DNS), without rewriting RAs only for it.
+    pub set_other_flag_on_rewrite: bool,
+    /// Override/inject the RA NAT64 prefix (PREF64) option.
+    pub pref64: Option<Pref64>,
+    /// Put our RA RDNSS/DNSSL options after all the other options rather than where
+    /// the router's were.
+    pub dns_options_at_end: bool,
+    /// Check the checksum of packets to rewrite first, returning
+    /// `ModifyError::BadChecksum` rather than rewriting (and fixing) corrupt packets.
+    pub verify_checksum: bool,
+    /// Refuse (`ModifyError::TooLarge`) rewrites that grow a packet past this many
+    /// bytes (IP header included), ex: the link MTU.  Packets that were already this
+    /// large are still rewritten as long as they don't grow.
+    pub max_packet: Option<usize>,
+}
+
+/// A NAT64 prefix for the RA PREF64 option.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc8781
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pref64 {
+    prefix: Ipv6Addr,
+    len: u8,
+}
+
+impl Pref64 {
+    /// `None` unless `len` is one of the lengths the option can encode (32, 40, 48, 56,
+    /// 64, 96).
+    pub fn new(prefix: Ipv6Addr, len: u8) -> Option<Pref64> {
+        if !matches!(len, 32 | 40 | 48 | 56 | 64 | 96) {
+            return None;
+        }
+        return Some(Pref64 {
+            prefix,
+            len,
+        });
+    }
+
+    /// The prefix length code.
+    fn plc(&self) -> u16 {
+        match self.len {
+            96 => return 0,
+            len => return (64 - len as u16) / 8 + 1,
+        }
+    }
+
+    /// The option, valid as long as the router (scaled to 8 second units).
+    fn option(&self, router_lifetime: u16) -> [u8; 16] {
+        let mut out = [0u8; 16];
+        out[0] = OPT_PREF64;
+        out[1] = 2;
+        let scaled_lifetime = router_lifetime.div_ceil(8).min(0x1fff);
+        out[2 .. 4].copy_from_slice(&(scaled_lifetime << 3 | self.plc()).to_be_bytes());
+
+        // Only the prefix's bytes, rest zero
+        let prefix_bytes = self.len as usize / 8;
+        out[4 .. 4 + prefix_bytes].copy_from_slice(&self.prefix.octets()[.. prefix_bytes]);
+        return out;
+    }
+}
+
+impl ModifyConfig {
+    /// The lifetime for our RDNSS/DNSSL options: `rdnss_lifetime` or the router's,
+    /// clamped.  0 (withdraw the addresses) is never clamped.
+    pub fn effective_rdnss_lifetime(&self, router_lifetime: u32) -> u32 {
+        let lifetime = self.rdnss_lifetime.unwrap_or(router_lifetime);
+        if lifetime == 0 {
+            return 0;
+        }
+        return lifetime
+            .max(self.min_rdnss_lifetime.unwrap_or(0))
+            .min(self.max_rdnss_lifetime.unwrap_or(u32::MAX));
+    }
+}
+
+pub const DHCP_ADVERTISE: u8 = 2;
+pub const DHCP_REPLY: u8 = 7;
+pub const DHCP_RELAY_REPLY: u8 = 13;
+
+/// DHCPv6 message types that carry server options back to the client.
+pub const DEFAULT_DHCP_MESSAGE_TYPES: &[u8] = &[DHCP_ADVERTISE, DHCP_REPLY];
+
+/// The DHCPv6 client and server (and relay agent) ports.
+pub const DEFAULT_DHCP_PORTS: &[u16] = &[546, 547];
+
+/// An RA RDNSS option (type, length, reserved, lifetime, addresses) advertising
+/// `addrs` for `lifetime` seconds.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc8106#section-5.1
+pub fn build_rdnss_option(addrs: &[Ipv6Addr], lifetime: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(8 + 16 * addrs.len());
+    out.push(OPT_RDNSS);
+
+    // In units of 8 octets
+    out.push(((1 + 1 + 2 + 4 + 16 * addrs.len()) / 8) as u8);
+    out.extend_from_slice(&[0, 0]);
+    out.extend(lifetime.to_be_bytes());
+    for addr in addrs {
+        out.extend(addr.octets());
+    }
+    return out;
+}
+
+/// A DHCPv6 DNS recursive name server option (code, length, addresses) advertising
+/// `addrs`.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc3646#section-3
+pub fn build_dhcp6_dns_option(addrs: &[Ipv6Addr]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(4 + 16 * addrs.len());
+    out.extend_from_slice(&OPT_DHCP6_DNS.to_be_bytes());
+
+    // 16 bytes per address
+    out.extend_from_slice(&((16 * addrs.len()) as u16).to_be_bytes());
+    for addr in addrs {
+        out.extend(addr.octets());
+    }
+    return out;
+}
+
+/// Rewrite the options of a DHCPv6 `message` (not a relay message), appending the
+/// rewritten message to `out`.  `out_base` is the position in `out` and
+/// `message_offset` the position in the original packet that offsets in
+/// `checksum_update` are relative to.  Returns `false` if the message type isn't one
+/// we rewrite or it has no DNS options to replace, in which case `out` should be
+/// discarded.
+fn rewrite_dhcp6_message(
+    out: &mut Vec<u8>,
+    out_base: usize,
+    message: &[u8],
+    message_offset: usize,
+    config: &ModifyConfig,
+    checksum_update: &mut ChecksumUpdate,
+) -> Result<bool, ModifyError> {
+    // Confirm it's a message type we rewrite (advertise, reply)
+    let message_type = *message.first().ok_or(ModifyError::Truncated)?;
+    if !config.dhcp_message_types.as_deref().unwrap_or(DEFAULT_DHCP_MESSAGE_TYPES).contains(&message_type) {
+        return Ok(false);
+    }
+
+    // Copy + filter out options
+    const OPT_DOMAIN_LIST: u16 = 0x18;
+    let mut found_dns = false;
+    let mut found_domain_list = false;
+    let mut existing_dns = vec![];
+    out.extend_from_slice(message.get(.. DHCP_FIXED_HEADER_SIZE).ok_or(ModifyError::Truncated)?);
+    let dhcp_options = &message[DHCP_FIXED_HEADER_SIZE..];
+    let mut option_at = 0;
+    for option in Dhcp6Options::new(dhcp_options) {
+        let (at_option_code, at_option_body) = option.ok_or(ModifyError::BadOptionLength)?;
+        let option_offset = message_offset + DHCP_FIXED_HEADER_SIZE + option_at;
+        let out_offset = out.len() - out_base;
+        shed!{
+            'next_option _;
+            if at_option_code == OPT_DHCP6_DNS {
+                // Drop existing DNS, maybe keeping the addresses to merge into ours
+                found_dns = true;
+                if config.keep_existing_dns {
+                    // Skipping ours, in case this was already rewritten
+                    for ip in at_option_body.chunks_exact(16) {
+                        let ip = Ipv6Addr::from(<[u8; 16]>::try_from(ip).unwrap());
+                        if !config.dns_ips.contains(&ip) {
+                            existing_dns.push(ip);
+                        }
+                    }
+                }
+                break 'next_option;
+            }
+            if !config.dns_search.is_empty() && at_option_code == OPT_DOMAIN_LIST {
+                found_domain_list = true;
+                break 'next_option;
+            }
+            // Keep anything not DNS
+            out.extend_from_slice(&at_option_code.to_be_bytes());
+            out.extend_from_slice(&(at_option_body.len() as u16).to_be_bytes());
+            out.extend_from_slice(at_option_body);
+        }
+        let option_len = 4 + at_option_body.len();
+        if out.len() - out_base == out_offset {
+            checksum_update.remove(option_offset, &dhcp_options[option_at .. option_at + option_len]);
+        } else {
+            checksum_update.moved(option_offset, out_offset);
+        }
+        option_at += option_len;
+    }
+    if !found_dns && !found_domain_list {
+        return Ok(false);
+    }
+
+    // Generate custom DNS option
+    let added_start = out.len();
+    if found_dns {
+        existing_dns.extend_from_slice(&config.dns_ips);
+        out.extend(build_dhcp6_dns_option(&existing_dns));
+    }
+
+    // Generate custom domain search list (RFC 3646), like DNSSL only if the server sent
+    // DNS info
+    if !config.dns_search.is_empty() {
+        let option_start = out.len();
+        out.extend_from_slice(&OPT_DOMAIN_LIST.to_be_bytes());
+        out.extend_from_slice(&[0, 0]);
+        for name in &config.dns_search {
+            encode_dns_name(out, name);
+        }
+        let length = (out.len() - option_start - 4) as u16;
+        out[option_start + 2 .. option_start + 4].copy_from_slice(&length.to_be_bytes());
+    }
+    checksum_update.add(added_start - out_base, &out[added_start..]);
+    return Ok(true);
+}
+
+/// Rewrite the message relayed in a DHCPv6 Relay-Reply `message`, descending through
+/// further Relay-Replies for multi-hop relays, and append the relay message with its
+/// Relay-Message option lengths fixed to `out`.  `hops` is the number of relays
+/// already descended through.  Returns `false` if the relayed message wasn't
+/// rewritten, in which case `out` should be discarded.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc8415#section-9
+fn rewrite_dhcp6_relay(
+    out: &mut Vec<u8>,
+    message: &[u8],
+    config: &ModifyConfig,
+    hops: u8,
+) -> Result<bool, ModifyError> {
+    if hops >= DHCP_HOP_COUNT_LIMIT {
+        return Ok(false);
+    }
+    out.extend_from_slice(message.get(.. DHCP_RELAY_HEADER_SIZE).ok_or(ModifyError::Truncated)?);
+    let mut rewritten = false;
+    for option in Dhcp6Options::new(&message[DHCP_RELAY_HEADER_SIZE..]) {
+        let (at_option_code, at_option_body) = option.ok_or(ModifyError::BadOptionLength)?;
+        let option_start = out.len();
+        out.extend_from_slice(&at_option_code.to_be_bytes());
+        out.extend_from_slice(&[0, 0]);
+        let body_start = out.len();
+        let body_rewritten = at_option_code == OPT_DHCP6_RELAY_MSG && match at_option_body.first() {
+            Some(&DHCP_RELAY_REPLY) => rewrite_dhcp6_relay(out, at_option_body, config, hops + 1)?,
+            Some(_) => rewrite_dhcp6_message(
+                out,
+                body_start,
+                at_option_body,
+                0,
+                config,
+                &mut ChecksumUpdate::default(),
+            )?,
+            None => false,
+        };
+        if body_rewritten {
+            rewritten = true;
+        } else {
+            out.truncate(body_start);
+            out.extend_from_slice(at_option_body);
+        }
+        let length = (out.len() - body_start) as u16;
+        out[option_start + 2 .. option_start + 4].copy_from_slice(&length.to_be_bytes());
+    }
+    return Ok(rewritten);
+}
+
+/// Append `name` in DNS wire format (length-prefixed labels, zero terminated).
+fn encode_dns_name(out: &mut Vec<u8>, name: &str) {
+    for label in name.split('.') {
+        if label.is_empty() {
+            continue;
+        }
+        out.push(label.len() as u8);
+        out.extend_from_slice(label.as_bytes());
+    }
+    out.push(0);
+}
+
+/// Rewrite an IP packet (starting at the IP header) per `config`.
+///
+/// * RAs: RDNSS (and DNSSL, MTU, PREF64) options are replaced and the Managed and
+///   Other flags are updated per the config.  Option order is kept, replacements go
+///   where the router's options were
+///
+/// * DHCPv6 replies: the DNS (and domain search list) option is replaced
+///
+/// * IPv4 DHCP offers/acks: the DNS option is replaced
+///
+/// Packets that don't need modification are returned unchanged. The IP version is
+/// checked first, anything that's neither IPv4 nor IPv6 is `NotApplicable`.
+///
+/// The ICMPv6/UDP checksum is updated for the changes rather than recomputed where
+/// possible, so a packet that arrived with a bad checksum keeps a bad checksum.
+pub fn modify(source: &[u8], config: &ModifyConfig) -> Result<Vec<u8>, ModifyError> {
+    let mut out = vec![];
+    modify_into(&mut out, source, config)?;
+    return Ok(out);
+}
+
+/// Like `modify`, but writes the packet to `out` (clearing it first) so one buffer
+/// can be reused for every packet.  On error the contents of `out` are unspecified.
+pub fn modify_into(out: &mut Vec<u8>, source: &[u8], config: &ModifyConfig) -> Result<(), ModifyError> {
+    rewrite_into(out, source, config)?;
+    if let Some(max_packet) = config.max_packet {
+        if out.len() > max_packet && out.len() > source.len() {
+            return Err(ModifyError::TooLarge);
+        }
+    }
+    return Ok(());
+}
+
+/// `modify_into` without the `max_packet` check.
+fn rewrite_into(out: &mut Vec<u8>, source: &[u8], config: &ModifyConfig) -> Result<(), ModifyError> {
+    out.clear();
+    match source.first().ok_or(ModifyError::Truncated)? >> 4 {
+        4 => return dhcp4::modify_into(out, source, config),
+        6 => { },
+        _ => return Err(ModifyError::NotApplicable),
+    }
+    let ipv6_packet = Ipv6Packet::new(source).ok_or(ModifyError::Truncated)?;
+
+    // Captured short (ex: a small netfilter queue copy range), rewriting would drop
+    // the rest and fix up the lengths to match
+    if ipv6_packet.payload().len() < ipv6_packet.payload_len() as usize {
+        return Err(ModifyError::Truncated);
+    }
+
+    // Offsets below are relative to the IPv6 payload
+    let (next_header, upper_start) = ipv6_packet.upper_layer()?;
+    match next_header {
+        // ICMP
+        //
+        // * https://datatracker.ietf.org/doc/html/rfc4443
+        //
+        // RA
+        //
+        // * https://datatracker.ietf.org/doc/html/rfc4861#section-4.2
+        //
+        // Only replace RA if present.  If it's not present, it may come via DHCP so don't
+        // add things here and confuse devices (also need the option for a lifetime to
+        // copy).
+        NEXT_HEADER_ICMPV6 => {
+            // Confirm it's RA
+            if ipv6_packet.payload_u8(upper_start).ok_or(ModifyError::Truncated)? != ICMPV6_TYPE_RA {
+                return Err(ModifyError::NotApplicable);
+            }
+            verify_checksum(source, config)?;
+
+            // Modify RA
+            const OPT_MTU: u8 = 5;
+            const OPT_DNSSL: u8 = 31;
+            let ra_options_start = upper_start + RA_FIXED_HEADER_SIZE;
+
+            // Copy the headers, updating the flags and overridden fields
+            const FLAG_MANAGED: u8 = 0x80;
+            const FLAG_OTHER: u8 = 0x40;
+            out.reserve(source.len() + 128);
+            out.extend_from_slice(source.get(.. IPV6_HEADER_SIZE + ra_options_start).ok_or(ModifyError::Truncated)?);
+            let ra_header = &mut out[IPV6_HEADER_SIZE + upper_start..];
+            let old_flags = ra_header[5];
+            for (flag, set) in [(FLAG_MANAGED, config.set_managed_flag), (FLAG_OTHER, config.set_other_flag)] {
+                match set {
+                    Some(true) => ra_header[5] |= flag,
+                    Some(false) => ra_header[5] &= !flag,
+                    None => { },
+                }
+            }
+            if let Some(cur_hop_limit) = config.cur_hop_limit {
+                ra_header[4] = cur_hop_limit;
+            }
+            if let Some(router_lifetime) = config.router_lifetime {
+                ra_header[6 .. 8].copy_from_slice(&router_lifetime.to_be_bytes());
+            }
+            if let Some(reachable_time) = config.reachable_time {
+                ra_header[8 .. 12].copy_from_slice(&reachable_time.to_be_bytes());
+            }
+            if let Some(retrans_timer) = config.retrans_timer {
+                ra_header[12 .. 16].copy_from_slice(&retrans_timer.to_be_bytes());
+            }
+            let flags_changed = ra_header[5] != old_flags;
+            let router_lifetime = u16::from_be_bytes([ra_header[6], ra_header[7]]);
+
+            // Copy options, find + filter out RDNSS/DNSSL
+            #[derive(Clone, Copy)]
+            struct FoundOption {
+                lifetime: u32,
+            }
+
+            let mut found_rdnss = None;
+            let mut found_dnssl = None;
+
+            // The router's addresses, with `consolidate_rdnss`
+            let mut existing_dns = vec![];
+
+            // Where our RDNSS/DNSSL go (relative to the upper layer header): where the
+            // first RDNSS/DNSSL was, or after the last RDNSS we keep
+            let mut dns_options_at = None;
+            let mut modify = false;
+            if flags_changed ||
+                config.mtu.is_some() ||
+                config.pref64.is_some() ||
+                config.cur_hop_limit.is_some() ||
+                config.router_lifetime.is_some() ||
+                config.reachable_time.is_some() ||
+                config.retrans_timer.is_some() {
+                modify = true;
+            }
+            fn read_lifetime(option: &[u8]) -> Option<u32> {
+                return Some(u32::from_be_bytes(option.get(4 .. 8)?.try_into().unwrap()));
+            }
+
+            // MTU and PREF64 replace the router's in place, or are added after the other
+            // options
+            let mut mtu_option = config.mtu.map(|mtu| [[OPT_MTU, 1, 0, 0], mtu.to_be_bytes()].concat());
+            let mut pref64_option = config.pref64.as_ref().map(|pref64| pref64.option(router_lifetime));
+
+            let upper_offset = IPV6_HEADER_SIZE + upper_start;
+            let mut checksum_update = ChecksumUpdate::default();
+            let mut option_offset = RA_FIXED_HEADER_SIZE;
+            for option in RaOptions::new(ipv6_packet.payload().get(ra_options_start..).ok_or(ModifyError::Truncated)?) {
+                let (at_option_type, option) = option.ok_or(ModifyError::BadOptionLength)?;
+                let out_offset = out.len() - upper_offset;
+                shed!{
+                    'next_option _;
+                    if at_option_type == OPT_RDNSS {
+                        found_rdnss =
+                            Some(FoundOption { lifetime: read_lifetime(option).ok_or(ModifyError::BadOptionLength)? });
+                        modify = true;
+                        if !config.keep_existing_dns {
+                            dns_options_at = dns_options_at.or(Some(out_offset));
+                            break 'next_option;
+                        }
+                        if config.consolidate_rdnss {
+                            // Skipping ours, in case this was already rewritten
+                            for ip in option.get(8..).ok_or(ModifyError::BadOptionLength)?.chunks_exact(16) {
+                                let ip = Ipv6Addr::from(<[u8; 16]>::try_from(ip).unwrap());
+                                if !config.dns_ips.contains(&ip) && !existing_dns.contains(&ip) {
+                                    existing_dns.push(ip);
+                                }
+                            }
+                            dns_options_at = dns_options_at.or(Some(out_offset));
+                            break 'next_option;
+                        }
+
+                        // Our own from a previous pass (ex: reinjected packet wasn't skipped),
+                        // replace it rather than adding another
+                        let ips = option.get(8..).ok_or(ModifyError::BadOptionLength)?;
+                        if ips.chunks(16).eq(config.dns_ips.iter().map(|ip| ip.octets())) {
+                            dns_options_at = dns_options_at.or(Some(out_offset));
+                            break 'next_option;
+                        }
+                        dns_options_at = Some(out_offset + option.len());
+                    }
+                    if !config.dns_search.is_empty() && at_option_type == OPT_DNSSL {
+                        found_dnssl =
+                            Some(FoundOption { lifetime: read_lifetime(option).ok_or(ModifyError::BadOptionLength)? });
+                        modify = true;
+                        dns_options_at = dns_options_at.or(Some(out_offset));
+                        break 'next_option;
+                    }
+                    if config.mtu.is_some() && at_option_type == OPT_MTU {
+                        if let Some(mtu_option) = mtu_option.take() {
+                            out.extend_from_slice(&mtu_option);
+                            checksum_update.replace(out_offset, option, &mtu_option);
+                        }
+                        break 'next_option;
+                    }
+                    if config.pref64.is_some() && at_option_type == OPT_PREF64 {
+                        if let Some(pref64_option) = pref64_option.take() {
+                            out.extend_from_slice(&pref64_option);
+                            checksum_update.replace(out_offset, option, &pref64_option);
+                        }
+                        break 'next_option;
+                    }
+                    // Keep anything we're not going to modify
+                    out.extend_from_slice(option);
+                }
+                if out.len() - upper_offset == out_offset {
+                    checksum_update.remove(option_offset, option);
+                } else {
+                    checksum_update.moved(option_offset, out_offset);
+                }
+                option_offset += option.len();
+            }
+            if !modify {
+                out.clear();
+                out.extend_from_slice(source);
+                return Ok(());
+            }
+            if config.set_other_flag.is_none() && config.set_other_flag_on_rewrite {
+                out[IPV6_HEADER_SIZE + upper_start + 5] |= FLAG_OTHER;
+            }
+
+            // Create custom MTU
+            let added_offset = out.len() - upper_offset;
+            if let Some(mtu_option) = mtu_option {
+                out.extend_from_slice(&mtu_option);
+            }
+
+            // Generate custom RDNSS
+            let dns_options_start = out.len();
+            if let Some(found_rdnss) = found_rdnss {
+                existing_dns.extend(config.dns_ips.iter().copied());
+                out.extend(build_rdnss_option(&existing_dns, config.effective_rdnss_lifetime(found_rdnss.lifetime)));
+            }
+
+            // Generate custom DNSSL (RFC 6106), sharing the RDNSS lifetime if there was one
+            if let Some(found) = found_rdnss.or(found_dnssl).filter(|_| !config.dns_search.is_empty()) {
+                let option_start = out.len();
+                out.push(OPT_DNSSL);
+                out.push(0);
+                out.extend_from_slice(&[0, 0]);
+                out.extend(config.effective_rdnss_lifetime(found.lifetime).to_be_bytes());
+                for name in &config.dns_search {
+                    encode_dns_name(out, name);
+                }
+
+                // Pad to a multiple of 8 octets
+                out.resize(option_start + (out.len() - option_start).div_ceil(8) * 8, 0);
+                out[option_start + 1] = ((out.len() - option_start) / 8) as u8;
+            }
+            let dns_options_end = out.len();
+
+            // Create custom PREF64
+            if let Some(pref64_option) = pref64_option {
+                out.extend_from_slice(&pref64_option);
+            }
+
+            // Update the ipv6 payload length
+            let old_upper = ipv6_packet.payload().get(upper_start..).ok_or(ModifyError::Truncated)?;
+            let mut ipv6_packet = Ipv6Packet::new(&mut *out).ok_or(ModifyError::Truncated)?;
+            ipv6_packet.set_payload_len(ipv6_packet.payload().len() as u16);
+
+            // Update the checksum for the options, fixed fields and length (pseudo header)
+            let new_upper = &out[upper_offset..];
+            checksum_update.add(added_offset, &new_upper[added_offset..]);
+            checksum_update.replace(4, &old_upper[4 .. RA_FIXED_HEADER_SIZE], &new_upper[4 .. RA_FIXED_HEADER_SIZE]);
+            checksum_update.replace(
+                0,
+                &(old_upper.len() as u32).to_be_bytes(),
+                &(new_upper.len() as u32).to_be_bytes(),
+            );
+
+            // Move our RDNSS/DNSSL into place.  Options are multiples of 8 bytes so this
+            // doesn't affect the checksum.
+            if let Some(at) = dns_options_at.filter(|_| !config.dns_options_at_end) {
+                out[upper_offset + at .. dns_options_end].rotate_right(dns_options_end - dns_options_start);
+            }
+            set_checksum(out, upper_start, 2, [old_upper[2], old_upper[3]], &checksum_update)?;
+        },
+        // UDP (DHCPv6)
+        //
+        // * https://datatracker.ietf.org/doc/html/rfc8415
+        NEXT_HEADER_UDP => {
+            if !is_dhcp6(&ipv6_packet, upper_start, config).ok_or(ModifyError::Truncated)? {
+                return Err(ModifyError::NotApplicable);
+            }
+            verify_checksum(source, config)?;
+
+            // Rewrite the message, or the message it relays
+            let message_type =
+                ipv6_packet.payload_u8(upper_start + UDP_FIXED_HEADER_SIZE).ok_or(ModifyError::Truncated)?;
+            let dhcp_start = upper_start + UDP_FIXED_HEADER_SIZE;
+            let message = ipv6_packet.payload().get(dhcp_start..).ok_or(ModifyError::Truncated)?;
+            let old_upper = ipv6_packet.payload().get(upper_start..).ok_or(ModifyError::Truncated)?;
+            let upper_offset = IPV6_HEADER_SIZE + upper_start;
+            out.reserve(source.len() + 128);
+            out.extend_from_slice(source.get(.. IPV6_HEADER_SIZE + dhcp_start).ok_or(ModifyError::Truncated)?);
+            let mut checksum_update = ChecksumUpdate::default();
+            let mut old_checksum = [old_upper[6], old_upper[7]];
+            let rewritten = if message_type == DHCP_RELAY_REPLY {
+                // The lengths of every enclosing layer change, so recompute the checksum
+                // (a zero checksum is always recomputed)
+                old_checksum = [0, 0];
+                rewrite_dhcp6_relay(out, message, config, 0)?
+            } else {
+                rewrite_dhcp6_message(out, upper_offset, message, UDP_FIXED_HEADER_SIZE, config, &mut checksum_update)?
+            };
+            if !rewritten {
+                out.clear();
+                out.extend_from_slice(source);
+                return Ok(());
+            }
+
+            // Update the ipv6 payload length and payload length in udp header
+            let mut ipv6_packet = Ipv6Packet::new(&mut *out).ok_or(ModifyError::Truncated)?;
+            ipv6_packet.set_payload_len(ipv6_packet.payload().len() as u16);
+            let new_len = ipv6_packet.payload().len() - upper_start;
+            ipv6_packet.set_payload_u16(upper_start + 4, new_len as u16).ok_or(ModifyError::Truncated)?;
+
+            // Update the checksum for the lengths (udp header and pseudo header)
+            let new_upper = &out[upper_offset..];
+            checksum_update.replace(4, &old_upper[4 .. 6], &new_upper[4 .. 6]);
+            checksum_update.replace(
+                0,
+                &(old_upper.len() as u32).to_be_bytes(),
+                &(new_upper.len() as u32).to_be_bytes(),
+            );
+            set_checksum(out, upper_start, 6, old_checksum, &checksum_update)?;
+        },
+        _ => {
+            return Err(ModifyError::UnsupportedNextHeader(next_header));
+        },
+    }
+
+    // Done
+    return Ok(());
+}
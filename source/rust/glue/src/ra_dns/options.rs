@@ -0,0 +1,82 @@
+/// Iterates over RA (NDP) options, yielding `(type, option)` where `option` is the
+/// whole option including the type and length.
+///
+/// Yields `None` (then stops) if an option has a zero length or runs past the end
+/// of the buffer.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc4861#section-4.6
+pub struct RaOptions<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> RaOptions<'a> {
+    /// `data` starts at the first option and ends at the end of the packet.
+    pub fn new(data: &'a [u8]) -> RaOptions<'a> {
+        return RaOptions { data };
+    }
+}
+
+impl<'a> Iterator for RaOptions<'a> {
+    type Item = Option<(u8, &'a [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let data = self.data;
+
+        // Stop after an error
+        self.data = &[];
+        let Some(length) = data.get(1).map(|l| *l as usize * 8) else {
+            return Some(None);
+        };
+        if length == 0 || length > data.len() {
+            return Some(None);
+        }
+        let (option, rest) = data.split_at(length);
+        self.data = rest;
+        return Some(Some((option[0], option)));
+    }
+}
+
+/// Iterates over DHCPv6 options, yielding `(code, body)` where `body` excludes the
+/// code and length.
+///
+/// Yields `None` (then stops) if an option header is truncated or its length runs
+/// past the end of the buffer.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc8415#section-21.1
+pub struct Dhcp6Options<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Dhcp6Options<'a> {
+    /// `data` starts at the first option and ends at the end of the UDP payload.
+    pub fn new(data: &'a [u8]) -> Dhcp6Options<'a> {
+        return Dhcp6Options { data };
+    }
+}
+
+impl<'a> Iterator for Dhcp6Options<'a> {
+    type Item = Option<(u16, &'a [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let data = self.data;
+
+        // Stop after an error
+        self.data = &[];
+        let Some(header) = data.get(.. 4) else {
+            return Some(None);
+        };
+        let code = u16::from_be_bytes([header[0], header[1]]);
+        let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let Some(body) = data.get(4 .. 4 + length) else {
+            return Some(None);
+        };
+        self.data = &data[4 + length..];
+        return Some(Some((code, body)));
+    }
+}
@@ -0,0 +1,176 @@
+use {
+    super::ModifyError,
+    alloc::vec::Vec,
+    core::net::Ipv6Addr,
+};
+
+pub const IPV6_HEADER_SIZE: usize = 40;
+
+/// Bounds-checked access to an IPv6 packet (starting at the IPv6 header), over a
+/// `&[u8]`, `&mut [u8]` or `Vec<u8>`.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc8200#section-3
+pub struct Ipv6Packet<B> {
+    data: B,
+}
+
+impl<B: AsRef<[u8]>> Ipv6Packet<B> {
+    /// Returns `None` if `data` is shorter than the fixed header or isn't IPv6.
+    pub fn new(data: B) -> Option<Ipv6Packet<B>> {
+        if *data.as_ref().first()? >> 4 != 6 {
+            return None;
+        }
+        return Ipv6Packet::new_any_version(data);
+    }
+
+    /// Like `new` but doesn't check the version, for code that doesn't depend on it
+    /// (ex: checksums).
+    pub(crate) fn new_any_version(data: B) -> Option<Ipv6Packet<B>> {
+        if data.as_ref().len() < IPV6_HEADER_SIZE {
+            return None;
+        }
+        return Some(Ipv6Packet { data });
+    }
+
+    /// The whole packet, including the fixed header.
+    pub fn as_bytes(&self) -> &[u8] {
+        return self.data.as_ref();
+    }
+
+    pub fn into_inner(self) -> B {
+        return self.data;
+    }
+
+    /// The payload length field (extension headers and upper-layer).
+    pub fn payload_len(&self) -> u16 {
+        return u16::from_be_bytes([self.as_bytes()[4], self.as_bytes()[5]]);
+    }
+
+    /// The type of the first header after the fixed header.
+    pub fn next_header(&self) -> u8 {
+        return self.as_bytes()[6];
+    }
+
+    pub fn src(&self) -> Ipv6Addr {
+        return Ipv6Addr::from(<[u8; 16]>::try_from(&self.as_bytes()[8 .. 24]).unwrap());
+    }
+
+    pub fn dst(&self) -> Ipv6Addr {
+        return Ipv6Addr::from(<[u8; 16]>::try_from(&self.as_bytes()[24 .. 40]).unwrap());
+    }
+
+    /// Everything after the fixed header - extension headers then the upper-layer.
+    /// This is the captured data, which may not match `payload_len` (ex: truncated,
+    /// or a jumbogram).
+    pub fn payload(&self) -> &[u8] {
+        return &self.as_bytes()[IPV6_HEADER_SIZE..];
+    }
+
+    /// Read a byte at `at` in the payload.
+    pub fn payload_u8(&self, at: usize) -> Option<u8> {
+        return self.payload().get(at).cloned();
+    }
+
+    /// Read a big-endian `u16` at `at` in the payload.
+    pub fn payload_u16(&self, at: usize) -> Option<u16> {
+        return Some(u16::from_be_bytes(self.payload().get(at .. at + 2)?.try_into().unwrap()));
+    }
+
+    /// Read a big-endian `u32` at `at` in the payload.
+    pub fn payload_u32(&self, at: usize) -> Option<u32> {
+        return Some(u32::from_be_bytes(self.payload().get(at .. at + 4)?.try_into().unwrap()));
+    }
+
+    /// Follow the extension header chain, returning the upper-layer protocol and the
+    /// offset in the payload where its header starts.
+    ///
+    /// * https://datatracker.ietf.org/doc/html/rfc8200#section-4
+    pub fn upper_layer(&self) -> Result<(u8, usize), ModifyError> {
+        let mut next_header = self.next_header();
+        let mut at = 0;
+        loop {
+            match next_header {
+                // Hop-by-hop, routing, destination options
+                0 | 43 | 60 => {
+                    let length = (self.payload_u8(at + 1).ok_or(ModifyError::Truncated)? as usize + 1) * 8;
+                    next_header = self.payload_u8(at).ok_or(ModifyError::Truncated)?;
+                    at += length;
+                },
+                // Fragment - the upper layer is only complete in an unfragmented (atomic)
+                // fragment
+                44 => {
+                    if self.payload_u16(at + 2).ok_or(ModifyError::Truncated)? & 0xFFF9 != 0 {
+                        return Err(ModifyError::UnsupportedNextHeader(next_header));
+                    }
+                    next_header = self.payload_u8(at).ok_or(ModifyError::Truncated)?;
+                    at += 8;
+                },
+                _ => {
+                    if at > self.payload().len() {
+                        return Err(ModifyError::Truncated);
+                    }
+                    return Ok((next_header, at));
+                },
+            }
+        }
+    }
+
+    /// Find the fragment header, returning its offset in the payload and the offset
+    /// in the packet of the next header field that points to it. `None` if there is
+    /// no fragment header before the upper-layer.
+    pub fn fragment_header(&self) -> Result<Option<(usize, usize)>, ModifyError> {
+        let mut next_header_at = 6;
+        let mut at = 0;
+        loop {
+            match self.as_bytes()[next_header_at] {
+                // Hop-by-hop, routing, destination options
+                0 | 43 | 60 => {
+                    let length = (self.payload_u8(at + 1).ok_or(ModifyError::Truncated)? as usize + 1) * 8;
+                    self.payload_u8(at).ok_or(ModifyError::Truncated)?;
+                    next_header_at = IPV6_HEADER_SIZE + at;
+                    at += length;
+                },
+                44 => {
+                    if at + 8 > self.payload().len() {
+                        return Err(ModifyError::Truncated);
+                    }
+                    return Ok(Some((at, next_header_at)));
+                },
+                _ => {
+                    return Ok(None);
+                },
+            }
+        }
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> Ipv6Packet<B> {
+    pub fn set_payload_len(&mut self, len: u16) {
+        self.data.as_mut()[4 .. 6].copy_from_slice(&len.to_be_bytes());
+    }
+
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        return &mut self.data.as_mut()[IPV6_HEADER_SIZE..];
+    }
+
+    /// Write a big-endian `u16` at `at` in the payload.
+    #[must_use]
+    pub fn set_payload_u16(&mut self, at: usize, value: u16) -> Option<()> {
+        self.payload_mut().get_mut(at .. at + 2)?.copy_from_slice(&value.to_be_bytes());
+        return Some(());
+    }
+}
+
+impl Ipv6Packet<Vec<u8>> {
+    /// Replace the payload from `start` to the end with `data`, updating the payload
+    /// length.
+    #[must_use]
+    pub fn replace_payload_tail(&mut self, start: usize, data: &[u8]) -> Option<()> {
+        if start > self.payload().len() {
+            return None;
+        }
+        self.data.splice(IPV6_HEADER_SIZE + start.., data.iter().cloned());
+        self.set_payload_len((start + data.len()) as u16);
+        return Some(());
+    }
+}
@@ -0,0 +1,229 @@
+//! Read-only decoding of RAs and DHCPv6 messages, for logs and `--parse`.
+use {
+    super::{
+        options::{
+            Dhcp6Options,
+            RaOptions,
+        },
+        packet::Ipv6Packet,
+        ModifyError,
+        DHCP_FIXED_HEADER_SIZE,
+        DHCP_RELAY_HEADER_SIZE,
+        DHCP_RELAY_REPLY,
+        ICMPV6_TYPE_RA,
+        NEXT_HEADER_ICMPV6,
+        NEXT_HEADER_UDP,
+        OPT_DHCP6_DNS,
+        OPT_DHCP6_RELAY_MSG,
+        OPT_RDNSS,
+        RA_FIXED_HEADER_SIZE,
+        UDP_FIXED_HEADER_SIZE,
+    },
+    alloc::{
+        boxed::Box,
+        vec,
+        vec::Vec,
+    },
+    core::{
+        fmt::{
+            self,
+            Display,
+            Formatter,
+        },
+        net::Ipv6Addr,
+    },
+};
+
+const DHCP_RELAY_FORWARD: u8 = 12;
+
+/// An RA option or DHCPv6 option.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedOption {
+    /// The RA option type or DHCPv6 option code.
+    pub code: u16,
+    /// Bytes, including the type/code and length.
+    pub len: usize,
+    /// The addresses in an RDNSS or DHCPv6 DNS option.
+    pub dns: Vec<Ipv6Addr>,
+}
+
+/// The fields of an RA that matter when rewriting it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedRa {
+    pub source: Ipv6Addr,
+    pub hop_limit: u8,
+    pub managed: bool,
+    pub other: bool,
+    /// Seconds.
+    pub router_lifetime: u16,
+    pub options: Vec<ParsedOption>,
+}
+
+/// A DHCPv6 message, or relay message with the message it relays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedDhcp6 {
+    pub message_type: u8,
+    /// For relay messages.
+    pub hop_count: Option<u8>,
+    /// For other messages.
+    pub transaction_id: Option<u32>,
+    pub options: Vec<ParsedOption>,
+    /// The message in a relay message's Relay-Message option.
+    pub relayed: Option<Box<ParsedDhcp6>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParsedPacket {
+    Ra(ParsedRa),
+    Dhcp6(ParsedDhcp6),
+}
+
+/// Decode an RA or DHCPv6 IPv6 packet, without checking the checksum.  Returns
+/// `ModifyError::NotApplicable` for other packets.
+pub fn parse(packet: &[u8]) -> Result<ParsedPacket, ModifyError> {
+    let packet = Ipv6Packet::new(packet).ok_or(ModifyError::Truncated)?;
+    let (next_header, upper_start) = packet.upper_layer()?;
+    match next_header {
+        NEXT_HEADER_ICMPV6 => {
+            if packet.payload_u8(upper_start).ok_or(ModifyError::Truncated)? != ICMPV6_TYPE_RA {
+                return Err(ModifyError::NotApplicable);
+            }
+            let ra = packet.payload().get(upper_start..).ok_or(ModifyError::Truncated)?;
+            let header = ra.get(.. RA_FIXED_HEADER_SIZE).ok_or(ModifyError::Truncated)?;
+            let mut options = vec![];
+            for option in RaOptions::new(&ra[RA_FIXED_HEADER_SIZE..]) {
+                let (type_, option) = option.ok_or(ModifyError::BadOptionLength)?;
+                options.push(ParsedOption {
+                    code: type_ as u16,
+                    len: option.len(),
+                    dns: if type_ == OPT_RDNSS {
+                        addresses(option.get(8..).unwrap_or_default())
+                    } else {
+                        vec![]
+                    },
+                });
+            }
+            return Ok(ParsedPacket::Ra(ParsedRa {
+                source: packet.src(),
+                hop_limit: header[4],
+                managed: header[5] & 0x80 != 0,
+                other: header[5] & 0x40 != 0,
+                router_lifetime: u16::from_be_bytes([header[6], header[7]]),
+                options,
+            }));
+        },
+        NEXT_HEADER_UDP => {
+            let message =
+                packet.payload().get(upper_start + UDP_FIXED_HEADER_SIZE..).ok_or(ModifyError::Truncated)?;
+            return Ok(ParsedPacket::Dhcp6(parse_dhcp6(message)?));
+        },
+        _ => {
+            return Err(ModifyError::NotApplicable);
+        },
+    }
+}
+
+fn parse_dhcp6(message: &[u8]) -> Result<ParsedDhcp6, ModifyError> {
+    let message_type = *message.first().ok_or(ModifyError::Truncated)?;
+    let relay = message_type == DHCP_RELAY_FORWARD || message_type == DHCP_RELAY_REPLY;
+    let header_size = if relay {
+        DHCP_RELAY_HEADER_SIZE
+    } else {
+        DHCP_FIXED_HEADER_SIZE
+    };
+    let header = message.get(.. header_size).ok_or(ModifyError::Truncated)?;
+    let mut out = ParsedDhcp6 {
+        message_type,
+        hop_count: None,
+        transaction_id: None,
+        options: vec![],
+        relayed: None,
+    };
+    if relay {
+        out.hop_count = Some(header[1]);
+    } else {
+        out.transaction_id = Some(u32::from_be_bytes([0, header[1], header[2], header[3]]));
+    }
+    for option in Dhcp6Options::new(&message[header_size..]) {
+        let (code, body) = option.ok_or(ModifyError::BadOptionLength)?;
+        if relay && code == OPT_DHCP6_RELAY_MSG && out.relayed.is_none() {
+            out.relayed = Some(Box::new(parse_dhcp6(body)?));
+        }
+        out.options.push(ParsedOption {
+            code,
+            len: 4 + body.len(),
+            dns: if code == OPT_DHCP6_DNS {
+                addresses(body)
+            } else {
+                vec![]
+            },
+        });
+    }
+    return Ok(out);
+}
+
+fn addresses(data: &[u8]) -> Vec<Ipv6Addr> {
+    return data.chunks_exact(16).map(|ip| Ipv6Addr::from(<[u8; 16]>::try_from(ip).unwrap())).collect();
+}
+
+fn fmt_options(f: &mut Formatter<'_>, indent: usize, options: &[ParsedOption]) -> fmt::Result {
+    for option in options {
+        write!(f, "\n{:indent$}  option {}, {} bytes", "", option.code, option.len, indent = indent)?;
+        for (i, ip) in option.dns.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 {
+                ": "
+            } else {
+                " "
+            }, ip)?;
+        }
+    }
+    return Ok(());
+}
+
+impl Display for ParsedRa {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "RA from {}, hop limit {}, flags", self.source, self.hop_limit)?;
+        for (flag, set) in [("M", self.managed), ("O", self.other)] {
+            write!(f, " {}", if set {
+                flag
+            } else {
+                "-"
+            })?;
+        }
+        write!(f, ", router lifetime {}s", self.router_lifetime)?;
+        return fmt_options(f, 0, &self.options);
+    }
+}
+
+impl ParsedDhcp6 {
+    fn fmt_indented(&self, f: &mut Formatter<'_>, indent: usize) -> fmt::Result {
+        write!(f, "DHCPv6 message type {}", self.message_type)?;
+        if let Some(hop_count) = self.hop_count {
+            write!(f, ", hop count {}", hop_count)?;
+        }
+        if let Some(transaction_id) = self.transaction_id {
+            write!(f, ", transaction {:06x}", transaction_id)?;
+        }
+        fmt_options(f, indent, &self.options)?;
+        if let Some(relayed) = &self.relayed {
+            write!(f, "\n{:indent$}  relaying ", "", indent = indent)?;
+            relayed.fmt_indented(f, indent + 2)?;
+        }
+        return Ok(());
+    }
+}
+
+impl Display for ParsedDhcp6 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        return self.fmt_indented(f, 0);
+    }
+}
+
+impl Display for ParsedPacket {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParsedPacket::Ra(ra) => return ra.fmt(f),
+            ParsedPacket::Dhcp6(dhcp6) => return dhcp6.fmt(f),
+        }
+    }
+}
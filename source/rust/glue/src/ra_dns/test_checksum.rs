@@ -1,5 +1,5 @@
 use {
-    crate::manglelib::{
+    crate::ra_dns::{
         checksum_finish,
         checksum_roll,
         icmpv6_udp_checksum,
@@ -86,11 +86,11 @@ const PAYLOAD_RA1: &[u8] = &[
 
 #[test]
 fn test_checksum_roll_ex1() {
-    let mut sum32 = 0u32;
+    let mut sum64 = 0u64;
 
     // Wikipedia, checksum set to 0 first
     checksum_roll(
-        &mut sum32,
+        &mut sum64,
         &[
             0x45,
             0x00,
@@ -114,34 +114,60 @@ fn test_checksum_roll_ex1() {
             0xc7,
         ],
     );
-    assert_eq!(checksum_finish(sum32), [0xb8, 0x61]);
+    assert_eq!(checksum_finish(sum64), [0xb8, 0x61]);
 }
 
 #[test]
 fn test_checksum_roll_ex2() {
-    let mut sum32 = 0u32;
+    let mut sum64 = 0u64;
 
     // RFC 1071 example 1
-    checksum_roll(&mut sum32, &[0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7]);
-    assert_eq!(checksum_finish(sum32), [!0xdd, !0xf2]);
+    checksum_roll(&mut sum64, &[0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7]);
+    assert_eq!(checksum_finish(sum64), [!0xdd, !0xf2]);
 }
 
 #[test]
 fn test_checksum_roll_ex3() {
-    let mut sum32 = 0u32;
+    let mut sum64 = 0u64;
 
     // RFC 1071 example 2a
-    checksum_roll(&mut sum32, &[0x00, 0x01, 0xf2]);
-    assert_eq!(checksum_finish(sum32), [!0xf2, !0x01]);
+    checksum_roll(&mut sum64, &[0x00, 0x01, 0xf2]);
+    assert_eq!(checksum_finish(sum64), [!0xf2, !0x01]);
 }
 
 #[test]
 fn test_checksum_roll_ex4() {
-    let mut sum32 = 0u32;
+    let mut sum64 = 0u64;
 
     // RFC 1071 example 2b but shifted by 1
-    checksum_roll(&mut sum32, &[0x03, 0xf4, 0xf5, 0xf6, 0xf7]);
-    assert_eq!(checksum_finish(sum32), [!0xf0, !0xeb]);
+    checksum_roll(&mut sum64, &[0x03, 0xf4, 0xf5, 0xf6, 0xf7]);
+    assert_eq!(checksum_finish(sum64), [!0xf0, !0xeb]);
+}
+
+#[test]
+fn test_checksum_finish_fold_overflow() {
+    // High + low (0x101 + 0xffff) overflows 16 bits, so the carry needs to be folded
+    // a second time. Byte-symmetric so the result doesn't depend on host byte order.
+    assert_eq!(checksum_finish(0x0101_ffff), [0xfe, 0xfe]);
+    assert_eq!(checksum_finish(0xffff_ffff), [0x00, 0x00]);
+}
+
+#[test]
+fn test_checksum_roll_large() {
+    // Enough 0xffff words to overflow a u32 accumulator
+    let bytes = vec![0xffu8; 256 * 1024 + 1];
+    let mut sum64 = 0u64;
+    checksum_roll(&mut sum64, &bytes);
+
+    // Reference one's complement sum, folding as it goes
+    let mut ref_sum = 0u32;
+    for pair in bytes.chunks(2) {
+        ref_sum += u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]) as u32;
+        if ref_sum > 0xffff {
+            ref_sum = (ref_sum & 0xffff) + 1;
+        }
+    }
+    assert_eq!(checksum_finish(sum64), (!(ref_sum as u16)).to_be_bytes());
 }
 
 #[test]
@@ -0,0 +1,109 @@
+use {
+    crate::ra_dns::{
+        icmpv6_udp_checksum,
+        modify,
+        test_util::{
+            PAYLOAD_DHCP,
+            PAYLOAD_RA,
+        },
+        ChecksumUpdate,
+        ModifyConfig,
+        Pref64,
+    },
+    std::net::Ipv6Addr,
+};
+
+const RA_CHECKSUM_AT: usize = 40 + 2;
+const DHCP_CHECKSUM_AT: usize = 40 + 6;
+
+/// The checksum recomputed over the whole packet.
+fn full_checksum(packet: &[u8], checksum_at: usize) -> [u8; 2] {
+    let mut packet = packet.to_vec();
+    packet[checksum_at .. checksum_at + 2].copy_from_slice(&[0, 0]);
+    return icmpv6_udp_checksum(&packet).unwrap();
+}
+
+fn configs() -> Vec<ModifyConfig> {
+    let ip = Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8);
+    return vec![ModifyConfig {
+        dns_ips: vec![ip],
+        ..Default::default()
+    }, ModifyConfig {
+        dns_ips: vec![ip; 3],
+        keep_existing_dns: true,
+        ..Default::default()
+    }, ModifyConfig {
+        dns_ips: vec![ip],
+        dns_search: vec!["home.arpa".to_string(), "a.b".to_string()],
+        mtu: Some(1280),
+        set_other_flag: Some(true),
+        set_managed_flag: Some(false),
+        ..Default::default()
+    }, ModifyConfig {
+        dns_ips: vec![ip],
+        pref64: Pref64::new(Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0), 96),
+        ..Default::default()
+    }];
+}
+
+#[test]
+fn test_checksum_incremental_ra() {
+    for config in configs() {
+        let got = modify(PAYLOAD_RA, &config).unwrap();
+        assert_ne!(got, PAYLOAD_RA);
+        assert_eq!(got[RA_CHECKSUM_AT .. RA_CHECKSUM_AT + 2], full_checksum(&got, RA_CHECKSUM_AT));
+    }
+}
+
+#[test]
+fn test_checksum_incremental_dhcp() {
+    for config in configs() {
+        let got = modify(PAYLOAD_DHCP, &config).unwrap();
+        assert_ne!(got, PAYLOAD_DHCP);
+        assert_eq!(got[DHCP_CHECKSUM_AT .. DHCP_CHECKSUM_AT + 2], full_checksum(&got, DHCP_CHECKSUM_AT));
+    }
+}
+
+/// Removing an odd length option moves the options after it to odd offsets, so the
+/// checksum is recomputed.
+#[test]
+fn test_checksum_incremental_dhcp_misaligned() {
+    let mut packet = PAYLOAD_DHCP[.. 40 + 8 + 4].to_vec();
+
+    // Domain list with an odd length, then a server id and DNS
+    packet.extend([0x00, 0x18, 0x00, 0x03, 0x01, b'a', 0x00]);
+    packet.extend([0x00, 0x02, 0x00, 0x04, 0x00, 0x03, 0x00, 0x01]);
+    packet.extend([0x00, 0x17, 0x00, 0x10]);
+    packet.extend(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53).octets());
+    let len = (packet.len() - 40) as u16;
+    packet[4 .. 6].copy_from_slice(&len.to_be_bytes());
+    packet[44 .. 46].copy_from_slice(&len.to_be_bytes());
+    let checksum = full_checksum(&packet, DHCP_CHECKSUM_AT);
+    packet[DHCP_CHECKSUM_AT .. DHCP_CHECKSUM_AT + 2].copy_from_slice(&checksum);
+    let got = modify(&packet, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        dns_search: vec!["home.arpa".to_string()],
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(got[DHCP_CHECKSUM_AT .. DHCP_CHECKSUM_AT + 2], full_checksum(&got, DHCP_CHECKSUM_AT));
+}
+
+#[test]
+fn test_checksum_update() {
+    // Replace a word at an odd offset: [.., 0x12, 0x34, ..] -> [.., 0x56, 0x78, ..]
+    let old = [0x01, 0x12, 0x34, 0x02];
+    let new = [0x01, 0x56, 0x78, 0x02];
+    let full = |data: &[u8]| {
+        let mut sum64 = 0;
+        crate::ra_dns::checksum_roll(&mut sum64, data);
+        return crate::ra_dns::checksum_finish(sum64);
+    };
+    let mut update = ChecksumUpdate::default();
+    update.replace(1, &old[1 .. 3], &new[1 .. 3]);
+    assert_eq!(update.apply(full(&old)), Some(full(&new)));
+
+    // Needs recomputing
+    update.moved(4, 7);
+    assert_eq!(update.apply(full(&old)), None);
+    assert_eq!(ChecksumUpdate::default().apply([0, 0]), None);
+}
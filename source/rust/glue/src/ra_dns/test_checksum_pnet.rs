@@ -0,0 +1,108 @@
+//! Check `icmpv6_udp_checksum` against an independent implementation.
+use {
+    crate::ra_dns::{
+        icmpv6_udp_checksum,
+        pseudo_header_checksum,
+        test_util::{
+            PAYLOAD_DHCP,
+            PAYLOAD_RA,
+        },
+    },
+    pnet_packet::{
+        icmpv6::{
+            self,
+            Icmpv6Packet,
+        },
+        udp::{
+            self,
+            UdpPacket,
+        },
+    },
+    proptest::{
+        collection::vec,
+        prelude::*,
+    },
+    std::net::Ipv6Addr,
+};
+
+/// The offset of the checksum in the ICMPv6 and UDP headers.
+const ICMPV6_CHECKSUM_OFFSET: usize = 2;
+const UDP_CHECKSUM_OFFSET: usize = 6;
+
+/// The checksum pnet calculates for a packet without extension headers.
+fn pnet_checksum(packet: &[u8]) -> [u8; 2] {
+    let src = Ipv6Addr::from(<[u8; 16]>::try_from(&packet[8 .. 24]).unwrap());
+    let dst = Ipv6Addr::from(<[u8; 16]>::try_from(&packet[24 .. 40]).unwrap());
+    let checksum = match packet[6] {
+        58 => icmpv6::checksum(&Icmpv6Packet::new(&packet[40..]).unwrap(), &src, &dst),
+        17 => udp::ipv6_checksum(&UdpPacket::new(&packet[40..]).unwrap(), &src, &dst),
+        _ => unreachable!(),
+    };
+    return checksum.to_be_bytes();
+}
+
+/// Our checksum, zeroing the checksum field first.
+fn checksum(packet: &[u8], checksum_offset: usize) -> [u8; 2] {
+    let mut packet = packet.to_vec();
+    packet[40 + checksum_offset .. 40 + checksum_offset + 2].copy_from_slice(&[0, 0]);
+    return icmpv6_udp_checksum(&packet).unwrap();
+}
+
+/// An IPv6 packet with an upper layer of `next_header` and `body` (with the checksum
+/// field zeroed by the caller).
+fn packet(src: [u8; 16], dst: [u8; 16], next_header: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x60, 0, 0, 0];
+    out.extend((body.len() as u16).to_be_bytes());
+    out.extend([next_header, 0xff]);
+    out.extend(src);
+    out.extend(dst);
+    out.extend_from_slice(body);
+    return out;
+}
+
+#[test]
+fn test_checksum_pnet_ra() {
+    let want = [PAYLOAD_RA[40 + ICMPV6_CHECKSUM_OFFSET], PAYLOAD_RA[40 + ICMPV6_CHECKSUM_OFFSET + 1]];
+    assert_eq!(pnet_checksum(PAYLOAD_RA), want);
+    assert_eq!(checksum(PAYLOAD_RA, ICMPV6_CHECKSUM_OFFSET), want);
+}
+
+#[test]
+fn test_checksum_pnet_dhcp() {
+    // The capture's checksum is zero (not set), so only compare the calculations
+    assert_eq!(checksum(PAYLOAD_DHCP, UDP_CHECKSUM_OFFSET), pnet_checksum(PAYLOAD_DHCP));
+}
+
+#[test]
+fn test_checksum_pnet_dhcp_ext_header() {
+    // Insert a hop-by-hop header with a padding option before the UDP header
+    let mut packet = PAYLOAD_DHCP[.. 40].to_vec();
+    packet[6] = 0;
+    packet.extend([17, 0, 1, 4, 0, 0, 0, 0]);
+    packet.extend_from_slice(&PAYLOAD_DHCP[40..]);
+    let payload_len = (packet.len() - 40) as u16;
+    packet[4 .. 6].copy_from_slice(&payload_len.to_be_bytes());
+    let upper_len = PAYLOAD_DHCP.len() - 40;
+    assert_eq!(pseudo_header_checksum(&packet, 17, 8, upper_len), Some(pnet_checksum(PAYLOAD_DHCP)));
+    assert_eq!(icmpv6_udp_checksum(&packet), Some(pnet_checksum(PAYLOAD_DHCP)));
+}
+
+proptest!{
+    #[test]
+    fn prop_checksum_pnet_icmpv6(src in any::<[u8; 16]>(), dst in any::<[u8; 16]>(), body in vec(any::<u8>(), 4 .. 1500)) {
+        let mut body = body;
+        body[ICMPV6_CHECKSUM_OFFSET .. ICMPV6_CHECKSUM_OFFSET + 2].copy_from_slice(&[0, 0]);
+        let packet = packet(src, dst, 58, &body);
+        prop_assert_eq!(icmpv6_udp_checksum(&packet).unwrap(), pnet_checksum(&packet));
+    }
+
+    #[test]
+    fn prop_checksum_pnet_udp(src in any::<[u8; 16]>(), dst in any::<[u8; 16]>(), body in vec(any::<u8>(), 8 .. 1500)) {
+        let mut body = body;
+        let len = body.len() as u16;
+        body[4 .. 6].copy_from_slice(&len.to_be_bytes());
+        body[UDP_CHECKSUM_OFFSET .. UDP_CHECKSUM_OFFSET + 2].copy_from_slice(&[0, 0]);
+        let packet = packet(src, dst, 17, &body);
+        prop_assert_eq!(icmpv6_udp_checksum(&packet).unwrap(), pnet_checksum(&packet));
+    }
+}
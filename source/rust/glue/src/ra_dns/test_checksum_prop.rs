@@ -0,0 +1,76 @@
+use {
+    crate::ra_dns::{
+        checksum_finish,
+        checksum_roll,
+    },
+    proptest::{
+        collection::vec,
+        prelude::*,
+    },
+};
+
+/// Straightforward RFC 1071 checksum: big-endian words, odd byte padded with zero,
+/// carries folded back in as they happen.
+fn reference_checksum(bytes: &[u8]) -> [u8; 2] {
+    let mut sum = 0u32;
+    for pair in bytes.chunks(2) {
+        sum += u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]) as u32;
+        if sum > 0xffff {
+            sum = (sum & 0xffff) + 1;
+        }
+    }
+    return (!(sum as u16)).to_be_bytes();
+}
+
+fn checksum(bytes: &[u8]) -> [u8; 2] {
+    let mut sum64 = 0u64;
+    checksum_roll(&mut sum64, bytes);
+    return checksum_finish(sum64);
+}
+
+fn even_bytes() -> impl Strategy<Value = Vec<u8>> {
+    return (0usize .. 1024).prop_flat_map(|words| vec(any::<u8>(), words * 2));
+}
+
+fn odd_bytes() -> impl Strategy<Value = Vec<u8>> {
+    return (0usize .. 1024).prop_flat_map(|words| vec(any::<u8>(), words * 2 + 1));
+}
+
+proptest!{
+    #[test]
+    fn prop_checksum_even_matches_reference(bytes in even_bytes()) {
+        prop_assert_eq!(checksum(&bytes), reference_checksum(&bytes));
+    }
+
+    #[test]
+    fn prop_checksum_odd_matches_reference(bytes in odd_bytes()) {
+        prop_assert_eq!(checksum(&bytes), reference_checksum(&bytes));
+    }
+
+    #[test]
+    fn prop_checksum_split_matches_whole(bytes in even_bytes(), split in any::<usize>()) {
+        // Rolling in two parts split on a word boundary is the same as all at once
+        let split = if bytes.is_empty() {
+            0
+        } else {
+            (split % bytes.len()) & !1
+        };
+        let mut sum64 = 0u64;
+        checksum_roll(&mut sum64, &bytes[..split]);
+        checksum_roll(&mut sum64, &bytes[split..]);
+        prop_assert_eq!(checksum_finish(sum64), checksum(&bytes));
+    }
+
+    #[test]
+    fn prop_checksum_inserted_verifies(mut bytes in vec(any::<u8>(), 0 .. 2048), at in any::<usize>()) {
+        // Reserve a zeroed, word-aligned checksum field, fill it, then re-sum the whole
+        // buffer - a valid checksum sums to all ones, which finishes to zero
+        bytes.extend([0, 0]);
+        let at = (at % (bytes.len() - 1)) & !1;
+        bytes[at] = 0;
+        bytes[at + 1] = 0;
+        let sum = checksum(&bytes);
+        bytes[at .. at + 2].copy_from_slice(&sum);
+        prop_assert_eq!(checksum(&bytes), [0, 0]);
+    }
+}
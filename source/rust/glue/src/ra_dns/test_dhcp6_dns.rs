@@ -0,0 +1,45 @@
+use {
+    crate::ra_dns::build_dhcp6_dns_option,
+    std::net::Ipv6Addr,
+};
+
+#[test]
+fn test_build_dhcp6_dns_option_one() {
+    let got = build_dhcp6_dns_option(&[Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53)]);
+    assert_eq!(got, vec![
+        // Code
+        0x00,
+        0x17,
+        // Length
+        0x00,
+        0x10,
+        // Address
+        0x20,
+        0x01,
+        0x0d,
+        0xb8,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x53,
+    ]);
+}
+
+#[test]
+fn test_build_dhcp6_dns_option_three() {
+    let addrs = [1, 2, 3].map(|i| Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, i));
+    let got = build_dhcp6_dns_option(&addrs);
+    assert_eq!(got.len(), 4 + 48);
+    assert_eq!(&got[.. 4], &[0x00, 0x17, 0x00, 0x30]);
+    for (i, addr) in addrs.iter().enumerate() {
+        assert_eq!(got[4 + 16 * i .. 4 + 16 * (i + 1)], addr.octets());
+    }
+}
@@ -0,0 +1,150 @@
+use {
+    crate::ra_dns::{
+        list_options,
+        modify,
+        options::Dhcp6Options,
+        ModifyConfig,
+        ModifyError,
+    },
+    std::net::Ipv6Addr,
+};
+
+#[test]
+fn test_dhcp6_options() {
+    let data = [
+        // Server identifier, length 2
+        0x00,
+        0x02,
+        0x00,
+        0x02,
+        0xab,
+        0xcd,
+        // Rapid commit, length 0
+        0x00,
+        0x0e,
+        0x00,
+        0x00,
+    ];
+    let got = Dhcp6Options::new(&data).collect::<Vec<_>>();
+    assert_eq!(got, vec![Some((2, &data[4 .. 6])), Some((14, &data[10 .. 10]))]);
+}
+
+#[test]
+fn test_dhcp6_options_overrun() {
+    // Second option claims 16 bytes, only 2 remain
+    let data = [0x00, 0x0e, 0x00, 0x00, 0x00, 0x17, 0x00, 0x10, 0x20, 0x01];
+    assert_eq!(Dhcp6Options::new(&data).collect::<Vec<_>>(), vec![Some((14, &data[4 .. 4])), None]);
+
+    // Truncated header
+    assert_eq!(Dhcp6Options::new(&[0x00, 0x17, 0x00]).collect::<Vec<_>>(), vec![None]);
+}
+
+#[test]
+fn test_dhcp6_options_overrun_modify() {
+    let mut payload = vec![
+        // IPv6
+        0x60,
+        0x00,
+        0x00,
+        0x00,
+        // Length
+        0x00,
+        0x14,
+        0x11,
+        0x01,
+    ];
+    payload.extend(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1).octets());
+    payload.extend(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2).octets());
+    payload.extend([
+        // UDP
+        0x02,
+        0x23,
+        0x02,
+        0x22,
+        0x00,
+        0x14,
+        0x00,
+        0x00,
+        // DHCPv6 reply
+        0x07,
+        0x00,
+        0x00,
+        0x01,
+        // DNS option, claiming 16 bytes with 4 present
+        0x00,
+        0x17,
+        0x00,
+        0x10,
+        0x20,
+        0x01,
+        0x0d,
+        0xb8,
+    ]);
+    assert_eq!(modify(&payload, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        keep_existing_dns: true,
+        ..Default::default()
+    }), Err(ModifyError::BadOptionLength));
+}
+
+#[test]
+fn test_dhcp6_options_zero_length() {
+    // Only the header, each still advances 4 bytes
+    let data = [0x00, 0x0e, 0x00, 0x00].repeat(1000);
+    assert_eq!(Dhcp6Options::new(&data).count(), 1000);
+
+    // Then a partial header
+    let mut data = data;
+    data.extend([0x00, 0x0e]);
+    assert_eq!(Dhcp6Options::new(&data).last(), Some(None));
+}
+
+#[test]
+fn test_dhcp6_options_zero_length_modify() {
+    let mut payload = vec![
+        // IPv6
+        0x60,
+        0x00,
+        0x00,
+        0x00,
+        // Length
+        0x00,
+        0x00,
+        0x11,
+        0x01,
+    ];
+    payload.extend(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1).octets());
+    payload.extend(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2).octets());
+    payload.extend([
+        // UDP
+        0x02,
+        0x23,
+        0x02,
+        0x22,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        // DHCPv6 reply
+        0x07,
+        0x00,
+        0x00,
+        0x01,
+    ]);
+
+    // Zero length options, including a DNS option with no addresses
+    for _ in 0 .. 100 {
+        payload.extend([0x00, 0x0e, 0x00, 0x00]);
+    }
+    payload.extend([0x00, 0x17, 0x00, 0x00]);
+    let len = (payload.len() - 40) as u16;
+    payload[4 .. 6].copy_from_slice(&len.to_be_bytes());
+    payload[44 .. 46].copy_from_slice(&len.to_be_bytes());
+    assert_eq!(list_options(&payload, &ModifyConfig::default()).unwrap().len(), 101);
+    let got = modify(&payload, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(list_options(&got, &ModifyConfig::default()).unwrap().len(), 101);
+    assert_eq!(got.len(), payload.len() + 16);
+}
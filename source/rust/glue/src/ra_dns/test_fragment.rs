@@ -0,0 +1,394 @@
+use {
+    crate::ra_dns::{
+        fragment::{
+            is_fragment,
+            Reassembler,
+            Reassembly,
+        },
+        icmpv6_udp_checksum,
+        modify,
+        test_util::PAYLOAD_DHCP,
+        ModifyConfig,
+        ModifyError,
+    },
+    std::{
+        net::Ipv6Addr,
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+};
+
+/// `PAYLOAD_DHCP` split into two fragments.
+const PAYLOAD_DHCP_FRAG1: &[u8] = &[
+    // IPv6
+    0x6b,
+    0x80,
+    0x00,
+    0x00,
+    // Length
+    0x00,
+    0x60,
+    // Next header (fragment), hop limit
+    0x2c,
+    0x01,
+    // Source
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x40,
+    0xff,
+    0xfe,
+    0x12,
+    0x20,
+    0x0a,
+    // Destination
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0xb2,
+    0x6e,
+    0xbf,
+    0xff,
+    0xfe,
+    0x39,
+    0xbf,
+    0x7b,
+    // Fragment: next header (UDP), reserved
+    0x11,
+    0x00,
+    // Offset, more fragments
+    0x00,
+    0x01,
+    // Identification
+    0x12,
+    0x34,
+    0x56,
+    0x78,
+    // Data (UDP header, DHCPv6 reply)
+    0x02,
+    0x23,
+    0x02,
+    0x22,
+    0x00,
+    0x91,
+    0x00,
+    0x00,
+    0x07,
+    0x56,
+    0x20,
+    0xfd,
+    0x00,
+    0x02,
+    0x00,
+    0x0a,
+    0x00,
+    0x03,
+    0x00,
+    0x01,
+    0x00,
+    0x19,
+    0xaa,
+    0xbc,
+    0xfa,
+    0x1b,
+    0x00,
+    0x01,
+    0x00,
+    0x0e,
+    0x00,
+    0x02,
+    0x00,
+    0x00,
+    0xab,
+    0x11,
+    0xfc,
+    0x72,
+    0xdb,
+    0x43,
+    0x6a,
+    0xc5,
+    0xca,
+    0x1f,
+    0x00,
+    0x17,
+    0x00,
+    0x20,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x7f,
+    0x01,
+    0x00,
+    0x0b,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x03,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x7f,
+    0x01,
+    0x00,
+    0x0a,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x03,
+    0x00,
+    0x18,
+    0x00,
+    0x19,
+    0x0a,
+    0x66,
+    0x6c,
+    0x65,
+];
+
+const PAYLOAD_DHCP_FRAG2: &[u8] = &[
+    // IPv6
+    0x6b,
+    0x80,
+    0x00,
+    0x00,
+    // Length
+    0x00,
+    0x41,
+    // Next header (fragment), hop limit
+    0x2c,
+    0x01,
+    // Source
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x40,
+    0xff,
+    0xfe,
+    0x12,
+    0x20,
+    0x0a,
+    // Destination
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0xb2,
+    0x6e,
+    0xbf,
+    0xff,
+    0xfe,
+    0x39,
+    0xbf,
+    0x7b,
+    // Fragment: next header (UDP), reserved
+    0x11,
+    0x00,
+    // Offset, more fragments
+    0x00,
+    0x58,
+    // Identification
+    0x12,
+    0x34,
+    0x56,
+    0x78,
+    // Data
+    0x74,
+    0x73,
+    0x2d,
+    0x65,
+    0x61,
+    0x73,
+    0x74,
+    0x02,
+    0x6a,
+    0x70,
+    0x00,
+    0x05,
+    0x69,
+    0x70,
+    0x74,
+    0x76,
+    0x66,
+    0x02,
+    0x6a,
+    0x70,
+    0x00,
+    0x00,
+    0x1f,
+    0x00,
+    0x20,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x11,
+    0x02,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x0b,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x11,
+    0x02,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x0a,
+];
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+fn new_reassembler() -> Reassembler<u32> {
+    return Reassembler::new(4, 4, TIMEOUT);
+}
+
+#[test]
+fn test_fragment_reassemble() {
+    assert!(is_fragment(PAYLOAD_DHCP_FRAG1));
+    assert!(is_fragment(PAYLOAD_DHCP_FRAG2));
+    assert!(!is_fragment(PAYLOAD_DHCP));
+
+    // Out of order
+    let now = Instant::now();
+    let mut reassembler = new_reassembler();
+    assert!(matches!(reassembler.add(now, PAYLOAD_DHCP_FRAG2, 2), Ok(Reassembly::Incomplete)));
+    let Ok(Reassembly::Complete { datagram, items }) = reassembler.add(now, PAYLOAD_DHCP_FRAG1, 1) else {
+        panic!();
+    };
+    assert_eq!(datagram.packet, PAYLOAD_DHCP);
+    assert_eq!(items, vec![2, 1]);
+}
+
+#[test]
+fn test_fragment_modify_refragment() {
+    let now = Instant::now();
+    let mut reassembler = new_reassembler();
+    assert!(matches!(reassembler.add(now, PAYLOAD_DHCP_FRAG1, 1), Ok(Reassembly::Incomplete)));
+    let Ok(Reassembly::Complete { mut datagram, .. }) = reassembler.add(now, PAYLOAD_DHCP_FRAG2, 2) else {
+        panic!();
+    };
+
+    // Grows by one address
+    datagram.packet = modify(&datagram.packet, &ModifyConfig {
+        dns_ips: vec![
+            Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8),
+            Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 9),
+            Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 10)
+        ],
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(datagram.packet.len(), PAYLOAD_DHCP.len() + 16);
+    let fragments = datagram.fragment().unwrap();
+    assert_eq!(fragments.len(), 2);
+    for fragment in &fragments {
+        assert!(fragment.len() <= PAYLOAD_DHCP_FRAG1.len());
+
+        // Same identification
+        assert_eq!(fragment[44 .. 48], PAYLOAD_DHCP_FRAG1[44 .. 48]);
+    }
+
+    // Reassembles to the rewritten packet
+    let mut reassembler = new_reassembler();
+    assert!(matches!(reassembler.add(now, &fragments[0], 1), Ok(Reassembly::Incomplete)));
+    let Ok(Reassembly::Complete { datagram: got, .. }) = reassembler.add(now, &fragments[1], 2) else {
+        panic!();
+    };
+    assert_eq!(got.packet, datagram.packet);
+    assert_eq!(icmpv6_udp_checksum(&got.packet), Some([0, 0]));
+}
+
+#[test]
+fn test_fragment_overlap() {
+    let now = Instant::now();
+    let mut reassembler = new_reassembler();
+    assert!(matches!(reassembler.add(now, PAYLOAD_DHCP_FRAG1, 1), Ok(Reassembly::Incomplete)));
+    let Ok(Reassembly::Failed { error, items }) = reassembler.add(now, PAYLOAD_DHCP_FRAG1, 2) else {
+        panic!();
+    };
+    assert_eq!(error, ModifyError::BadFragments);
+    assert_eq!(items, vec![1, 2]);
+
+    // Discarded, starts over
+    assert!(matches!(reassembler.add(now, PAYLOAD_DHCP_FRAG2, 3), Ok(Reassembly::Incomplete)));
+}
+
+#[test]
+fn test_fragment_expire() {
+    let start = Instant::now();
+    let mut reassembler = new_reassembler();
+    assert!(matches!(reassembler.add(start, PAYLOAD_DHCP_FRAG1, 1), Ok(Reassembly::Incomplete)));
+    assert_eq!(reassembler.expire(start + TIMEOUT - Duration::from_secs(1)), Vec::<u32>::new());
+    assert_eq!(reassembler.expire(start + TIMEOUT), vec![1]);
+    assert!(matches!(reassembler.add(start + TIMEOUT, PAYLOAD_DHCP_FRAG2, 2), Ok(Reassembly::Incomplete)));
+}
+
+#[test]
+fn test_fragment_limits() {
+    let start = Instant::now();
+
+    // Too many datagrams, the oldest is discarded to make room
+    let mut reassembler = Reassembler::new(1, 4, TIMEOUT);
+    let mut other = PAYLOAD_DHCP_FRAG2.to_vec();
+    other[47] ^= 1;
+    assert!(matches!(reassembler.add(start, PAYLOAD_DHCP_FRAG1, 1), Ok(Reassembly::Incomplete)));
+    assert_eq!(reassembler.expire(start), vec![1]);
+    assert!(matches!(reassembler.add(start, &other, 2), Ok(Reassembly::Incomplete)));
+
+    // Too many fragments
+    let mut reassembler = Reassembler::new(4, 1, TIMEOUT);
+    assert!(matches!(reassembler.add(start, PAYLOAD_DHCP_FRAG1, 1), Ok(Reassembly::Incomplete)));
+    assert!(matches!(reassembler.add(start, PAYLOAD_DHCP_FRAG2, 2), Ok(Reassembly::Failed { .. })));
+
+    // Not a fragment
+    assert!(matches!(reassembler.add(start, PAYLOAD_DHCP, 3), Err((ModifyError::NotApplicable, 3))));
+}
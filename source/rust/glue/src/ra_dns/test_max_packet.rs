@@ -0,0 +1,48 @@
+use {
+    crate::ra_dns::{
+        modify,
+        test_util::PAYLOAD_RA,
+        ModifyConfig,
+        ModifyError,
+    },
+    std::net::Ipv6Addr,
+};
+
+const ROUTER_DNS: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53);
+
+fn config(dns_search: &[&str], max_packet: Option<usize>) -> ModifyConfig {
+    return ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x53), Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x54)],
+        dns_search: dns_search.iter().map(|s| s.to_string()).collect(),
+        mtu: Some(1400),
+        max_packet,
+        ..Default::default()
+    };
+}
+
+#[test]
+fn test_max_packet() {
+    let search = ["a-rather-long-search-domain.example.com", "another.example.org"];
+    let with_search = modify(PAYLOAD_RA, &config(&search, None)).unwrap().len();
+    let without_search = modify(PAYLOAD_RA, &config(&[], None)).unwrap().len();
+    assert!(without_search > PAYLOAD_RA.len());
+    assert!(with_search > without_search);
+
+    // Room for the RDNSS but not the search list
+    assert_eq!(modify(PAYLOAD_RA, &config(&search, Some(without_search))), Err(ModifyError::TooLarge));
+    assert_eq!(modify(PAYLOAD_RA, &config(&[], Some(without_search))).unwrap().len(), without_search);
+    assert_eq!(modify(PAYLOAD_RA, &config(&search, Some(with_search))).unwrap().len(), with_search);
+}
+
+#[test]
+fn test_max_packet_not_grown() {
+    // Already over the limit, but rewriting doesn't make it any larger
+    let got = modify(PAYLOAD_RA, &ModifyConfig {
+        dns_ips: vec![ROUTER_DNS],
+        mtu: Some(1400),
+        max_packet: Some(40),
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(got.len(), PAYLOAD_RA.len());
+    assert_ne!(got, PAYLOAD_RA);
+}
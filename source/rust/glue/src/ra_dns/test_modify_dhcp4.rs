@@ -0,0 +1,744 @@
+use {
+    crate::ra_dns::{
+        modify,
+        ModifyConfig,
+        ModifyError,
+    },
+    std::net::Ipv4Addr,
+};
+
+/// A DHCPACK offering `192.168.1.1` and `8.8.8.8` as DNS servers.
+const PAYLOAD_DHCP4_ACK: &[u8] = &[
+    // IPv4
+    0x45,
+    0x10,
+    // Total length
+    0x01,
+    0x48,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x80,
+    0x11,
+    // Header checksum
+    0xb5,
+    0xdf,
+    // Source, destination
+    0xc0,
+    0xa8,
+    0x01,
+    0x01,
+    0xc0,
+    0xa8,
+    0x01,
+    0x64,
+    // UDP
+    0x00,
+    0x43,
+    0x00,
+    0x44,
+    // Length
+    0x01,
+    0x34,
+    // Checksum
+    0x3d,
+    0xc1,
+    // BOOTP
+    0x02,
+    0x01,
+    0x06,
+    0x00,
+    0x39,
+    0x03,
+    0xf3,
+    0x26,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0xc0,
+    0xa8,
+    0x01,
+    0x64,
+    0xc0,
+    0xa8,
+    0x01,
+    0x01,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    // Client hardware address
+    0x52,
+    0x54,
+    0x00,
+    0x12,
+    0x34,
+    0x56,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    // Server name, boot file
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    // Magic cookie
+    0x63,
+    0x82,
+    0x53,
+    0x63,
+    // Message type option
+    0x35,
+    0x01,
+    0x05,
+    // Server identifier option
+    0x36,
+    0x04,
+    0xc0,
+    0xa8,
+    0x01,
+    0x01,
+    // Lease time option
+    0x33,
+    0x04,
+    0x00,
+    0x01,
+    0x51,
+    0x80,
+    // Subnet mask option
+    0x01,
+    0x04,
+    0xff,
+    0xff,
+    0xff,
+    0x00,
+    // Router option
+    0x03,
+    0x04,
+    0xc0,
+    0xa8,
+    0x01,
+    0x01,
+    // DNS option
+    0x06,
+    0x08,
+    0xc0,
+    0xa8,
+    0x01,
+    0x01,
+    0x08,
+    0x08,
+    0x08,
+    0x08,
+    // Domain name option
+    0x0f,
+    0x03,
+    0x6c,
+    0x61,
+    0x6e,
+    // End
+    0xff,
+    // Padding
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+];
+
+#[test]
+fn test_modify_dhcp4_ack() {
+    let got = modify(PAYLOAD_DHCP4_ACK, &ModifyConfig {
+        dns_ips_v4: vec![Ipv4Addr::new(10, 0, 0, 53)],
+        ..Default::default()
+    }).unwrap();
+    let mut want = vec![
+        // IPv4
+        0x45,
+        0x10,
+        // Total length
+        0x01,
+        0x44,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x80,
+        0x11,
+        // Header checksum
+        0xb5,
+        0xe3,
+        // Source, destination
+        0xc0,
+        0xa8,
+        0x01,
+        0x01,
+        0xc0,
+        0xa8,
+        0x01,
+        0x64,
+        // UDP
+        0x00,
+        0x43,
+        0x00,
+        0x44,
+        // Length
+        0x01,
+        0x30,
+        // Checksum
+        0xef,
+        0x67,
+        // BOOTP
+        0x02,
+        0x01,
+        0x06,
+        0x00,
+        0x39,
+        0x03,
+        0xf3,
+        0x26,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0xc0,
+        0xa8,
+        0x01,
+        0x64,
+        0xc0,
+        0xa8,
+        0x01,
+        0x01,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        // Client hardware address
+        0x52,
+        0x54,
+        0x00,
+        0x12,
+        0x34,
+        0x56,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        // Server name, boot file
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        // Magic cookie
+        0x63,
+        0x82,
+        0x53,
+        0x63,
+        // Message type option
+        0x35,
+        0x01,
+        0x05,
+        // Server identifier option
+        0x36,
+        0x04,
+        0xc0,
+        0xa8,
+        0x01,
+        0x01,
+        // Lease time option
+        0x33,
+        0x04,
+        0x00,
+        0x01,
+        0x51,
+        0x80,
+        // Subnet mask option
+        0x01,
+        0x04,
+        0xff,
+        0xff,
+        0xff,
+        0x00,
+        // Router option
+        0x03,
+        0x04,
+        0xc0,
+        0xa8,
+        0x01,
+        0x01,
+        // Domain name option
+        0x0f,
+        0x03,
+        0x6c,
+        0x61,
+        0x6e,
+        // DNS option
+        0x06,
+        0x04,
+        0x0a,
+        0x00,
+        0x00,
+        0x35,
+        // End
+        0xff,
+        // Padding
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+
+#[test]
+fn test_modify_dhcp4_other_message_type() {
+    // DHCPNAK, passed through unchanged
+    let mut payload = PAYLOAD_DHCP4_ACK.to_vec();
+    payload[20 + 8 + 236 + 4 + 2] = 6;
+    assert_eq!(modify(&payload, &ModifyConfig {
+        dns_ips_v4: vec![Ipv4Addr::new(10, 0, 0, 53)],
+        ..Default::default()
+    }), Ok(payload.clone()));
+}
+
+#[test]
+fn test_modify_dhcp4_not_configured() {
+    assert_eq!(modify(PAYLOAD_DHCP4_ACK, &ModifyConfig::default()), Err(ModifyError::NotApplicable));
+}
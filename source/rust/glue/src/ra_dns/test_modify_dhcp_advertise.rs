@@ -1,9 +1,13 @@
 use {
-    crate::manglelib::modify,
+    crate::ra_dns::{
+        modify,
+        ModifyConfig,
+        ModifyError,
+    },
     std::net::Ipv6Addr,
 };
 
-const PAYLOAD_DHCP1: &[u8] = &[
+const PAYLOAD_DHCP_ADVERTISE: &[u8] = &[
     // IPv6
     0x6b,
     0x80,
@@ -55,8 +59,8 @@ const PAYLOAD_DHCP1: &[u8] = &[
     // Checksum (zeroed)
     0x00,
     0x00,
-    // DHCPv6
-    0x07,
+    // DHCPv6 Advertise
+    0x02,
     0x56,
     0x20,
     0xfd,
@@ -196,8 +200,11 @@ const PAYLOAD_DHCP1: &[u8] = &[
 ];
 
 #[test]
-fn test_modify_dhcp_ex1() {
-    let got = modify(PAYLOAD_DHCP1, Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8), None).unwrap();
+fn test_modify_dhcp_advertise() {
+    let got = modify(PAYLOAD_DHCP_ADVERTISE, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        ..Default::default()
+    }).unwrap();
     let mut want = vec![
         // IPv6
         0x6b,
@@ -250,10 +257,10 @@ fn test_modify_dhcp_ex1() {
         0x00,
         0x81,
         // Checksum
-        0xe6,
+        0xeb,
         0xc9,
-        // DHCPv6
-        0x07,
+        // DHCPv6 Advertise
+        0x02,
         0x56,
         0x20,
         0xfd,
@@ -358,25 +365,25 @@ fn test_modify_dhcp_ex1() {
         0x00,
         0x17,
         // Length 16
-        0,
-        16,
+        0x00,
+        0x10,
         // IP
-        0,
-        1,
-        0,
-        2,
-        0,
-        3,
-        0,
-        4,
-        0,
-        5,
-        0,
-        6,
-        0,
-        7,
-        0,
-        8
+        0x00,
+        0x01,
+        0x00,
+        0x02,
+        0x00,
+        0x03,
+        0x00,
+        0x04,
+        0x00,
+        0x05,
+        0x00,
+        0x06,
+        0x00,
+        0x07,
+        0x00,
+        0x08,
     ];
     if want.len() < got.len() {
         want.resize(got.len(), 0);
@@ -392,3 +399,40 @@ fn test_modify_dhcp_ex1() {
     }
     assert_eq!(got, want);
 }
+
+#[test]
+fn test_modify_dhcp_advertise_reply_only() {
+    let got = modify(PAYLOAD_DHCP_ADVERTISE, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        dhcp_message_types: Some(vec![7]),
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(got, PAYLOAD_DHCP_ADVERTISE);
+}
+
+/// The advertise, sent to port 5353 instead of the DHCPv6 client port.
+fn other_port() -> Vec<u8> {
+    let mut packet = PAYLOAD_DHCP_ADVERTISE.to_vec();
+    packet[40 + 2 .. 40 + 4].copy_from_slice(&5353u16.to_be_bytes());
+    return packet;
+}
+
+#[test]
+fn test_modify_dhcp_other_port() {
+    let got = modify(&other_port(), &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        ..Default::default()
+    });
+    assert_eq!(got, Err(ModifyError::NotApplicable));
+}
+
+#[test]
+fn test_modify_dhcp_ports_override() {
+    let got = modify(&other_port(), &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        dhcp_ports: Some(vec![547, 5353]),
+        ..Default::default()
+    }).unwrap();
+    assert_ne!(got, other_port());
+    assert_eq!(&got[40 + 2 .. 40 + 4], &5353u16.to_be_bytes());
+}
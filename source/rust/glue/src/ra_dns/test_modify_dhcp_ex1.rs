@@ -0,0 +1,880 @@
+use {
+    crate::ra_dns::{
+        icmpv6_udp_checksum,
+        list_options,
+        message_kind,
+        modify,
+        ra_source,
+        MessageKind,
+        ModifyConfig,
+    },
+    std::net::Ipv6Addr,
+};
+
+const PAYLOAD_DHCP1: &[u8] = &[
+    // IPv6
+    0x6b,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x91,
+    0x11,
+    0x01,
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x40,
+    0xff,
+    0xfe,
+    0x12,
+    0x20,
+    0x0a,
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0xb2,
+    0x6e,
+    0xbf,
+    0xff,
+    0xfe,
+    0x39,
+    0xbf,
+    0x7b,
+    // UDP
+    0x02,
+    0x23,
+    0x02,
+    0x22,
+    0x00,
+    0x91,
+    // Checksum (zeroed)
+    0x00,
+    0x00,
+    // DHCPv6
+    0x07,
+    0x56,
+    0x20,
+    0xfd,
+    0x00,
+    0x02,
+    0x00,
+    0x0a,
+    0x00,
+    0x03,
+    0x00,
+    0x01,
+    0x00,
+    0x19,
+    0xaa,
+    0xbc,
+    0xfa,
+    0x1b,
+    0x00,
+    0x01,
+    0x00,
+    0x0e,
+    0x00,
+    0x02,
+    0x00,
+    0x00,
+    0xab,
+    0x11,
+    0xfc,
+    0x72,
+    0xdb,
+    0x43,
+    0x6a,
+    0xc5,
+    0xca,
+    0x1f,
+    0x00,
+    0x17,
+    0x00,
+    0x20,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x7f,
+    0x01,
+    0x00,
+    0x0b,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x03,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x7f,
+    0x01,
+    0x00,
+    0x0a,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x03,
+    0x00,
+    0x18,
+    0x00,
+    0x19,
+    0x0a,
+    0x66,
+    0x6c,
+    0x65,
+    0x74,
+    0x73,
+    0x2d,
+    0x65,
+    0x61,
+    0x73,
+    0x74,
+    0x02,
+    0x6a,
+    0x70,
+    0x00,
+    0x05,
+    0x69,
+    0x70,
+    0x74,
+    0x76,
+    0x66,
+    0x02,
+    0x6a,
+    0x70,
+    0x00,
+    0x00,
+    0x1f,
+    0x00,
+    0x20,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x11,
+    0x02,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x0b,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x11,
+    0x02,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x0a,
+];
+
+#[test]
+fn test_modify_dhcp_ex1() {
+    let got = modify(PAYLOAD_DHCP1, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        ..Default::default()
+    }).unwrap();
+    let mut want = vec![
+        // IPv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        // Length
+        0x00,
+        0x81,
+        0x11,
+        0x01,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x40,
+        0xff,
+        0xfe,
+        0x12,
+        0x20,
+        0x0a,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0xb2,
+        0x6e,
+        0xbf,
+        0xff,
+        0xfe,
+        0x39,
+        0xbf,
+        0x7b,
+        // UDP
+        0x02,
+        0x23,
+        0x02,
+        0x22,
+        // UDP length
+        0x00,
+        0x81,
+        // Checksum
+        0xe6,
+        0xc9,
+        // DHCPv6
+        0x07,
+        0x56,
+        0x20,
+        0xfd,
+        0x00,
+        0x02,
+        0x00,
+        0x0a,
+        0x00,
+        0x03,
+        0x00,
+        0x01,
+        0x00,
+        0x19,
+        0xaa,
+        0xbc,
+        0xfa,
+        0x1b,
+        0x00,
+        0x01,
+        0x00,
+        0x0e,
+        0x00,
+        0x02,
+        0x00,
+        0x00,
+        0xab,
+        0x11,
+        0xfc,
+        0x72,
+        0xdb,
+        0x43,
+        0x6a,
+        0xc5,
+        0xca,
+        0x1f,
+        0x00,
+        0x18,
+        0x00,
+        0x19,
+        0x0a,
+        0x66,
+        0x6c,
+        0x65,
+        0x74,
+        0x73,
+        0x2d,
+        0x65,
+        0x61,
+        0x73,
+        0x74,
+        0x02,
+        0x6a,
+        0x70,
+        0x00,
+        0x05,
+        0x69,
+        0x70,
+        0x74,
+        0x76,
+        0x66,
+        0x02,
+        0x6a,
+        0x70,
+        0x00,
+        0x00,
+        0x1f,
+        0x00,
+        0x20,
+        0x24,
+        0x04,
+        0x01,
+        0xa8,
+        0x11,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x0b,
+        0x24,
+        0x04,
+        0x01,
+        0xa8,
+        0x11,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x0a,
+        // DNS option
+        0x00,
+        0x17,
+        // Length 16
+        0,
+        16,
+        // IP
+        0,
+        1,
+        0,
+        2,
+        0,
+        3,
+        0,
+        4,
+        0,
+        5,
+        0,
+        6,
+        0,
+        7,
+        0,
+        8
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_modify_dhcp_ex1_two_ips() {
+    let got = modify(PAYLOAD_DHCP1, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8), Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 9)],
+        ..Default::default()
+    }).unwrap();
+    let mut want = vec![
+        // IPv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        // Length
+        0x00,
+        0x91,
+        0x11,
+        0x01,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x40,
+        0xff,
+        0xfe,
+        0x12,
+        0x20,
+        0x0a,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0xb2,
+        0x6e,
+        0xbf,
+        0xff,
+        0xfe,
+        0x39,
+        0xbf,
+        0x7b,
+        // UDP
+        0x02,
+        0x23,
+        0x02,
+        0x22,
+        // UDP length
+        0x00,
+        0x91,
+        // Checksum
+        0xb1,
+        0xa9,
+        // DHCPv6
+        0x07,
+        0x56,
+        0x20,
+        0xfd,
+        0x00,
+        0x02,
+        0x00,
+        0x0a,
+        0x00,
+        0x03,
+        0x00,
+        0x01,
+        0x00,
+        0x19,
+        0xaa,
+        0xbc,
+        0xfa,
+        0x1b,
+        0x00,
+        0x01,
+        0x00,
+        0x0e,
+        0x00,
+        0x02,
+        0x00,
+        0x00,
+        0xab,
+        0x11,
+        0xfc,
+        0x72,
+        0xdb,
+        0x43,
+        0x6a,
+        0xc5,
+        0xca,
+        0x1f,
+        0x00,
+        0x18,
+        0x00,
+        0x19,
+        0x0a,
+        0x66,
+        0x6c,
+        0x65,
+        0x74,
+        0x73,
+        0x2d,
+        0x65,
+        0x61,
+        0x73,
+        0x74,
+        0x02,
+        0x6a,
+        0x70,
+        0x00,
+        0x05,
+        0x69,
+        0x70,
+        0x74,
+        0x76,
+        0x66,
+        0x02,
+        0x6a,
+        0x70,
+        0x00,
+        0x00,
+        0x1f,
+        0x00,
+        0x20,
+        0x24,
+        0x04,
+        0x01,
+        0xa8,
+        0x11,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x0b,
+        0x24,
+        0x04,
+        0x01,
+        0xa8,
+        0x11,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x0a,
+        // DNS option
+        0x00,
+        0x17,
+        // Length 32
+        0x00,
+        0x20,
+        // IPs
+        0x00,
+        0x01,
+        0x00,
+        0x02,
+        0x00,
+        0x03,
+        0x00,
+        0x04,
+        0x00,
+        0x05,
+        0x00,
+        0x06,
+        0x00,
+        0x07,
+        0x00,
+        0x08,
+        0x00,
+        0x01,
+        0x00,
+        0x02,
+        0x00,
+        0x03,
+        0x00,
+        0x04,
+        0x00,
+        0x05,
+        0x00,
+        0x06,
+        0x00,
+        0x07,
+        0x00,
+        0x09,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_modify_dhcp_ex1_list_options() {
+    assert_eq!(list_options(PAYLOAD_DHCP1, &ModifyConfig::default()), Some(vec![0x02, 0x01, 0x17, 0x18, 0x1f]));
+    let got = modify(PAYLOAD_DHCP1, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(list_options(&got, &ModifyConfig::default()), Some(vec![0x02, 0x01, 0x18, 0x1f, 0x17]));
+}
+
+#[test]
+fn test_modify_dhcp_ex1_message_kind() {
+    assert_eq!(message_kind(PAYLOAD_DHCP1, &ModifyConfig::default()), Some(MessageKind::Dhcp6(7)));
+}
+
+#[test]
+fn test_modify_dhcp_ex1_other_ports() {
+    // Other UDP isn't described as DHCPv6, same as `modify`
+    let config = ModifyConfig {
+        dhcp_ports: Some(vec![53]),
+        ..Default::default()
+    };
+    assert_eq!(message_kind(PAYLOAD_DHCP1, &config), None);
+    assert_eq!(list_options(PAYLOAD_DHCP1, &config), None);
+}
+
+#[test]
+fn test_modify_dhcp_ex1_keep_existing() {
+    let got = modify(PAYLOAD_DHCP1, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        keep_existing_dns: true,
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(list_options(&got, &ModifyConfig::default()), Some(vec![0x02, 0x01, 0x18, 0x1f, 0x17]));
+
+    // One DNS option, the 2 existing ips then ours
+    let dns = &got[got.len() - 4 - 48..];
+    assert_eq!(&dns[..4], &[0x00, 0x17, 0x00, 0x30]);
+    assert_eq!(&dns[4 .. 20], &"2404:1a8:7f01:b::3".parse::<Ipv6Addr>().unwrap().octets());
+    assert_eq!(&dns[20 .. 36], &"2404:1a8:7f01:a::3".parse::<Ipv6Addr>().unwrap().octets());
+    assert_eq!(&dns[36..], &Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8).octets());
+
+    // Lengths and checksum
+    assert_eq!(u16::from_be_bytes([got[4], got[5]]) as usize, got.len() - 40);
+    assert_eq!(u16::from_be_bytes([got[44], got[45]]) as usize, got.len() - 40);
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+}
+
+#[test]
+fn test_modify_dhcp_ex1_ra_source() {
+    assert_eq!(ra_source(PAYLOAD_DHCP1), None);
+}
+
+#[test]
+fn test_modify_dhcp_ex1_dns_search() {
+    let got = modify(PAYLOAD_DHCP1, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        dns_search: vec!["lan.example.".to_string()],
+        ..Default::default()
+    }).unwrap();
+    let mut want = vec![
+        // IPv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        // Length
+        0x00,
+        0x75,
+        0x11,
+        0x01,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x40,
+        0xff,
+        0xfe,
+        0x12,
+        0x20,
+        0x0a,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0xb2,
+        0x6e,
+        0xbf,
+        0xff,
+        0xfe,
+        0x39,
+        0xbf,
+        0x7b,
+        // UDP
+        0x02,
+        0x23,
+        0x02,
+        0x22,
+        // Length
+        0x00,
+        0x75,
+        // Checksum
+        0xbc,
+        0xd2,
+        // DHCPv6
+        0x07,
+        0x56,
+        0x20,
+        0xfd,
+        // Kept options
+        0x00,
+        0x02,
+        0x00,
+        0x0a,
+        0x00,
+        0x03,
+        0x00,
+        0x01,
+        0x00,
+        0x19,
+        0xaa,
+        0xbc,
+        0xfa,
+        0x1b,
+        0x00,
+        0x01,
+        0x00,
+        0x0e,
+        0x00,
+        0x02,
+        0x00,
+        0x00,
+        0xab,
+        0x11,
+        0xfc,
+        0x72,
+        0xdb,
+        0x43,
+        0x6a,
+        0xc5,
+        0xca,
+        0x1f,
+        0x00,
+        0x1f,
+        0x00,
+        0x20,
+        0x24,
+        0x04,
+        0x01,
+        0xa8,
+        0x11,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x0b,
+        0x24,
+        0x04,
+        0x01,
+        0xa8,
+        0x11,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x0a,
+        // DNS option
+        0x00,
+        0x17,
+        0x00,
+        0x10,
+        0x00,
+        0x01,
+        0x00,
+        0x02,
+        0x00,
+        0x03,
+        0x00,
+        0x04,
+        0x00,
+        0x05,
+        0x00,
+        0x06,
+        0x00,
+        0x07,
+        0x00,
+        0x08,
+        // Domain search list option
+        0x00,
+        0x18,
+        0x00,
+        0x0d,
+        0x03,
+        0x6c,
+        0x61,
+        0x6e,
+        0x07,
+        0x65,
+        0x78,
+        0x61,
+        0x6d,
+        0x70,
+        0x6c,
+        0x65,
+        0x00,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_modify_dhcp_ex1_idempotent() {
+    // Rewriting an already rewritten reply doesn't add our IPs again
+    for keep_existing_dns in [false, true] {
+        let config = ModifyConfig {
+            dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+            dns_search: vec!["home.arpa".to_string()],
+            keep_existing_dns,
+            ..Default::default()
+        };
+        let once = modify(PAYLOAD_DHCP1, &config).unwrap();
+        assert_eq!(modify(&once, &config).unwrap(), once);
+    }
+}
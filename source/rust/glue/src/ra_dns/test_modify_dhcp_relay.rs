@@ -0,0 +1,117 @@
+use {
+    crate::ra_dns::{
+        icmpv6_udp_checksum,
+        list_options,
+        modify,
+        ModifyConfig,
+    },
+    std::net::Ipv6Addr,
+};
+
+fn option(code: u16, body: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    out.extend(code.to_be_bytes());
+    out.extend((body.len() as u16).to_be_bytes());
+    out.extend(body);
+    return out;
+}
+
+fn relay_reply(hop_count: u8, relayed: &[u8]) -> Vec<u8> {
+    let mut out = vec![13, hop_count];
+    // Link address, peer address
+    out.extend(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1).octets());
+    out.extend(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0x20).octets());
+    // Interface-ID
+    out.extend(option(18, b"eth0"));
+    out.extend(option(9, relayed));
+    return out;
+}
+
+fn reply(dns: &[Ipv6Addr]) -> Vec<u8> {
+    let mut out = vec![
+        // DHCPv6 reply
+        0x07,
+        0x12,
+        0x34,
+        0x56,
+    ];
+    // Server identifier
+    out.extend(option(2, &[0xab, 0xcd]));
+    out.extend(option(0x17, &dns.iter().flat_map(|ip| ip.octets()).collect::<Vec<_>>()));
+    return out;
+}
+
+/// A checksummed packet from the server to a relay agent.
+fn packet(dhcp: &[u8]) -> Vec<u8> {
+    let len = (8 + dhcp.len()) as u16;
+    let mut out = vec![
+        // IPv6
+        0x60,
+        0x00,
+        0x00,
+        0x00,
+    ];
+    out.extend(len.to_be_bytes());
+    out.extend([0x11, 0x40]);
+    out.extend(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x547).octets());
+    out.extend(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1).octets());
+    // UDP, server to relay
+    out.extend([0x02, 0x23, 0x02, 0x23]);
+    out.extend(len.to_be_bytes());
+    out.extend([0x00, 0x00]);
+    out.extend(dhcp);
+    let checksum = icmpv6_udp_checksum(&out).unwrap();
+    out[46 .. 48].copy_from_slice(&checksum);
+    return out;
+}
+
+#[test]
+fn test_modify_dhcp_relay() {
+    let theirs = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53);
+    let ours = Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8);
+    let source = packet(&relay_reply(0, &reply(&[theirs])));
+    let got = modify(&source, &ModifyConfig {
+        dns_ips: vec![ours],
+        keep_existing_dns: true,
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(got, packet(&relay_reply(0, &reply(&[theirs, ours]))));
+    assert_eq!(got.len(), source.len() + 16);
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+
+    // Relay options are kept in place
+    assert_eq!(list_options(&got, &ModifyConfig::default()), list_options(&source, &ModifyConfig::default()));
+}
+
+#[test]
+fn test_modify_dhcp_relay_two_hops() {
+    let ours = Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8);
+    let source = packet(&relay_reply(1, &relay_reply(0, &reply(&[Ipv6Addr::LOCALHOST, Ipv6Addr::LOCALHOST]))));
+    let got = modify(&source, &ModifyConfig {
+        dns_ips: vec![ours],
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(got, packet(&relay_reply(1, &relay_reply(0, &reply(&[ours])))));
+    assert_eq!(got.len(), source.len() - 16);
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+}
+
+#[test]
+fn test_modify_dhcp_relay_passthrough() {
+    let config = ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        ..Default::default()
+    };
+
+    // Relayed message without DNS
+    let mut relayed = reply(&[]);
+    relayed.truncate(relayed.len() - 4);
+    let source = packet(&relay_reply(0, &relayed));
+    assert_eq!(modify(&source, &config).unwrap(), source);
+
+    // Relayed message of a type we don't rewrite
+    let mut relayed = reply(&[Ipv6Addr::LOCALHOST]);
+    relayed[0] = 0x01;
+    let source = packet(&relay_reply(0, &relayed));
+    assert_eq!(modify(&source, &config).unwrap(), source);
+}
@@ -0,0 +1,677 @@
+use {
+    crate::ra_dns::{
+        icmpv6_udp_checksum,
+        list_options,
+        modify,
+        modify_into,
+        ra_source,
+        ModifyConfig,
+        ModifyError,
+    },
+    std::net::Ipv6Addr,
+};
+
+const PAYLOAD_RA1: &[u8] = &[
+    // IPv6
+    0x6b,
+    0x80,
+    0x00,
+    0x00,
+    // Length
+    0x00,
+    0x38,
+    0x3a,
+    0xff,
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x4a,
+    0x2e,
+    0x72,
+    0xff,
+    0xfe,
+    0x63,
+    0x7d,
+    0x10,
+    0xff,
+    0x02,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x01,
+    // ICMPv6 RA
+    0x86,
+    0x00,
+    // Checksum
+    0xa8,
+    0x4d,
+    0x40,
+    0x80,
+    0x07,
+    0x08,
+    0x00,
+    0x00,
+    0x93,
+    0xe0,
+    0x00,
+    0x00,
+    0x27,
+    0x10,
+    // Source link-layer address option
+    0x01,
+    0x01,
+    0x48,
+    0x2e,
+    0x72,
+    0x63,
+    0x7d,
+    0x10,
+    // MTU option
+    0x05,
+    0x01,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x05,
+    0xdc,
+    // RDNSS option, length
+    0x19,
+    0x03,
+    0x00,
+    0x00,
+    // Lifetime
+    0x00,
+    0x00,
+    0x0e,
+    0x10,
+    // IPs
+    0x20,
+    0x01,
+    0x0d,
+    0xb8,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x53,
+];
+
+#[test]
+fn test_modify_ra_ex1() {
+    let got = modify(PAYLOAD_RA1, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        set_other_flag: Some(true),
+        ..Default::default()
+    }).unwrap();
+    let mut want = vec![
+        // IPv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        // Length
+        0x00,
+        0x38,
+        0x3a,
+        0xff,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x4a,
+        0x2e,
+        0x72,
+        0xff,
+        0xfe,
+        0x63,
+        0x7d,
+        0x10,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // ICMPv6 RA
+        0x86,
+        0x00,
+        // Checksum
+        0xd5,
+        0xf5,
+        0x40,
+        0xc0,
+        0x07,
+        0x08,
+        0x00,
+        0x00,
+        0x93,
+        0xe0,
+        0x00,
+        0x00,
+        0x27,
+        0x10,
+        // Source link-layer address option
+        0x01,
+        0x01,
+        0x48,
+        0x2e,
+        0x72,
+        0x63,
+        0x7d,
+        0x10,
+        // MTU option
+        0x05,
+        0x01,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x05,
+        0xdc,
+        // RDNSS option, length
+        0x19,
+        0x03,
+        0x00,
+        0x00,
+        // Lifetime
+        0x00,
+        0x00,
+        0x0e,
+        0x10,
+        // IPs
+        0x00,
+        0x01,
+        0x00,
+        0x02,
+        0x00,
+        0x03,
+        0x00,
+        0x04,
+        0x00,
+        0x05,
+        0x00,
+        0x06,
+        0x00,
+        0x07,
+        0x00,
+        0x08,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_modify_ra_ex1_two_ips() {
+    let got = modify(PAYLOAD_RA1, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8), Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 9)],
+        set_other_flag: Some(true),
+        ..Default::default()
+    }).unwrap();
+    let mut want = vec![
+        // IPv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        // Length
+        0x00,
+        0x48,
+        0x3a,
+        0xff,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x4a,
+        0x2e,
+        0x72,
+        0xff,
+        0xfe,
+        0x63,
+        0x7d,
+        0x10,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // ICMPv6 RA
+        0x86,
+        0x00,
+        // Checksum
+        0xd5,
+        0xbe,
+        0x40,
+        0xc0,
+        0x07,
+        0x08,
+        0x00,
+        0x00,
+        0x93,
+        0xe0,
+        0x00,
+        0x00,
+        0x27,
+        0x10,
+        // Source link-layer address option
+        0x01,
+        0x01,
+        0x48,
+        0x2e,
+        0x72,
+        0x63,
+        0x7d,
+        0x10,
+        // MTU option
+        0x05,
+        0x01,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x05,
+        0xdc,
+        // RDNSS option, length
+        0x19,
+        0x05,
+        0x00,
+        0x00,
+        // Lifetime
+        0x00,
+        0x00,
+        0x0e,
+        0x10,
+        // IPs
+        0x00,
+        0x01,
+        0x00,
+        0x02,
+        0x00,
+        0x03,
+        0x00,
+        0x04,
+        0x00,
+        0x05,
+        0x00,
+        0x06,
+        0x00,
+        0x07,
+        0x00,
+        0x08,
+        0x00,
+        0x01,
+        0x00,
+        0x02,
+        0x00,
+        0x03,
+        0x00,
+        0x04,
+        0x00,
+        0x05,
+        0x00,
+        0x06,
+        0x00,
+        0x07,
+        0x00,
+        0x09,
+    ];
+    // RDNSS length byte (units of 8 octets) covers both ips
+    assert_eq!(got[56 + 16 + 1], 5);
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_modify_ra_ex1_lifetime() {
+    let got = modify(PAYLOAD_RA1, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        rdnss_lifetime: Some(300),
+        set_other_flag: Some(true),
+        ..Default::default()
+    }).unwrap();
+
+    // RDNSS lifetime
+    assert_eq!(&got[56 + 16 + 4 .. 56 + 16 + 8], &300u32.to_be_bytes());
+
+    // Checksum over the whole packet (including the checksum) folds to zero
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+}
+
+#[test]
+fn test_modify_ra_ex1_list_options() {
+    assert_eq!(list_options(PAYLOAD_RA1, &ModifyConfig::default()), Some(vec![1, 5, 25]));
+}
+
+#[test]
+fn test_modify_ra_ex1_keep_existing() {
+    let got = modify(PAYLOAD_RA1, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        keep_existing_dns: true,
+        set_other_flag: Some(true),
+        ..Default::default()
+    }).unwrap();
+    let mut want = vec![
+        // IPv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        // Length
+        0x00,
+        0x50,
+        0x3a,
+        0xff,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x4a,
+        0x2e,
+        0x72,
+        0xff,
+        0xfe,
+        0x63,
+        0x7d,
+        0x10,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // ICMPv6 RA
+        0x86,
+        0x00,
+        // Checksum
+        0x80,
+        0xbe,
+        0x40,
+        0xc0,
+        0x07,
+        0x08,
+        0x00,
+        0x00,
+        0x93,
+        0xe0,
+        0x00,
+        0x00,
+        0x27,
+        0x10,
+        // Source link-layer address option
+        0x01,
+        0x01,
+        0x48,
+        0x2e,
+        0x72,
+        0x63,
+        0x7d,
+        0x10,
+        // MTU option
+        0x05,
+        0x01,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x05,
+        0xdc,
+        // Existing RDNSS option
+        0x19,
+        0x03,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x0e,
+        0x10,
+        0x20,
+        0x01,
+        0x0d,
+        0xb8,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x53,
+        // Injected RDNSS option, length
+        0x19,
+        0x03,
+        0x00,
+        0x00,
+        // Lifetime
+        0x00,
+        0x00,
+        0x0e,
+        0x10,
+        // IPs
+        0x00,
+        0x01,
+        0x00,
+        0x02,
+        0x00,
+        0x03,
+        0x00,
+        0x04,
+        0x00,
+        0x05,
+        0x00,
+        0x06,
+        0x00,
+        0x07,
+        0x00,
+        0x08,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_modify_ra_ex1_mtu() {
+    let got = modify(PAYLOAD_RA1, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        mtu: Some(1492),
+        set_other_flag: Some(true),
+        ..Default::default()
+    }).unwrap();
+
+    // Existing MTU replaced, not duplicated, and placed before RDNSS
+    assert_eq!(list_options(&got, &ModifyConfig::default()), Some(vec![1, 5, 25]));
+    assert_eq!(&got[56 + 8 .. 56 + 16], &[5, 1, 0, 0, 0x00, 0x00, 0x05, 0xd4]);
+    assert_eq!(u16::from_be_bytes([got[4], got[5]]) as usize, got.len() - 40);
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+}
+
+#[test]
+fn test_modify_ra_ex1_zero_length_option() {
+    // A zero length option would never advance the option walk
+    let mut payload = PAYLOAD_RA1.to_vec();
+    payload[56 + 8 + 1] = 0;
+    assert_eq!(modify(&payload, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        set_other_flag: Some(true),
+        ..Default::default()
+    }), Err(ModifyError::BadOptionLength));
+    assert_eq!(list_options(&payload, &ModifyConfig::default()), None);
+}
+
+#[test]
+fn test_modify_ra_ex1_ra_source() {
+    assert_eq!(ra_source(PAYLOAD_RA1), Some(Ipv6Addr::new(0xfe80, 0, 0, 0, 0x4a2e, 0x72ff, 0xfe63, 0x7d10)));
+}
+
+#[test]
+fn test_modify_ra_ex1_into_reused() {
+    let config = ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        set_other_flag: Some(true),
+        ..Default::default()
+    };
+
+    // Leftovers from a previous, longer packet don't leak into the result
+    let mut out = vec![0xff; 512];
+    modify_into(&mut out, PAYLOAD_RA1, &config).unwrap();
+    assert_eq!(out, modify(PAYLOAD_RA1, &config).unwrap());
+    assert_eq!(modify_into(&mut out, &PAYLOAD_RA1[.. 20], &config), Err(ModifyError::Truncated));
+    modify_into(&mut out, PAYLOAD_RA1, &config).unwrap();
+    assert_eq!(out, modify(PAYLOAD_RA1, &config).unwrap());
+}
+
+#[test]
+fn test_modify_ra_ex1_idempotent() {
+    // Rewriting an already rewritten RA (ex: reinjected without the skip mark) doesn't
+    // change it again
+    for keep_existing_dns in [false, true] {
+        let config = ModifyConfig {
+            dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8), Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 9)],
+            dns_search: vec!["home.arpa".to_string()],
+            mtu: Some(1492),
+            keep_existing_dns,
+            set_other_flag: Some(true),
+            ..Default::default()
+        };
+        let once = modify(PAYLOAD_RA1, &config).unwrap();
+        assert_eq!(modify(&once, &config).unwrap(), once);
+    }
+}
+
+/// Offsets of the RA's Cur Hop Limit, Router Lifetime, Reachable Time and Retrans
+/// Timer.
+const HOP_LIMIT_OFFSET: usize = 40 + 4;
+const ROUTER_LIFETIME_OFFSET: usize = 40 + 6;
+const REACHABLE_TIME_OFFSET: usize = 40 + 8;
+const RETRANS_TIMER_OFFSET: usize = 40 + 12;
+
+#[test]
+fn test_modify_ra_ex1_fixed_fields_kept() {
+    let got = modify(PAYLOAD_RA1, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        set_other_flag: Some(true),
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(got[HOP_LIMIT_OFFSET], PAYLOAD_RA1[HOP_LIMIT_OFFSET]);
+    assert_eq!(&got[ROUTER_LIFETIME_OFFSET .. 40 + 16], &PAYLOAD_RA1[ROUTER_LIFETIME_OFFSET .. 40 + 16]);
+}
+
+#[test]
+fn test_modify_ra_ex1_fixed_fields_override() {
+    let got = modify(PAYLOAD_RA1, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        set_other_flag: Some(true),
+        cur_hop_limit: Some(255),
+        router_lifetime: Some(0),
+        reachable_time: Some(30000),
+        retrans_timer: Some(1000),
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(got[HOP_LIMIT_OFFSET], 255);
+    assert_eq!(&got[ROUTER_LIFETIME_OFFSET .. REACHABLE_TIME_OFFSET], &0u16.to_be_bytes());
+    assert_eq!(&got[REACHABLE_TIME_OFFSET .. RETRANS_TIMER_OFFSET], &30000u32.to_be_bytes());
+    assert_eq!(&got[RETRANS_TIMER_OFFSET .. 40 + 16], &1000u32.to_be_bytes());
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+}
@@ -0,0 +1,102 @@
+use {
+    crate::ra_dns::{
+        modify,
+        ModifyConfig,
+        ModifyError,
+    },
+    std::net::{
+        Ipv4Addr,
+        Ipv6Addr,
+    },
+};
+
+/// An IPv4 ICMP echo request, long enough to pass for an IPv6 header.
+const PAYLOAD_IPV4_PING: &[u8] = &[
+    // IPv4
+    0x45,
+    0x00,
+    // Total length
+    0x00,
+    0x30,
+    // Identification, flags, fragment offset
+    0x00,
+    0x00,
+    0x40,
+    0x00,
+    // TTL, protocol (ICMP)
+    0x40,
+    0x01,
+    // Checksum
+    0x00,
+    0x00,
+    // Source
+    0x0a,
+    0x00,
+    0x00,
+    0x01,
+    // Destination
+    0x0a,
+    0x00,
+    0x00,
+    0x02,
+    // ICMP echo request
+    0x08,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x01,
+    0x00,
+    0x01,
+    // Data
+    0x3a,
+    0x3a,
+    0x3a,
+    0x3a,
+    0x3a,
+    0x3a,
+    0x3a,
+    0x3a,
+    0x3a,
+    0x3a,
+    0x3a,
+    0x3a,
+    0x3a,
+    0x3a,
+    0x3a,
+    0x3a,
+    0x3a,
+    0x3a,
+    0x3a,
+    0x3a,
+];
+
+#[test]
+fn test_modify_version_ipv4_not_ipv6() {
+    // Only configured for IPv6, so not parsed at all
+    assert_eq!(modify(PAYLOAD_IPV4_PING, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        ..Default::default()
+    }), Err(ModifyError::NotApplicable));
+
+    // Parsed as IPv4, not DHCP
+    assert_eq!(modify(PAYLOAD_IPV4_PING, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        dns_ips_v4: vec![Ipv4Addr::new(10, 0, 0, 53)],
+        ..Default::default()
+    }), Err(ModifyError::UnsupportedNextHeader(1)));
+}
+
+#[test]
+fn test_modify_version_unknown() {
+    for version in [0, 5, 7, 15] {
+        let mut payload = PAYLOAD_IPV4_PING.to_vec();
+        payload[0] = version << 4;
+        assert_eq!(modify(&payload, &ModifyConfig {
+            dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+            dns_ips_v4: vec![Ipv4Addr::new(10, 0, 0, 53)],
+            ..Default::default()
+        }), Err(ModifyError::NotApplicable));
+    }
+    assert_eq!(modify(&[], &ModifyConfig::default()), Err(ModifyError::Truncated));
+}
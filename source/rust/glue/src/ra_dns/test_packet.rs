@@ -0,0 +1,45 @@
+use {
+    crate::ra_dns::packet::Ipv6Packet,
+    std::net::Ipv6Addr,
+};
+
+#[test]
+fn test_packet_fields() {
+    let mut data = [0u8; 44];
+    data[0] = 0x60;
+    data[5] = 4;
+    data[6] = 58;
+    data[8 .. 24].copy_from_slice(&Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1).octets());
+    data[24 .. 40].copy_from_slice(&Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1).octets());
+    data[40] = 134;
+    let packet = Ipv6Packet::new(&data[..]).unwrap();
+    assert_eq!(packet.payload_len(), 4);
+    assert_eq!(packet.next_header(), 58);
+    assert_eq!(packet.src(), Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1));
+    assert_eq!(packet.dst(), Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1));
+    assert_eq!(packet.payload(), &[134, 0, 0, 0]);
+    assert_eq!(packet.payload_u16(2), Some(0));
+    assert_eq!(packet.payload_u16(3), None);
+    assert_eq!(packet.upper_layer(), Ok((58, 0)));
+}
+
+#[test]
+fn test_packet_replace_payload_tail() {
+    let mut data = vec![0u8; 44];
+    data[0] = 0x60;
+    data[5] = 4;
+    let mut packet = Ipv6Packet::new(data).unwrap();
+    packet.replace_payload_tail(2, &[1, 2, 3, 4]).unwrap();
+    assert_eq!(packet.payload(), &[0, 0, 1, 2, 3, 4]);
+    assert_eq!(packet.payload_len(), 6);
+    assert_eq!(packet.replace_payload_tail(7, &[]), None);
+}
+
+#[test]
+fn test_packet_invalid() {
+    // Truncated
+    assert!(Ipv6Packet::new(&[0x60u8; 39][..]).is_none());
+
+    // IPv4
+    assert!(Ipv6Packet::new(&[0x45u8; 40][..]).is_none());
+}
@@ -0,0 +1,41 @@
+use crate::ra_dns::{
+    parse::parse,
+    test_util::{
+        PAYLOAD_DHCP,
+        PAYLOAD_RA,
+    },
+    ModifyError,
+};
+
+#[test]
+fn test_parse_ra() {
+    let got = parse(PAYLOAD_RA).unwrap().to_string();
+    let want = [
+        "RA from fe80::4a2e:72ff:fe63:7d10, hop limit 64, flags M -, router lifetime 1800s",
+        "  option 1, 8 bytes",
+        "  option 5, 8 bytes",
+        "  option 25, 24 bytes: 2001:db8::53",
+    ].join("\n");
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_parse_dhcp6() {
+    let got = parse(PAYLOAD_DHCP).unwrap().to_string();
+    let want = [
+        "DHCPv6 message type 7, transaction 5620fd",
+        "  option 2, 14 bytes",
+        "  option 1, 18 bytes",
+        "  option 23, 36 bytes: 2404:1a8:7f01:b::3 2404:1a8:7f01:a::3",
+        "  option 24, 29 bytes",
+        "  option 31, 36 bytes",
+    ].join("\n");
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_parse_not_applicable() {
+    let mut payload = PAYLOAD_RA.to_vec();
+    payload[40] = 135;
+    assert_eq!(parse(&payload), Err(ModifyError::NotApplicable));
+}
@@ -0,0 +1,202 @@
+use {
+    crate::ra_dns::{
+        icmpv6_udp_checksum,
+        modify,
+        ModifyConfig,
+    },
+    std::net::Ipv6Addr,
+};
+
+/// An RA with only the fixed header, no options.
+const PAYLOAD_RA_BARE: &[u8] = &[
+    // IPv6
+    0x6b,
+    0x80,
+    0x00,
+    0x00,
+    // Length
+    0x00,
+    0x10,
+    0x3a,
+    0xff,
+    // Source, destination
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x4a,
+    0x2e,
+    0x72,
+    0xff,
+    0xfe,
+    0x63,
+    0x7d,
+    0x10,
+    0xff,
+    0x02,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x01,
+    // ICMPv6 RA
+    0x86,
+    0x00,
+    // Checksum
+    0x41,
+    0x95,
+    0x40,
+    // Flags (Other unset)
+    0x00,
+    0x07,
+    0x08,
+    0x00,
+    0x00,
+    0x93,
+    0xe0,
+    0x00,
+    0x00,
+    0x27,
+    0x10,
+];
+
+#[test]
+fn test_ra_bare_no_rdnss() {
+    // The Other flag is set, but there's no RDNSS to replace so none is added (DNS
+    // may come via DHCPv6)
+    let got = modify(PAYLOAD_RA_BARE, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        set_other_flag: Some(true),
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(got.len(), PAYLOAD_RA_BARE.len());
+    assert_eq!(got[40 + 5], 0x40);
+    assert_eq!(&got[40 + 2 .. 40 + 4], &[0x41, 0x55]);
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+}
+
+#[test]
+fn test_ra_bare_inject_mtu() {
+    let got = modify(PAYLOAD_RA_BARE, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        mtu: Some(1492),
+        set_other_flag: Some(true),
+        ..Default::default()
+    }).unwrap();
+    let mut want = vec![
+        // IPv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        // Length
+        0x00,
+        0x18,
+        0x3a,
+        0xff,
+        // Source, destination
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x4a,
+        0x2e,
+        0x72,
+        0xff,
+        0xfe,
+        0x63,
+        0x7d,
+        0x10,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // ICMPv6 RA
+        0x86,
+        0x00,
+        // Checksum
+        0x36,
+        0x78,
+        0x40,
+        // Flags (Other set)
+        0x40,
+        0x07,
+        0x08,
+        0x00,
+        0x00,
+        0x93,
+        0xe0,
+        0x00,
+        0x00,
+        0x27,
+        0x10,
+        // MTU
+        0x05,
+        0x01,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x05,
+        0xd4,
+    ];
+    // Payload length covers the new option, checksum is valid, Other flag is set
+    assert_eq!(u16::from_be_bytes([got[4], got[5]]) as usize, got.len() - 40);
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+    assert_eq!(got[40 + 5] & 0x40, 0x40);
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_ra_bare_router_lifetime() {
+    // Overriding a fixed field is a rewrite even without an RDNSS
+    let got = modify(PAYLOAD_RA_BARE, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        router_lifetime: Some(0),
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(got.len(), PAYLOAD_RA_BARE.len());
+    assert_eq!(&got[40 + 6 .. 40 + 8], &[0, 0]);
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+}
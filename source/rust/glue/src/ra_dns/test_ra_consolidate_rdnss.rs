@@ -0,0 +1,49 @@
+use {
+    crate::ra_dns::{
+        build_rdnss_option,
+        icmpv6_udp_checksum,
+        list_options,
+        modify,
+        test_util::{
+            ra,
+            OPT_SLLA,
+        },
+        ModifyConfig,
+    },
+    std::net::Ipv6Addr,
+};
+
+fn config() -> ModifyConfig {
+    return ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        keep_existing_dns: true,
+        consolidate_rdnss: true,
+        ..Default::default()
+    };
+}
+
+#[test]
+fn test_ra_consolidate_rdnss() {
+    let a = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x53);
+    let b = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x54);
+    let c = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x55);
+
+    // The second option has several addresses, one repeated from the first
+    let got =
+        modify(&ra(&[OPT_SLLA, &build_rdnss_option(&[a], 600), &build_rdnss_option(&[b, a, c], 600)]), &config())
+            .unwrap();
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+    assert_eq!(list_options(&got, &ModifyConfig::default()), Some(vec![1, 25]));
+    assert_eq!(&got[40 + 16 + 8..], build_rdnss_option(&[a, b, c, Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)], 600));
+}
+
+#[test]
+fn test_ra_consolidate_rdnss_idempotent() {
+    let once = modify(&ra(&[
+        &build_rdnss_option(&[Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x53)], 600),
+        OPT_SLLA,
+        &build_rdnss_option(&[Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x54)], 600),
+    ]), &config()).unwrap();
+    assert_eq!(list_options(&once, &ModifyConfig::default()), Some(vec![25, 1]));
+    assert_eq!(modify(&once, &config()).unwrap(), once);
+}
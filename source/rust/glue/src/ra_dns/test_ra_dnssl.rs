@@ -0,0 +1,306 @@
+use {
+    crate::ra_dns::{
+        modify,
+        ModifyConfig,
+    },
+    std::net::Ipv6Addr,
+};
+
+const PAYLOAD_RA1: &[u8] = &[
+    // IPv6
+    0x6b,
+    0x80,
+    0x00,
+    0x00,
+    // Length
+    0x00,
+    0x48,
+    0x3a,
+    0xff,
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x4a,
+    0x2e,
+    0x72,
+    0xff,
+    0xfe,
+    0x63,
+    0x7d,
+    0x10,
+    0xff,
+    0x02,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x01,
+    // ICMPv6 RA
+    0x86,
+    0x00,
+    // Checksum
+    0xb9,
+    0x5a,
+    0x40,
+    0xc0,
+    0x07,
+    0x08,
+    0x00,
+    0x00,
+    0x93,
+    0xe0,
+    0x00,
+    0x00,
+    0x27,
+    0x10,
+    // Source link-layer address option
+    0x01,
+    0x01,
+    0x48,
+    0x2e,
+    0x72,
+    0x63,
+    0x7d,
+    0x10,
+    // RDNSS option
+    0x19,
+    0x03,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x0e,
+    0x10,
+    0x20,
+    0x01,
+    0x0d,
+    0xb8,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x53,
+    // DNSSL option (example.com)
+    0x1f,
+    0x03,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x0e,
+    0x10,
+    0x07,
+    0x65,
+    0x78,
+    0x61,
+    0x6d,
+    0x70,
+    0x6c,
+    0x65,
+    0x03,
+    0x63,
+    0x6f,
+    0x6d,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+];
+
+#[test]
+fn test_ra_dnssl() {
+    let got = modify(PAYLOAD_RA1, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        dns_search: vec!["home.arpa".to_string()],
+        ..Default::default()
+    }).unwrap();
+    let mut want = vec![
+        // IPv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        // Length
+        0x00,
+        0x48,
+        0x3a,
+        0xff,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x4a,
+        0x2e,
+        0x72,
+        0xff,
+        0xfe,
+        0x63,
+        0x7d,
+        0x10,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // ICMPv6 RA
+        0x86,
+        0x00,
+        // Checksum
+        0x09,
+        0x02,
+        0x40,
+        0xc0,
+        0x07,
+        0x08,
+        0x00,
+        0x00,
+        0x93,
+        0xe0,
+        0x00,
+        0x00,
+        0x27,
+        0x10,
+        // Source link-layer address option
+        0x01,
+        0x01,
+        0x48,
+        0x2e,
+        0x72,
+        0x63,
+        0x7d,
+        0x10,
+        // RDNSS option
+        0x19,
+        0x03,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x0e,
+        0x10,
+        0x00,
+        0x01,
+        0x00,
+        0x02,
+        0x00,
+        0x03,
+        0x00,
+        0x04,
+        0x00,
+        0x05,
+        0x00,
+        0x06,
+        0x00,
+        0x07,
+        0x00,
+        0x08,
+        // DNSSL option (home.arpa)
+        0x1f,
+        0x03,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x0e,
+        0x10,
+        0x04,
+        0x68,
+        0x6f,
+        0x6d,
+        0x65,
+        0x04,
+        0x61,
+        0x72,
+        0x70,
+        0x61,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+    ];
+    assert_eq!(
+        &got[88 ..],
+        &[
+            // Type, length (3 * 8 octets)
+            31,
+            3,
+            // Reserved
+            0,
+            0,
+            // Lifetime
+            0x00,
+            0x00,
+            0x0e,
+            0x10,
+            // Labels
+            4,
+            b'h',
+            b'o',
+            b'm',
+            b'e',
+            4,
+            b'a',
+            b'r',
+            b'p',
+            b'a',
+            0,
+            // Padding
+            0,
+            0,
+            0,
+            0,
+            0,
+        ]
+    );
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
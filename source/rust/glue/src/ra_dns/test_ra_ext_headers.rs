@@ -0,0 +1,262 @@
+use {
+    crate::ra_dns::{
+        icmpv6_udp_checksum,
+        modify,
+        pseudo_header_checksum,
+        ModifyConfig,
+    },
+    std::net::Ipv6Addr,
+};
+
+const PAYLOAD_RA_HBH: &[u8] = &[
+    // IPv6
+    0x6b,
+    0x80,
+    0x00,
+    0x00,
+    // Length
+    0x00,
+    0x38,
+    // Next header (hop-by-hop)
+    0x00,
+    0xff,
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x4a,
+    0x2e,
+    0x72,
+    0xff,
+    0xfe,
+    0x63,
+    0x7d,
+    0x10,
+    0xff,
+    0x02,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x01,
+    // Hop-by-hop, next header ICMPv6, PadN
+    0x3a,
+    0x00,
+    0x01,
+    0x04,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    // ICMPv6 RA
+    0x86,
+    0x00,
+    // Checksum
+    0xb3,
+    0x32,
+    0x40,
+    0x80,
+    0x07,
+    0x08,
+    0x00,
+    0x00,
+    0x93,
+    0xe0,
+    0x00,
+    0x00,
+    0x27,
+    0x10,
+    // Source link-layer address option
+    0x01,
+    0x01,
+    0x48,
+    0x2e,
+    0x72,
+    0x63,
+    0x7d,
+    0x10,
+    // RDNSS option
+    0x19,
+    0x03,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x0e,
+    0x10,
+    0x20,
+    0x01,
+    0x0d,
+    0xb8,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x53,
+];
+
+#[test]
+fn test_ra_ext_headers_checksum() {
+    // Checksum over the whole packet (including the checksum) folds to zero if the
+    // pseudo header skips the hop-by-hop header
+    assert_eq!(icmpv6_udp_checksum(PAYLOAD_RA_HBH), Some([0, 0]));
+}
+
+#[test]
+fn test_ra_ext_headers_checksum_explicit() {
+    // The ICMPv6 packet starts after the 8 byte hop-by-hop header
+    let upper_len = PAYLOAD_RA_HBH.len() - 40 - 8;
+    assert_eq!(pseudo_header_checksum(PAYLOAD_RA_HBH, 58, 8, upper_len), Some([0, 0]));
+
+    // Using the IPv6 payload length in the pseudo header gives the wrong checksum
+    assert_ne!(pseudo_header_checksum(PAYLOAD_RA_HBH, 58, 0, upper_len + 8), Some([0, 0]));
+    assert_eq!(pseudo_header_checksum(PAYLOAD_RA_HBH, 58, 8, upper_len + 1), None);
+}
+
+#[test]
+fn test_ra_ext_headers() {
+    let got = modify(PAYLOAD_RA_HBH, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        set_other_flag: Some(true),
+        ..Default::default()
+    }).unwrap();
+    let mut want = vec![
+        // IPv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        // Length
+        0x00,
+        0x38,
+        // Next header (hop-by-hop)
+        0x00,
+        0xff,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x4a,
+        0x2e,
+        0x72,
+        0xff,
+        0xfe,
+        0x63,
+        0x7d,
+        0x10,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // Hop-by-hop, next header ICMPv6, PadN
+        0x3a,
+        0x00,
+        0x01,
+        0x04,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        // ICMPv6 RA
+        0x86,
+        0x00,
+        // Checksum
+        0xe0,
+        0xda,
+        0x40,
+        0xc0,
+        0x07,
+        0x08,
+        0x00,
+        0x00,
+        0x93,
+        0xe0,
+        0x00,
+        0x00,
+        0x27,
+        0x10,
+        // Source link-layer address option
+        0x01,
+        0x01,
+        0x48,
+        0x2e,
+        0x72,
+        0x63,
+        0x7d,
+        0x10,
+        // RDNSS option
+        0x19,
+        0x03,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x0e,
+        0x10,
+        0x00,
+        0x01,
+        0x00,
+        0x02,
+        0x00,
+        0x03,
+        0x00,
+        0x04,
+        0x00,
+        0x05,
+        0x00,
+        0x06,
+        0x00,
+        0x07,
+        0x00,
+        0x08,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
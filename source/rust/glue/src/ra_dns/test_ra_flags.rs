@@ -0,0 +1,164 @@
+use {
+    crate::ra_dns::{
+        icmpv6_udp_checksum,
+        modify,
+        test_util::{
+            ra,
+            OPT_SLLA,
+        },
+        ModifyConfig,
+    },
+    std::net::Ipv6Addr,
+};
+
+/// An RA with the Managed flag set and the Other flag clear, with an RDNSS option.
+const PAYLOAD_RA_MANAGED: &[u8] = &[
+    // IPv6
+    0x6b,
+    0x80,
+    0x00,
+    0x00,
+    // Length
+    0x00,
+    0x28,
+    // Next header, hop limit
+    0x3a,
+    0xff,
+    // Source
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x4a,
+    0x2e,
+    0x72,
+    0xff,
+    0xfe,
+    0x63,
+    0x7d,
+    0x10,
+    // Destination
+    0xff,
+    0x02,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x01,
+    // ICMPv6 RA
+    0x86,
+    0x00,
+    0x28,
+    0x4e,
+    0x40,
+    0x80,
+    0x07,
+    0x08,
+    0x00,
+    0x00,
+    0x93,
+    0xe0,
+    0x00,
+    0x00,
+    0x27,
+    0x10,
+    // RDNSS
+    0x19,
+    0x03,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x02,
+    0x58,
+    0xfd,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x53,
+];
+const FLAGS_OFFSET: usize = 40 + 5;
+
+#[test]
+fn test_ra_flags() {
+    for set_managed_flag in [None, Some(false), Some(true)] {
+        for set_other_flag in [None, Some(false), Some(true)] {
+            let got = modify(PAYLOAD_RA_MANAGED, &ModifyConfig {
+                dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+                set_other_flag,
+                set_managed_flag,
+                ..Default::default()
+            }).unwrap();
+            let mut want_flags = 0;
+            if set_managed_flag.unwrap_or(true) {
+                want_flags |= 0x80;
+            }
+            if set_other_flag.unwrap_or(false) {
+                want_flags |= 0x40;
+            }
+            assert_eq!(got[FLAGS_OFFSET], want_flags, "managed {:?}, other {:?}", set_managed_flag, set_other_flag);
+            assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+        }
+    }
+}
+
+#[test]
+fn test_ra_flags_no_rdnss() {
+    let bare = ra(&[OPT_SLLA]);
+    assert_eq!(bare[FLAGS_OFFSET], 0);
+
+    // Changing a flag is reason enough to rewrite
+    let got = modify(&bare, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        set_managed_flag: Some(true),
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(got[FLAGS_OFFSET], 0x80);
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+
+    // Already as requested, or only set when rewriting anyway
+    for config in [ModifyConfig {
+        set_other_flag: Some(false),
+        ..Default::default()
+    }, ModifyConfig {
+        set_other_flag_on_rewrite: true,
+        ..Default::default()
+    }] {
+        assert_eq!(modify(&bare, &config).unwrap(), bare);
+    }
+}
+
+#[test]
+fn test_ra_flags_other_on_rewrite() {
+    let got = modify(PAYLOAD_RA_MANAGED, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        set_other_flag_on_rewrite: true,
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(got[FLAGS_OFFSET], 0xc0);
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+}
@@ -0,0 +1,147 @@
+use {
+    crate::ra_dns::{
+        icmpv6_udp_checksum,
+        modify,
+        ModifyConfig,
+    },
+    std::net::Ipv6Addr,
+};
+
+/// An RA with an RDNSS option with lifetime 600.
+const PAYLOAD_RA: &[u8] = &[
+    // IPv6
+    0x6b,
+    0x80,
+    0x00,
+    0x00,
+    // Length
+    0x00,
+    0x28,
+    // Next header, hop limit
+    0x3a,
+    0xff,
+    // Source
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x4a,
+    0x2e,
+    0x72,
+    0xff,
+    0xfe,
+    0x63,
+    0x7d,
+    0x10,
+    // Destination
+    0xff,
+    0x02,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x01,
+    // ICMPv6 RA
+    0x86,
+    0x00,
+    0x28,
+    0x4e,
+    0x40,
+    0x80,
+    0x07,
+    0x08,
+    0x00,
+    0x00,
+    0x93,
+    0xe0,
+    0x00,
+    0x00,
+    0x27,
+    0x10,
+    // RDNSS
+    0x19,
+    0x03,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x02,
+    0x58,
+    0xfd,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x53,
+];
+
+const RDNSS_LIFETIME_OFFSET: usize = 40 + 16 + 4;
+
+/// After the RDNSS with one address.
+const DNSSL_LIFETIME_OFFSET: usize = 40 + 16 + 24 + 4;
+
+fn lifetimes(rdnss_lifetime: Option<u32>, min: Option<u32>, max: Option<u32>) -> (u32, u32) {
+    let got = modify(PAYLOAD_RA, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        dns_search: vec!["home.arpa".to_string()],
+        rdnss_lifetime,
+        min_rdnss_lifetime: min,
+        max_rdnss_lifetime: max,
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+    let read = |at: usize| u32::from_be_bytes(got[at .. at + 4].try_into().unwrap());
+    return (read(RDNSS_LIFETIME_OFFSET), read(DNSSL_LIFETIME_OFFSET));
+}
+
+#[test]
+fn test_ra_lifetime_unclamped() {
+    assert_eq!(lifetimes(None, None, None), (600, 600));
+    assert_eq!(lifetimes(Some(30), None, None), (30, 30));
+    assert_eq!(lifetimes(None, Some(60), Some(3600)), (600, 600));
+}
+
+#[test]
+fn test_ra_lifetime_clamp_configured() {
+    assert_eq!(lifetimes(Some(10), Some(60), None), (60, 60));
+    assert_eq!(lifetimes(Some(u32::MAX), None, Some(3600)), (3600, 3600));
+}
+
+#[test]
+fn test_ra_lifetime_clamp_router() {
+    assert_eq!(lifetimes(None, Some(1000), None), (1000, 1000));
+    assert_eq!(lifetimes(None, None, Some(300)), (300, 300));
+}
+
+#[test]
+fn test_ra_lifetime_clamp_conflicting() {
+    assert_eq!(lifetimes(None, Some(1000), Some(300)), (300, 300));
+}
+
+#[test]
+fn test_ra_lifetime_withdraw_unclamped() {
+    assert_eq!(lifetimes(Some(0), Some(60), None), (0, 0));
+}
@@ -1,5 +1,8 @@
 use {
-    crate::manglelib::modify,
+    crate::ra_dns::{
+        modify,
+        ModifyConfig,
+    },
     std::net::Ipv6Addr,
 };
 
@@ -84,7 +87,11 @@ const PAYLOAD_RA1: &[u8] = &[
 
 #[test]
 fn test() {
-    let got = modify(PAYLOAD_RA1, Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8), Some(13)).unwrap();
+    let got = modify(PAYLOAD_RA1, &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        mtu: Some(13),
+        ..Default::default()
+    }).unwrap();
     let mut want = vec![
         // ipv6
         0x6b,
@@ -0,0 +1,131 @@
+use {
+    crate::ra_dns::{
+        icmpv6_udp_checksum,
+        list_options,
+        modify,
+        test_util::{
+            ra,
+            OPT_SLLA,
+        },
+        ModifyConfig,
+    },
+    std::net::Ipv6Addr,
+};
+
+const OPT_MTU: &[u8] = &[0x05, 0x01, 0x00, 0x00, 0x00, 0x00, 0x05, 0xdc];
+const OPT_RDNSS: &[u8] = &[
+    0x19,
+    0x03,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x02,
+    0x58,
+    0xfd,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x53,
+];
+
+/// `lan.` with lifetime 600
+const OPT_DNSSL: &[u8] = &[0x1f, 0x02, 0x00, 0x00, 0x00, 0x00, 0x02, 0x58, 0x03, 0x6c, 0x61, 0x6e, 0x00, 0x00, 0x00, 0x00];
+const OPT_PREFIX: &[u8] = &[
+    0x03,
+    0x04,
+    0x40,
+    0xc0,
+    0x00,
+    0x27,
+    0x8d,
+    0x00,
+    0x00,
+    0x09,
+    0x3a,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x24,
+    0x04,
+    0x7a,
+    0x82,
+    0x3c,
+    0x40,
+    0x1f,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+];
+
+fn config() -> ModifyConfig {
+    return ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        dns_search: vec!["home.arpa".to_string()],
+        mtu: Some(1280),
+        ..Default::default()
+    };
+}
+
+#[test]
+fn test_ra_option_order_original() {
+    let got = modify(&ra(&[OPT_SLLA, OPT_RDNSS, OPT_PREFIX, OPT_DNSSL, OPT_MTU]), &config()).unwrap();
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+    assert_eq!(list_options(&got, &ModifyConfig::default()), Some(vec![1, 25, 31, 3, 5]));
+
+    // MTU replaced in place
+    assert_eq!(&got[got.len() - 8..], &[0x05, 0x01, 0x00, 0x00, 0x00, 0x00, 0x05, 0x00]);
+}
+
+#[test]
+fn test_ra_option_order_dnssl_first() {
+    let got = modify(&ra(&[OPT_DNSSL, OPT_PREFIX, OPT_RDNSS, OPT_SLLA]), &config()).unwrap();
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+    assert_eq!(list_options(&got, &ModifyConfig::default()), Some(vec![25, 31, 3, 1, 5]));
+}
+
+#[test]
+fn test_ra_option_order_keep_existing() {
+    let got = modify(&ra(&[OPT_SLLA, OPT_RDNSS, OPT_PREFIX]), &ModifyConfig {
+        keep_existing_dns: true,
+        ..config()
+    }).unwrap();
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+    assert_eq!(list_options(&got, &ModifyConfig::default()), Some(vec![1, 25, 25, 31, 3, 5]));
+    assert_eq!(&got[40 + 16 + 8 .. 40 + 16 + 8 + OPT_RDNSS.len()], OPT_RDNSS);
+}
+
+#[test]
+fn test_ra_option_order_end() {
+    let got = modify(&ra(&[OPT_SLLA, OPT_RDNSS, OPT_PREFIX, OPT_MTU]), &ModifyConfig {
+        dns_options_at_end: true,
+        ..config()
+    }).unwrap();
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+    assert_eq!(list_options(&got, &ModifyConfig::default()), Some(vec![1, 3, 5, 25, 31]));
+}
+
+#[test]
+fn test_ra_option_order_idempotent() {
+    let once = modify(&ra(&[OPT_SLLA, OPT_RDNSS, OPT_PREFIX, OPT_DNSSL, OPT_MTU]), &config()).unwrap();
+    assert_eq!(modify(&once, &config()).unwrap(), once);
+}
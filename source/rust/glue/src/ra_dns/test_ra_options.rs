@@ -0,0 +1,76 @@
+use {
+    crate::ra_dns::{
+        options::RaOptions,
+        ra_prefix,
+        test_util::PAYLOAD_RA,
+    },
+    std::net::Ipv6Addr,
+};
+
+#[test]
+fn test_ra_options() {
+    let data = [
+        // Source link-layer address
+        0x01,
+        0x01,
+        0x48,
+        0x2e,
+        0x72,
+        0x63,
+        0x7d,
+        0x10,
+        // MTU
+        0x05,
+        0x01,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x05,
+        0xdc,
+    ];
+    let got = RaOptions::new(&data).collect::<Vec<_>>();
+    assert_eq!(got, vec![Some((1, &data[0 .. 8])), Some((5, &data[8 .. 16]))]);
+}
+
+#[test]
+fn test_ra_options_empty() {
+    assert_eq!(RaOptions::new(&[]).count(), 0);
+}
+
+#[test]
+fn test_ra_options_zero_length() {
+    // Would never advance
+    let data = [0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x01];
+    assert_eq!(RaOptions::new(&data).collect::<Vec<_>>(), vec![None]);
+}
+
+#[test]
+fn test_ra_options_truncated() {
+    // Second option claims 16 bytes, only 4 remain
+    let data = [0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x19, 0x02, 0x00, 0x00];
+    assert_eq!(RaOptions::new(&data).collect::<Vec<_>>(), vec![Some((1, &data[0 .. 8])), None]);
+
+    // No length byte
+    assert_eq!(RaOptions::new(&[0x01]).collect::<Vec<_>>(), vec![None]);
+}
+
+#[test]
+fn test_ra_prefix() {
+    assert_eq!(ra_prefix(PAYLOAD_RA), None);
+    let mut payload = PAYLOAD_RA.to_vec();
+    payload.extend([
+        // Prefix information, /64, on-link + autonomous
+        0x03,
+        0x04,
+        0x40,
+        0xc0,
+    ]);
+    payload.extend(86400u32.to_be_bytes());
+    payload.extend(14400u32.to_be_bytes());
+    payload.extend([0, 0, 0, 0]);
+    payload.extend(Ipv6Addr::new(0x2001, 0xdb8, 1, 2, 0, 0, 0, 0).octets());
+    let len = (payload.len() - 40) as u16;
+    payload[4 .. 6].copy_from_slice(&len.to_be_bytes());
+    assert_eq!(ra_prefix(&payload), Some((Ipv6Addr::new(0x2001, 0xdb8, 1, 2, 0, 0, 0, 0), 64)));
+}
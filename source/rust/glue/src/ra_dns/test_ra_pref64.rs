@@ -0,0 +1,203 @@
+use {
+    crate::ra_dns::{
+        icmpv6_udp_checksum,
+        list_options,
+        modify,
+        ModifyConfig,
+        Pref64,
+    },
+    std::net::Ipv6Addr,
+};
+
+/// An RA (router lifetime 1800) with an RDNSS option and a PREF64 option for
+/// `2001:db8::/64`, lifetime 600.
+const PAYLOAD_RA: &[u8] = &[
+    // IPv6
+    0x6b,
+    0x80,
+    0x00,
+    0x00,
+    // Length
+    0x00,
+    0x38,
+    // Next header, hop limit
+    0x3a,
+    0xff,
+    // Source
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x4a,
+    0x2e,
+    0x72,
+    0xff,
+    0xfe,
+    0x63,
+    0x7d,
+    0x10,
+    // Destination
+    0xff,
+    0x02,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x01,
+    // ICMPv6 RA
+    0x86,
+    0x00,
+    0x00,
+    0x00,
+    0x40,
+    0x80,
+    0x07,
+    0x08,
+    0x00,
+    0x00,
+    0x93,
+    0xe0,
+    0x00,
+    0x00,
+    0x27,
+    0x10,
+    // RDNSS
+    0x19,
+    0x03,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x02,
+    0x58,
+    0xfd,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x53,
+    // PREF64, scaled lifetime 75, PLC 1 (/64)
+    0x26,
+    0x02,
+    0x02,
+    0x59,
+    0x20,
+    0x01,
+    0x0d,
+    0xb8,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+];
+
+/// Our PREF64 option, after the RDNSS with one address.
+const PREF64_OFFSET: usize = 40 + 16 + 24;
+
+fn well_known() -> Pref64 {
+    return Pref64::new(Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0), 96).unwrap();
+}
+
+fn payload_ra() -> Vec<u8> {
+    let mut packet = PAYLOAD_RA.to_vec();
+    let checksum = icmpv6_udp_checksum(&packet).unwrap();
+    packet[42 .. 44].copy_from_slice(&checksum);
+    return packet;
+}
+
+#[test]
+fn test_ra_pref64_replace() {
+    let got = modify(&payload_ra(), &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        pref64: Some(well_known()),
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+    assert_eq!(list_options(&got, &ModifyConfig::default()), Some(vec![25, 38]));
+    assert_eq!(got.len(), PREF64_OFFSET + 16);
+    assert_eq!(
+        &got[PREF64_OFFSET..],
+        &[
+            // Type, length
+            0x26,
+            0x02,
+            // Scaled lifetime 225 (1800s), PLC 0 (/96)
+            0x07,
+            0x08,
+            // 64:ff9b::
+            0x00,
+            0x64,
+            0xff,
+            0x9b,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+        ],
+    );
+}
+
+#[test]
+fn test_ra_pref64_truncated_prefix() {
+    let got = modify(&payload_ra(), &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        pref64: Pref64::new(Ipv6Addr::new(0x2001, 0xdb8, 0x1234, 0x5678, 0, 0, 0, 0xffff), 40),
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+
+    // Scaled lifetime 225, PLC 4 (/40), only the first 5 bytes of the prefix
+    assert_eq!(&got[PREF64_OFFSET + 2..], &[0x07, 0x0c, 0x20, 0x01, 0x0d, 0xb8, 0x12, 0, 0, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn test_ra_pref64_kept() {
+    let got = modify(&payload_ra(), &ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(icmpv6_udp_checksum(&got), Some([0, 0]));
+    assert_eq!(list_options(&got, &ModifyConfig::default()), Some(vec![25, 38]));
+}
+
+#[test]
+fn test_ra_pref64_lengths() {
+    let prefix = Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0);
+    for len in [32, 40, 48, 56, 64, 96] {
+        assert!(Pref64::new(prefix, len).is_some());
+    }
+    for len in [0, 24, 72, 128] {
+        assert_eq!(Pref64::new(prefix, len), None);
+    }
+}
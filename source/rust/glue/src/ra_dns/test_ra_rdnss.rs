@@ -0,0 +1,51 @@
+use {
+    crate::ra_dns::build_rdnss_option,
+    std::net::Ipv6Addr,
+};
+
+#[test]
+fn test_build_rdnss_option_one() {
+    let got = build_rdnss_option(&[Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53)], 1800);
+    assert_eq!(got, vec![
+        // Type
+        25,
+        // Length (3 * 8)
+        3,
+        // Reserved
+        0x00,
+        0x00,
+        // Lifetime
+        0x00,
+        0x00,
+        0x07,
+        0x08,
+        // Address
+        0x20,
+        0x01,
+        0x0d,
+        0xb8,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x53,
+    ]);
+}
+
+#[test]
+fn test_build_rdnss_option_two() {
+    let a = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+    let b = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2);
+    let got = build_rdnss_option(&[a, b], u32::MAX);
+    assert_eq!(got.len(), 40);
+    assert_eq!(&got[.. 8], &[25, 5, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff]);
+    assert_eq!(got[8 .. 24], a.octets());
+    assert_eq!(got[24 .. 40], b.octets());
+}
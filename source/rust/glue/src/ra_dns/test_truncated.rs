@@ -0,0 +1,25 @@
+use crate::ra_dns::{
+    modify,
+    test_util::{
+        PAYLOAD_DHCP,
+        PAYLOAD_RA,
+    },
+    ModifyConfig,
+    ModifyError,
+};
+
+fn config() -> ModifyConfig {
+    return ModifyConfig {
+        dns_ips: vec!["fd00::53".parse().unwrap()],
+        ..Default::default()
+    };
+}
+
+#[test]
+fn test_truncated_capture() {
+    // Captured short of the payload length, ex: with a small queue copy range
+    for packet in [PAYLOAD_RA, PAYLOAD_DHCP] {
+        assert!(modify(packet, &config()).is_ok());
+        assert_eq!(modify(&packet[.. packet.len() - 8], &config()), Err(ModifyError::Truncated));
+    }
+}
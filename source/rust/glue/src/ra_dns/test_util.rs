@@ -0,0 +1,31 @@
+//! Packet builders shared by the tests.
+use {
+    crate::ra_dns::icmpv6_udp_checksum,
+    std::net::Ipv6Addr,
+};
+
+/// An RA from `fe80::4a2e:72ff:fe63:7d10` with source link-layer address, MTU
+/// (1500) and RDNSS (`2001:db8::53`) options.
+pub const PAYLOAD_RA: &[u8] = include_bytes!("testdata/ra1");
+
+/// A DHCPv6 Reply with DNS and domain search list options.
+pub const PAYLOAD_DHCP: &[u8] = include_bytes!("testdata/dhcp1");
+
+/// A source link-layer address option.
+pub const OPT_SLLA: &[u8] = &[0x01, 0x01, 0x48, 0x2e, 0x72, 0x63, 0x7d, 0x10];
+
+/// An RA from `fe80::1` to all nodes with `options`, with a correct checksum.
+pub fn ra(options: &[&[u8]]) -> Vec<u8> {
+    let mut out = vec![0x60, 0, 0, 0, 0, 0, 0x3a, 0xff];
+    out.extend(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1).octets());
+    out.extend(Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1).octets());
+    out.extend([0x86, 0x00, 0x00, 0x00, 0x40, 0x00, 0x07, 0x08, 0, 0, 0, 0, 0, 0, 0, 0]);
+    for option in options {
+        out.extend_from_slice(option);
+    }
+    let payload_len = (out.len() - 40) as u16;
+    out[4 .. 6].copy_from_slice(&payload_len.to_be_bytes());
+    let checksum = icmpv6_udp_checksum(&out).unwrap();
+    out[42 .. 44].copy_from_slice(&checksum);
+    return out;
+}
@@ -0,0 +1,44 @@
+use {
+    crate::ra_dns::{
+        icmpv6_udp_checksum,
+        modify,
+        test_util::PAYLOAD_RA,
+        ModifyConfig,
+        ModifyError,
+    },
+    std::net::Ipv6Addr,
+};
+
+fn config(verify_checksum: bool) -> ModifyConfig {
+    return ModifyConfig {
+        dns_ips: vec![Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        verify_checksum,
+        ..Default::default()
+    };
+}
+
+#[test]
+fn test_verify_checksum_good() {
+    assert_eq!(icmpv6_udp_checksum(PAYLOAD_RA), Some([0, 0]));
+    let got = modify(PAYLOAD_RA, &config(true)).unwrap();
+    assert_eq!(got, modify(PAYLOAD_RA, &config(false)).unwrap());
+}
+
+#[test]
+fn test_verify_checksum_corrupt() {
+    // Flip a bit in the router lifetime
+    let mut payload = PAYLOAD_RA.to_vec();
+    payload[40 + 6] ^= 0x01;
+    assert_eq!(modify(&payload, &config(true)), Err(ModifyError::BadChecksum));
+
+    // Otherwise it's rewritten
+    assert!(modify(&payload, &config(false)).is_ok());
+}
+
+#[test]
+fn test_verify_checksum_not_applicable() {
+    // Only packets to rewrite are checked
+    let mut payload = PAYLOAD_RA.to_vec();
+    payload[40] = 135;
+    assert_eq!(modify(&payload, &config(true)), Err(ModifyError::NotApplicable));
+}
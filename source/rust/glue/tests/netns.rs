@@ -0,0 +1,320 @@
+//! End to end test of `mangle_ip_configure` with a real netfilter queue. Needs root,
+//! `ip` and `nft`, so it's ignored by default - run with
+//! `sudo cargo test --test netns -- --ignored`.
+//!
+//! The test moves itself into a fresh network namespace with a veth pair, queues
+//! outgoing RAs to the daemon, sends an RA out one end and captures it on the other.
+use {
+    glue::{
+        command::run,
+        ra_dns::{
+            list_options,
+            ModifyConfig,
+        },
+    },
+    std::{
+        mem::{
+            size_of,
+            zeroed,
+        },
+        net::Ipv6Addr,
+        os::fd::{
+            AsRawFd,
+            FromRawFd,
+            OwnedFd,
+        },
+        process::{
+            Child,
+            Command,
+        },
+        thread::sleep,
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+};
+
+const QUEUE: &str = "7";
+const IFACE_IP: &str = "2001:db8:1::1";
+
+/// Kill the daemon even if the test fails.
+struct Daemon(Child);
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        _ = self.0.kill();
+        _ = self.0.wait();
+    }
+}
+
+fn cmd(args: &[&str]) {
+    run(Command::new(args[0]).args(&args[1..])).unwrap();
+}
+
+fn socket(domain: i32, type_: i32, protocol: i32) -> OwnedFd {
+    let fd = unsafe {
+        libc::socket(domain, type_, protocol)
+    };
+    assert!(fd >= 0, "{}", std::io::Error::last_os_error());
+    return unsafe {
+        OwnedFd::from_raw_fd(fd)
+    };
+}
+
+fn if_index(name: &str) -> u32 {
+    let name = std::ffi::CString::new(name).unwrap();
+    let index = unsafe {
+        libc::if_nametoindex(name.as_ptr())
+    };
+    assert!(index != 0);
+    return index;
+}
+
+/// ICMPv6 RA with an RDNSS option for 2001:db8::99. The kernel fills in the checksum
+/// for raw ICMPv6 sockets.
+fn ra() -> Vec<u8> {
+    let mut out = vec![
+        // RA
+        134,
+        0,
+        0,
+        0,
+        64,
+        0,
+        0x07,
+        0x08,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        // RDNSS, length 3, lifetime 3600
+        25,
+        3,
+        0,
+        0,
+        0x00,
+        0x00,
+        0x0e,
+        0x10,
+    ];
+    out.extend("2001:db8::99".parse::<Ipv6Addr>().unwrap().octets());
+    return out;
+}
+
+fn send_ra(sock: &OwnedFd, index: u32) {
+    let mut addr: libc::sockaddr_in6 = unsafe {
+        zeroed()
+    };
+    addr.sin6_family = libc::AF_INET6 as u16;
+    addr.sin6_addr.s6_addr = "ff02::1".parse::<Ipv6Addr>().unwrap().octets();
+    addr.sin6_scope_id = index;
+    let ra = ra();
+    let res = unsafe {
+        libc::sendto(
+            sock.as_raw_fd(),
+            ra.as_ptr() as *const libc::c_void,
+            ra.len(),
+            0,
+            &addr as *const libc::sockaddr_in6 as *const libc::sockaddr,
+            size_of::<libc::sockaddr_in6>() as u32,
+        )
+    };
+    assert!(res >= 0, "{}", std::io::Error::last_os_error());
+}
+
+/// Receive the next IPv6 RA on the packet socket, returning the IPv6 packet.
+fn recv_ra(sock: &OwnedFd) -> Option<Vec<u8>> {
+    let mut buf = [0u8; 2048];
+    let res = unsafe {
+        libc::recv(sock.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0)
+    };
+    if res < 0 {
+        return None;
+    }
+    let packet = &buf[..res as usize];
+    if packet.len() < 41 || packet[6] != 58 || packet[40] != 134 {
+        return None;
+    }
+    return Some(packet.to_vec());
+}
+
+/// Move this thread (and daemons, which inherit it) into a new namespace with a veth
+/// pair, queueing RAs sent out `veth0`.
+fn setup_netns() {
+    assert_eq!(unsafe {
+        libc::unshare(libc::CLONE_NEWNET)
+    }, 0, "{}", std::io::Error::last_os_error());
+    cmd(&["ip", "link", "set", "lo", "up"]);
+    cmd(&["ip", "link", "add", "veth0", "type", "veth", "peer", "name", "veth1"]);
+    cmd(&["ip", "link", "set", "veth0", "up"]);
+    cmd(&["ip", "link", "set", "veth1", "up"]);
+    cmd(&["ip", "addr", "add", &format!("{}/64", IFACE_IP), "dev", "veth0", "nodad"]);
+    cmd(&["nft", "add", "table", "ip6", "spagh_test"]);
+    cmd(&["nft", "add", "chain", "ip6", "spagh_test", "out", "{ type filter hook output priority 0; }"]);
+    cmd(
+        &[
+            "nft",
+            "add",
+            "rule",
+            "ip6",
+            "spagh_test",
+            "out",
+            "mark",
+            "0",
+            "icmpv6",
+            "type",
+            "nd-router-advert",
+            "queue",
+            "num",
+            QUEUE,
+        ],
+    );
+}
+
+fn spawn_daemon(extra_args: &[&str]) -> Daemon {
+    return spawn_daemon_with(&[&["--interface", "veth0", "--recheck-period", "1"], extra_args].concat());
+}
+
+fn spawn_daemon_with(args: &[&str]) -> Daemon {
+    return Daemon(
+        Command::new(env!("CARGO_BIN_EXE_mangle_ip_configure"))
+            .args(["--nf-queue", QUEUE, "--nf-mark", "2"])
+            .args(args)
+            .spawn()
+            .unwrap(),
+    );
+}
+
+/// A packet socket capturing IPv6 on `veth1`, the other end of the pair.
+fn capture_socket() -> OwnedFd {
+    let capture = socket(libc::AF_PACKET, libc::SOCK_DGRAM, (libc::ETH_P_IPV6 as u16).to_be() as i32);
+    let mut bind_addr: libc::sockaddr_ll = unsafe {
+        zeroed()
+    };
+    bind_addr.sll_family = libc::AF_PACKET as u16;
+    bind_addr.sll_protocol = (libc::ETH_P_IPV6 as u16).to_be();
+    bind_addr.sll_ifindex = if_index("veth1") as i32;
+    assert_eq!(unsafe {
+        libc::bind(
+            capture.as_raw_fd(),
+            &bind_addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+            size_of::<libc::sockaddr_ll>() as u32,
+        )
+    }, 0);
+    let timeout = libc::timeval {
+        tv_sec: 0,
+        tv_usec: 200_000,
+    };
+    assert_eq!(unsafe {
+        libc::setsockopt(
+            capture.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_RCVTIMEO,
+            &timeout as *const libc::timeval as *const libc::c_void,
+            size_of::<libc::timeval>() as u32,
+        )
+    }, 0);
+    return capture;
+}
+
+/// Keep sending until the daemon is up and has found the interface IP (earlier RAs
+/// are dropped), returning the first RA to come out.
+fn wait_for_rewrite(sender: &OwnedFd, capture: &OwnedFd) -> Vec<u8> {
+    let veth0 = if_index("veth0");
+    let start = Instant::now();
+    loop {
+        assert!(start.elapsed() < Duration::from_secs(20), "Timed out waiting for rewritten RA");
+        send_ra(sender, veth0);
+        if let Some(packet) = recv_ra(capture) {
+            return packet;
+        }
+        sleep(Duration::from_millis(300));
+    }
+}
+
+#[test]
+#[ignore]
+fn test_netns_rewrite_ra() {
+    setup_netns();
+    let _daemon = spawn_daemon(&[]);
+    let capture = capture_socket();
+    let sender = socket(libc::AF_INET6, libc::SOCK_RAW, libc::IPPROTO_ICMPV6);
+    let got = wait_for_rewrite(&sender, &capture);
+
+    // The RDNSS now has the interface IP, and the packet was re-marked and not queued
+    // again
+    assert_eq!(list_options(&got, &ModifyConfig::default()), Some(vec![25]));
+    assert_eq!(&got[40 + 16 + 8 .. 40 + 16 + 24], &IFACE_IP.parse::<Ipv6Addr>().unwrap().octets());
+    assert_eq!(got[40 + 5] & 0x40, 0x40);
+}
+
+#[test]
+#[ignore]
+fn test_netns_max_packets() {
+    const DNS_IP: &str = "2001:db8::53";
+    setup_netns();
+
+    // Static IP, so the first RA the daemon gets is rewritten
+    let mut daemon = spawn_daemon_with(&["--dns-ip", DNS_IP, "--max-packets", "1"]);
+    let capture = capture_socket();
+    let sender = socket(libc::AF_INET6, libc::SOCK_RAW, libc::IPPROTO_ICMPV6);
+    let got = wait_for_rewrite(&sender, &capture);
+    assert_eq!(&got[40 + 16 + 8 .. 40 + 16 + 24], &DNS_IP.parse::<Ipv6Addr>().unwrap().octets());
+
+    // Then it exits cleanly
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = daemon.0.try_wait().unwrap() {
+            break status;
+        }
+        assert!(start.elapsed() < Duration::from_secs(10), "Daemon didn't exit after --max-packets");
+        sleep(Duration::from_millis(100));
+    };
+    assert!(status.success());
+}
+
+/// Compare RAs rewritten per second with `--verdict repeat` and `--verdict accept`,
+/// one at a time (send, wait for it to come out). Run with `--nocapture` to see the
+/// results.
+#[test]
+#[ignore]
+fn test_netns_verdict_throughput() {
+    const COUNT: usize = 2000;
+    setup_netns();
+    let capture = capture_socket();
+    let sender = socket(libc::AF_INET6, libc::SOCK_RAW, libc::IPPROTO_ICMPV6);
+    let veth0 = if_index("veth0");
+    for verdict in ["repeat", "accept"] {
+        let daemon = spawn_daemon(&["--verdict", verdict]);
+        wait_for_rewrite(&sender, &capture);
+
+        // Drain any RAs from waiting
+        while recv_ra(&capture).is_some() { }
+        let start = Instant::now();
+        let mut rewritten = 0;
+        for _ in 0 .. COUNT {
+            send_ra(&sender, veth0);
+            if let Some(got) = recv_ra(&capture) {
+                if got.get(40 + 16 + 8 .. 40 + 16 + 24) == Some(&IFACE_IP.parse::<Ipv6Addr>().unwrap().octets()[..]) {
+                    rewritten += 1;
+                }
+            }
+        }
+        let elapsed = start.elapsed();
+        println!(
+            "--verdict {}: {} of {} rewritten in {:?}, {:.0}/s",
+            verdict,
+            rewritten,
+            COUNT,
+            elapsed,
+            rewritten as f64 / elapsed.as_secs_f64()
+        );
+        assert_eq!(rewritten, COUNT);
+        drop(daemon);
+    }
+}